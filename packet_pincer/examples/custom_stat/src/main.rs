@@ -0,0 +1,199 @@
+//! Computes a custom statistic on each flow alongside the built-in ones.
+//!
+//! Usage: `cargo run --manifest-path examples/custom_stat/Cargo.toml -- <directory>`
+
+use packet_pincer::{
+    etherparse, pcap, FlowGroup, FlowStat, FlowStatistics, FlowTimes, FragmentReasemblyInformation,
+    PacketCapture, PacketOrigin, StatSettings, StreamChunk, TransportFlow, TransportFlowIdentifier,
+};
+use std::io::{BufWriter, Error, Write};
+use std::path::PathBuf;
+
+/// Number of packets on each direction whose transport payload is smaller than
+/// `SMALL_PAYLOAD_BYTES`
+#[derive(Debug, Clone)]
+struct SmallPayloads {
+    forward_count: u32,
+    backward_count: u32,
+}
+
+impl SmallPayloads {
+    const SMALL_PAYLOAD_BYTES: usize = 64;
+
+    fn is_small(sliced_packet: &etherparse::SlicedPacket) -> bool {
+        let payload_len = match &sliced_packet.transport {
+            Some(etherparse::TransportSlice::Tcp(s)) => s.payload().len(),
+            Some(etherparse::TransportSlice::Udp(s)) => s.payload().len(),
+            _ => return false,
+        };
+        payload_len < Self::SMALL_PAYLOAD_BYTES
+    }
+}
+
+impl FlowStat for SmallPayloads {
+    fn from_packet(
+        _settings: &StatSettings,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        SmallPayloads {
+            forward_count: u32::from(Self::is_small(sliced_packet)),
+            backward_count: 0,
+        }
+    }
+    fn include(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        if !Self::is_small(sliced_packet) {
+            return;
+        }
+        if identifier.is_forward(sliced_packet) {
+            self.forward_count += 1;
+        } else {
+            self.backward_count += 1;
+        }
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        write!(writer, "forward_small_payload_count,")?;
+        write!(writer, "backward_small_payload_count,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        write!(writer, "{},", self.forward_count)?;
+        write!(writer, "{},", self.backward_count)?;
+        Ok(())
+    }
+}
+
+/// The built-in feature groups followed by the custom statistic
+#[derive(Debug, Clone)]
+struct ExtendedStatistics {
+    built_in: FlowStatistics,
+    small_payloads: SmallPayloads,
+}
+
+impl FlowStat for ExtendedStatistics {
+    fn from_packet(
+        settings: &StatSettings,
+        identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        ExtendedStatistics {
+            built_in: FlowStatistics::from_packet(
+                settings,
+                identifier,
+                flow_times,
+                packet_header,
+                sliced_packet,
+                reasembly_information,
+            ),
+            small_payloads: SmallPayloads::from_packet(
+                settings,
+                identifier,
+                flow_times,
+                packet_header,
+                sliced_packet,
+                reasembly_information,
+            ),
+        }
+    }
+    fn include(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        self.built_in.include(
+            identifier,
+            flow_times,
+            packet_header,
+            sliced_packet,
+            reasembly_information,
+        );
+        self.small_payloads.include(
+            identifier,
+            flow_times,
+            packet_header,
+            sliced_packet,
+            reasembly_information,
+        );
+    }
+    fn uses_streams(settings: &StatSettings) -> bool {
+        FlowStatistics::uses_streams(settings)
+    }
+    fn include_stream(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        chunk: &StreamChunk,
+    ) {
+        self.built_in.include_stream(identifier, flow_times, chunk);
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        settings: &StatSettings,
+    ) -> Result<(), Error> {
+        FlowStatistics::write_csv_header(writer, settings)?;
+        SmallPayloads::write_csv_header(writer, settings)?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        self.built_in.write_csv_value(writer, flow_times)?;
+        self.small_payloads.write_csv_value(writer, flow_times)?;
+        Ok(())
+    }
+}
+
+fn main() {
+    let directory: PathBuf = std::env::args_os()
+        .nth(1)
+        .expect("Expected a directory with captures")
+        .into();
+
+    let mut capture = PacketCapture::from_directory(&directory);
+    let mut flows = FlowGroup::<ExtendedStatistics>::with_feature_set();
+    let mut writer = BufWriter::new(std::io::stdout());
+
+    TransportFlow::<ExtendedStatistics>::write_csv_header(
+        &mut writer,
+        flows.stat_settings(),
+        false,
+        false,
+    )
+    .expect("Could not write header");
+
+    let mut process_packet =
+        |_origin: PacketOrigin, link_type: pcap::Linktype, packet: &pcap::Packet<'_>| {
+            let _ = flows.include(link_type, packet);
+        };
+    while capture.try_process_next(&mut process_packet) {}
+
+    while let Some(flow) = flows.pop_oldest_transport_flow() {
+        flow.write_csv_value(&mut writer, false)
+            .expect("Could not write flow");
+    }
+}
@@ -12,7 +12,7 @@ use chrono::{DateTime, TimeDelta, Utc};
 use etherparse::IpNumber;
 use serde::Deserialize;
 
-use crate::TransportFlow;
+use crate::{stats::FlowStat, TransportFlow};
 
 #[derive(Debug, Deserialize)]
 struct GroundTruthRecord {
@@ -59,7 +59,7 @@ impl GroundTruth {
     }
 
     /// Try finding a given label that matches the flow
-    pub fn find_label(&self, flow: &TransportFlow) -> Option<Rc<str>> {
+    pub fn find_label<S: FlowStat>(&self, flow: &TransportFlow<S>) -> Option<Rc<str>> {
         match self.flows.get(&HostPair::from(
             flow.identifier.source_ip,
             flow.identifier.dest_ip,
@@ -7,10 +7,30 @@ mod packet_capture;
 mod packet_flow;
 mod packet_parse;
 mod stats;
+mod stream_reassembly;
 
 pub use crate::ground_truth::GroundTruth;
 pub use crate::packet_capture::PacketCapture;
 pub use crate::packet_capture::PacketOrigin;
 pub use crate::packet_flow::FlowGroup;
+pub use crate::packet_flow::FragmentReasemblyInformation;
 pub use crate::packet_flow::TransportFlow;
 pub use crate::packet_parse::ParseError;
+pub use crate::packet_parse::TransportFlowIdentifier;
+pub use crate::stats::extract_byte_count;
+pub use crate::stats::extract_packet_count;
+pub use crate::stats::AppSignatures;
+pub use crate::stats::FeatureGroup;
+pub use crate::stats::FlowStat;
+pub use crate::stats::FlowStatistics;
+pub use crate::stats::FlowTimes;
+pub use crate::stats::OsSignatures;
+pub use crate::stats::OutputProfile;
+pub use crate::stats::RunningStat;
+pub use crate::stats::StatSettings;
+pub use crate::stream_reassembly::DirectionStream;
+pub use crate::stream_reassembly::StreamChunk;
+pub use crate::stream_reassembly::TcpStreamReassembler;
+
+pub use etherparse;
+pub use pcap;
@@ -2,7 +2,10 @@ use chrono::TimeDelta;
 use clap::{Parser, Subcommand};
 use env_logger::Env;
 use log::{error, info};
-use packet_pincer::{FlowGroup, GroundTruth, PacketCapture, PacketOrigin, TransportFlow};
+use packet_pincer::{
+    AppSignatures, FeatureGroup, FlowGroup, FlowStatistics, GroundTruth, OsSignatures,
+    OutputProfile, PacketCapture, PacketOrigin, StatSettings, TransportFlow,
+};
 
 use std::{
     fs::File,
@@ -104,6 +107,97 @@ struct Settings {
     #[arg(short, long)]
     pub ground_truth_csv: Option<PathBuf>,
 
+    /// Comma separated list of packet counts. If set, instead of a record per
+    /// closed flow, a record of each flow is generated as soon as it reaches
+    /// each of the packet counts. Flows closed before reaching them generate
+    /// the remaining records when closed
+    #[arg(short, long, value_delimiter = ',', value_name = "PACKET_COUNTS")]
+    pub early_records: Vec<u32>,
+
+    /// Comma separated list of feature groups to compute. If not set, the
+    /// groups protocols, packet_count, byte_count, interarrival, tcp_flags,
+    /// transport and activity are computed. The rest of available groups are
+    /// packet_sequence, payload_content, histograms, bulk_transfer,
+    /// tcp_analysis, tcp_connection, tcp_options, ip_header, os_fingerprint,
+    /// dns, tls, http, mqtt, coap, modbus, dnp3, reflection, ssh, sip, rtp and
+    /// app_protocol. The cicflowmeter group is only available through the
+    /// cicflowmeter output profile
+    #[arg(short, long, value_delimiter = ',', value_name = "FEATURE_GROUPS")]
+    pub features: Vec<FeatureGroup>,
+
+    /// Column set and semantics of the records. The cicflowmeter profile
+    /// writes the columns of CICFlowMeter and ignores the selected feature
+    /// groups
+    #[arg(short, long, default_value_t = OutputProfile::Native)]
+    pub output_profile: OutputProfile,
+
+    /// Number of first packets of each flow whose sizes, inter arrival times
+    /// and TCP flags are written. Only used by the packet_sequence group
+    #[arg(long, default_value_t = 20, value_name = "PACKETS")]
+    pub sequence_length: usize,
+
+    /// Comma separated list of percentiles, between 0 and 100, to estimate on
+    /// the packet sizes and inter arrival times (e.g. 25,50,75,95). Only
+    /// written by the byte_count and interarrival groups
+    #[arg(
+        short,
+        long,
+        value_delimiter = ',',
+        value_name = "PERCENTILES",
+        value_parser = clap::value_parser!(u8).range(0..=100)
+    )]
+    pub percentiles: Vec<u8>,
+
+    /// Write the skewness and kurtosis of the packet sizes and inter arrival
+    /// times. Only written by the byte_count and interarrival groups
+    #[arg(short = 'm', long)]
+    pub higher_moments: bool,
+
+    /// Comma separated list of the edges of the bins of the packet sizes
+    /// histograms, in bytes. Only used by the histograms group
+    #[arg(long, value_delimiter = ',', value_name = "BYTES")]
+    pub packet_bytes_bins: Vec<u64>,
+
+    /// Comma separated list of the edges of the bins of the inter arrival
+    /// times histograms, in microseconds. Only used by the histograms group
+    #[arg(long, value_delimiter = ',', value_name = "MICROSECONDS")]
+    pub inter_arrival_bins: Vec<u64>,
+
+    /// Minimum number of packets with payload on the same direction that
+    /// form a bulk, where 0 is the same as 1. Only used by the bulk_transfer
+    /// group
+    #[arg(long, default_value_t = 4, value_name = "PACKETS")]
+    pub bulk_min_packets: u32,
+
+    /// Maximum gap between the packets of a bulk, in microseconds. Only used
+    /// by the bulk_transfer group
+    #[arg(long, default_value_t = 1_000_000, value_name = "MICROSECONDS")]
+    pub bulk_timeout: i64,
+
+    /// Maximum gap between the packets of a subflow, in microseconds. Only
+    /// used by the bulk_transfer group
+    #[arg(long, default_value_t = 1_000_000, value_name = "MICROSECONDS")]
+    pub subflow_timeout: i64,
+
+    /// File of signatures in the p0f v3 format used to guess the operating
+    /// systems of the clients and servers, instead of the bundled ones of
+    /// assets/os_signatures.fp. Only used by the os_fingerprint group
+    #[arg(long, value_name = "FILE")]
+    pub os_signatures: Option<PathBuf>,
+
+    /// File of signatures of the first payloads used to identify the
+    /// application protocols, tried before the built-in ones. See
+    /// assets/app_signatures.rules for the format. Only used by the
+    /// app_protocol group
+    #[arg(long, value_name = "FILE")]
+    pub app_signatures: Option<PathBuf>,
+
+    /// Maximum bytes received out of order buffered on each TCP flow while
+    /// reassembling its streams. Only used by the groups that analyze the
+    /// application layer
+    #[arg(long, default_value_t = 65536, value_name = "BYTES")]
+    pub stream_buffer_limit: usize,
+
     #[command(subcommand)]
     pub analysis: Commands,
 }
@@ -142,13 +236,23 @@ fn create_packet_capture_from_settings(command: &Commands) -> PacketCapture {
     }
 }
 
-fn create_csv_output(mut path: PathBuf, label_column: bool) -> Option<BufWriter<Box<dyn Write>>> {
+fn create_csv_output(
+    mut path: PathBuf,
+    stat_settings: &StatSettings,
+    packet_threshold_column: bool,
+    label_column: bool,
+) -> Option<BufWriter<Box<dyn Write>>> {
     let timestamp = chrono::offset::Utc::now().timestamp_millis();
     path.set_extension(format!("{}.csv", timestamp));
     let file = File::create(path).expect("Unable to create file");
     let writer: Box<dyn Write> = Box::new(file);
     let mut w = BufWriter::new(writer);
-    let _ = TransportFlow::write_csv_header(&mut w, label_column);
+    let _ = TransportFlow::<FlowStatistics>::write_csv_header(
+        &mut w,
+        stat_settings,
+        packet_threshold_column,
+        label_column,
+    );
     Some(w)
 }
 
@@ -180,8 +284,10 @@ fn evaluate_packets(
     };
 
     // Init csv writer
+    let stat_settings = flows.stat_settings().clone();
+    let early_records = flows.generates_early_records();
     let mut csv_writer: Option<BufWriter<Box<dyn Write>>> = match (&csv_output_base, stdout_output) {
-        (Some(path), _) => create_csv_output(path.clone(), ground_truth.is_some()),
+        (Some(path), _) => create_csv_output(path.clone(), &stat_settings, early_records, ground_truth.is_some()),
         (None, true) => Some(BufWriter::new( Box::new(std::io::stdout()))), 
         (None, false) => None
     };
@@ -195,7 +301,7 @@ fn evaluate_packets(
             if MAX_LINES_FOR_CSV_FILE <= execution_stats.current_lines_written && csv_output_base.as_ref().is_some() {
                 csv_writer = csv_output_base
                     .as_ref()
-                    .and_then(|path| create_csv_output(path.clone(), ground_truth.is_some()));
+                    .and_then(|path| create_csv_output(path.clone(), &stat_settings, early_records, ground_truth.is_some()));
                 execution_stats.current_lines_written = 0;
             }
         }
@@ -282,9 +388,53 @@ fn main() {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
     let settings = Settings::parse();
 
+    if settings.output_profile != OutputProfile::CicFlowMeter
+        && settings.features.contains(&FeatureGroup::CicFlowMeter)
+    {
+        error!("The cicflowmeter feature group requires the cicflowmeter output profile");
+        exit(2);
+    }
+
     let termination_channel = create_termination_channel();
     let mut execution_stats = ExecutionStats::default();
-    let mut flows = FlowGroup::new();
+    let mut stat_settings = StatSettings::new(&settings.features)
+        .with_output_profile(settings.output_profile)
+        .with_sequence_length(settings.sequence_length)
+        .with_percentiles(settings.percentiles)
+        .with_higher_moments(settings.higher_moments)
+        .with_bulk_thresholds(
+            settings.bulk_min_packets,
+            TimeDelta::microseconds(settings.bulk_timeout),
+        )
+        .with_subflow_timeout(TimeDelta::microseconds(settings.subflow_timeout))
+        .with_stream_buffer_limit(settings.stream_buffer_limit);
+    if !settings.packet_bytes_bins.is_empty() {
+        stat_settings = stat_settings.with_packet_bytes_bin_edges(settings.packet_bytes_bins);
+    }
+    if !settings.inter_arrival_bins.is_empty() {
+        stat_settings = stat_settings.with_inter_arrival_bin_edges(settings.inter_arrival_bins);
+    }
+    if let Some(path) = settings.os_signatures {
+        match OsSignatures::from_file(path) {
+            Ok(os_signatures) => stat_settings = stat_settings.with_os_signatures(os_signatures),
+            Err(err) => {
+                error!("Error loading OS signatures: {}", err);
+                exit(2);
+            }
+        }
+    }
+    if let Some(path) = settings.app_signatures {
+        match AppSignatures::from_file(path) {
+            Ok(app_signatures) => stat_settings = stat_settings.with_app_signatures(app_signatures),
+            Err(err) => {
+                error!("Error loading application signatures: {}", err);
+                exit(2);
+            }
+        }
+    }
+    let mut flows = FlowGroup::new()
+        .with_stat_settings(stat_settings)
+        .with_early_records(settings.early_records);
     let mut packet_capture = create_packet_capture_from_settings(&settings.analysis);
     let ground_truth = match settings.ground_truth_csv {
         Some(path) => match GroundTruth::from_file(path) {
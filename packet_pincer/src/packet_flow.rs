@@ -3,56 +3,103 @@ use crate::{
         self, try_parse_packet, FlowIdentifier, FragmentationInformation, NetworkFlowIdentifier,
         ParseError, TransportFlowIdentifier,
     },
-    stats::{FlowStat, FlowStatistics, FlowTimes},
+    stats::{CicFlowMeter, FlowStat, FlowStatistics, FlowTimes, OutputProfile, StatSettings},
+    stream_reassembly::TcpStreamReassembler,
 };
 use chrono::{DateTime, TimeDelta, Utc};
 use etherparse::PacketBuilder;
 use priority_queue::PriorityQueue;
 use std::{
     cmp::{min, Reverse},
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     io::{BufWriter, Error, Write},
     rc::Rc,
     vec,
 };
 
-/// The commulative information of the flow of information between two hosts
-#[derive(Debug)]
-pub struct TransportFlow {
+/// The commulative information of the flow of information between two hosts.
+/// The features computed are given by the statistics type `S`
+#[derive(Debug, Clone)]
+pub struct TransportFlow<S: FlowStat = FlowStatistics> {
     pub(crate) identifier: TransportFlowIdentifier,
     pub(crate) flow_times: FlowTimes,
-    pub(crate) statistics: FlowStatistics,
+    pub(crate) statistics: S,
+    /// The reassembler of the TCP streams, if the statistics use them
+    stream: Option<TcpStreamReassembler>,
+    /// The number of transport packets accomulated on the flow
+    pub(crate) packet_count: u32,
+    /// The packet threshold this record was generated for, if it is an early
+    /// record
+    record_packet_threshold: Option<u32>,
+    /// The column set used to write the flow
+    output_profile: OutputProfile,
     label: Option<Rc<str>>,
 }
 
-impl TransportFlow {
+impl<S: FlowStat> TransportFlow<S> {
     /// Create a flow from an initial pcap packet header and its sliced contents
     pub fn from(
+        settings: &StatSettings,
         identifier: TransportFlowIdentifier,
         packet_header: &pcap::PacketHeader,
         sliced_packet: etherparse::SlicedPacket,
         reasembly_information: Option<&FragmentReasemblyInformation>,
-    ) -> TransportFlow {
+    ) -> TransportFlow<S> {
         let flow_times = FlowTimes::from_packet(
             &identifier,
             packet_header,
             &sliced_packet,
             reasembly_information,
         );
-        let statistics = FlowStatistics::from_packet(
+        let statistics = S::from_packet(
+            settings,
             &identifier,
             &flow_times,
             packet_header,
             &sliced_packet,
             reasembly_information,
         );
+        let is_tcp = matches!(
+            sliced_packet.transport,
+            Some(etherparse::TransportSlice::Tcp(_))
+        );
+        let stream = (is_tcp && S::uses_streams(settings))
+            .then(|| TcpStreamReassembler::new(settings.stream_buffer_limit()));
         let label = None;
 
-        TransportFlow {
+        let mut flow = TransportFlow {
             identifier,
             flow_times,
             statistics,
+            stream,
+            packet_count: 1,
+            record_packet_threshold: None,
+            output_profile: settings.output_profile(),
             label,
+        };
+        flow.include_stream(true, &sliced_packet);
+        flow
+    }
+
+    /// Pass the ordered bytes completed by the packet to the statistics
+    fn include_stream(&mut self, is_forward: bool, sliced_packet: &etherparse::SlicedPacket) {
+        let TransportFlow {
+            identifier,
+            flow_times,
+            statistics,
+            stream,
+            ..
+        } = self;
+
+        if let (Some(stream), Some(etherparse::TransportSlice::Tcp(tcp_slice))) =
+            (stream, &sliced_packet.transport)
+        {
+            stream.include(
+                is_forward,
+                flow_times.last_packet_time,
+                tcp_slice,
+                |chunk| statistics.include_stream(identifier, flow_times, chunk),
+            );
         }
     }
 
@@ -61,13 +108,15 @@ impl TransportFlow {
         self.label = Some(label);
     }
 
-    /// Accomulate information to the flow with a given pcap packet header and its sliced contents
+    /// Accomulate information to the flow with a given pcap packet header and
+    /// its sliced contents. Returns whether the packet was counted, as packets
+    /// that went back in time are ignored
     pub fn include(
         &mut self,
         packet_header: &pcap::PacketHeader,
         sliced_packet: etherparse::SlicedPacket,
         reasembly_information: Option<&FragmentReasemblyInformation>,
-    ) {
+    ) -> bool {
         // Get time
         let packet_time = packet_parse::get_datetime_of_packet(packet_header)
             .expect("Packet headers with invalid timestamps are not supported");
@@ -78,7 +127,7 @@ impl TransportFlow {
                 self.flow_times.last_packet_time,
                 packet_time
             );
-            return;
+            return false;
         }
 
         self.flow_times.include(
@@ -94,18 +143,69 @@ impl TransportFlow {
             &sliced_packet,
             reasembly_information,
         );
+        let is_forward = self.identifier.is_forward(&sliced_packet);
+        self.include_stream(is_forward, &sliced_packet);
+        self.packet_count += 1;
+        true
+    }
+
+    /// Get the identifier of the flow
+    pub fn identifier(&self) -> &TransportFlowIdentifier {
+        &self.identifier
     }
 
-    /// Write the header for separated information values of the flows to the given writer
+    /// Get the times of the first and last packets of the flow
+    pub fn flow_times(&self) -> &FlowTimes {
+        &self.flow_times
+    }
+
+    /// Get the statistics accomulated on the flow
+    pub fn statistics(&self) -> &S {
+        &self.statistics
+    }
+
+    /// Get the reassembler of the TCP streams, if the statistics use them
+    pub fn stream(&self) -> Option<&TcpStreamReassembler> {
+        self.stream.as_ref()
+    }
+
+    /// Get the number of transport packets accomulated on the flow
+    pub fn packet_count(&self) -> u32 {
+        self.packet_count
+    }
+
+    /// Get the packet threshold this record was generated for, if it is an
+    /// early record
+    pub fn record_packet_threshold(&self) -> Option<u32> {
+        self.record_packet_threshold
+    }
+
+    /// Write the header for separated information values of the flows to the
+    /// given writer. The `settings` should be the same ones used to create the
+    /// flows and the `packet_threshold_column` should be set when writing early
+    /// records
     pub fn write_csv_header<T: ?Sized + std::io::Write>(
         writer: &mut BufWriter<T>,
+        settings: &StatSettings,
+        packet_threshold_column: bool,
         label_column: bool,
     ) -> Result<(), Error> {
-        TransportFlowIdentifier::write_csv_header(writer)?;
-        FlowTimes::write_csv_header(writer)?;
-        FlowStatistics::write_csv_header(writer)?;
+        match settings.output_profile() {
+            OutputProfile::Native => {
+                TransportFlowIdentifier::write_csv_header(writer)?;
+                FlowTimes::write_csv_header(writer)?;
+            }
+            OutputProfile::CicFlowMeter => CicFlowMeter::write_flow_csv_header(writer)?,
+        }
+        S::write_csv_header(writer, settings)?;
+        if packet_threshold_column {
+            write!(writer, "packet_threshold,")?;
+        }
         if label_column {
-            write!(writer, "label")?;
+            match settings.output_profile() {
+                OutputProfile::Native => write!(writer, "label")?,
+                OutputProfile::CicFlowMeter => write!(writer, "Label")?,
+            }
         }
         writeln!(writer)?;
         Ok(())
@@ -117,9 +217,19 @@ impl TransportFlow {
         writer: &mut BufWriter<T>,
         label_column: bool,
     ) -> Result<(), Error> {
-        self.identifier.write_csv_value(writer)?;
-        self.flow_times.write_csv_value(writer)?;
+        match self.output_profile {
+            OutputProfile::Native => {
+                self.identifier.write_csv_value(writer)?;
+                self.flow_times.write_csv_value(writer)?;
+            }
+            OutputProfile::CicFlowMeter => {
+                CicFlowMeter::write_flow_csv_value(writer, &self.identifier, &self.flow_times)?
+            }
+        }
         self.statistics.write_csv_value(writer, &self.flow_times)?;
+        if let Some(packet_threshold) = self.record_packet_threshold {
+            write!(writer, "{},", packet_threshold)?;
+        }
         if label_column {
             match &self.label {
                 None => write!(writer, ""),
@@ -131,6 +241,24 @@ impl TransportFlow {
     }
 }
 
+impl<S: FlowStat + Clone> TransportFlow<S> {
+    /// Copy the flow as the early record of the given packet threshold. The
+    /// reassembler of the TCP streams is left out, as it is only needed to
+    /// include the next packets and may buffer many bytes
+    fn record(&self, packet_threshold: u32) -> TransportFlow<S> {
+        TransportFlow {
+            identifier: self.identifier,
+            flow_times: self.flow_times.clone(),
+            statistics: self.statistics.clone(),
+            stream: None,
+            packet_count: self.packet_count,
+            record_packet_threshold: Some(packet_threshold),
+            output_profile: self.output_profile,
+            label: self.label.clone(),
+        }
+    }
+}
+
 /// The fragments on a network flow yet to be reasembled
 #[derive(Debug)]
 pub struct NetworkFragmentFlow {
@@ -148,6 +276,7 @@ pub struct NetworkFragmentFlow {
     total_bytes_received_count: u32,
 }
 
+/// Information about the fragments used to reassemble an IPv4 packet
 #[derive(Debug)]
 pub struct FragmentReasemblyInformation {
     /// The first time a fragmented packet was received
@@ -318,28 +447,78 @@ impl NetworkFragmentFlow {
     }
 }
 
-/// A group of flows
+/// A group of flows. The features computed on each flow are given by the
+/// statistics type `S`
 #[derive(Debug)]
-pub struct FlowGroup {
-    transport_flows: HashMap<TransportFlowIdentifier, TransportFlow>,
+pub struct FlowGroup<S: FlowStat = FlowStatistics> {
+    transport_flows: HashMap<TransportFlowIdentifier, TransportFlow<S>>,
     transport_flows_queue: PriorityQueue<TransportFlowIdentifier, Reverse<DateTime<Utc>>>,
     network_fragment_flows: HashMap<NetworkFlowIdentifier, NetworkFragmentFlow>,
     network_fragment_flows_queue: PriorityQueue<NetworkFlowIdentifier, DateTime<Utc>>,
     latest_time: Option<DateTime<Utc>>,
+    /// Settings of the statistics computed on each flow
+    stat_settings: StatSettings,
+    /// Sorted packet counts at which an early record of a flow is generated.
+    /// If empty, the flows are only returned once they are closed
+    early_record_thresholds: Vec<u32>,
+    /// The early records generated and not yet popped
+    early_records: VecDeque<TransportFlow<S>>,
 }
 
 impl FlowGroup {
-    /// Create an empty group of flows
+    /// Create an empty group of flows computing the built-in feature groups
     pub fn new() -> FlowGroup {
+        FlowGroup::with_feature_set()
+    }
+}
+
+impl<S: FlowStat + Clone> FlowGroup<S> {
+    /// Create an empty group of flows computing the features of the
+    /// statistics type `S`
+    pub fn with_feature_set() -> FlowGroup<S> {
         FlowGroup {
             transport_flows: HashMap::new(),
             transport_flows_queue: PriorityQueue::new(),
             network_fragment_flows: HashMap::new(),
             network_fragment_flows_queue: PriorityQueue::new(),
             latest_time: None,
+            stat_settings: StatSettings::default(),
+            early_record_thresholds: Vec::new(),
+            early_records: VecDeque::new(),
         }
     }
 
+    /// Set the settings of the statistics computed on each flow. Should be
+    /// set before including any packet
+    pub fn with_stat_settings(mut self, stat_settings: StatSettings) -> FlowGroup<S> {
+        self.stat_settings = stat_settings;
+        self
+    }
+
+    /// Make the group generate early records. A record of each flow is
+    /// generated as soon as it reaches each of the given packet counts. Flows
+    /// that are closed before reaching some of them generate the remaining
+    /// records at the moment of closing. Should be set before including any
+    /// packet
+    pub fn with_early_records(mut self, mut packet_thresholds: Vec<u32>) -> FlowGroup<S> {
+        packet_thresholds.retain(|threshold| *threshold != 0);
+        packet_thresholds.sort_unstable();
+        packet_thresholds.dedup();
+
+        self.early_record_thresholds = packet_thresholds;
+        self
+    }
+
+    /// Get the settings of the statistics computed on each flow
+    pub fn stat_settings(&self) -> &StatSettings {
+        &self.stat_settings
+    }
+
+    /// Check if the group generates early records
+    pub fn generates_early_records(&self) -> bool {
+        !self.early_record_thresholds.is_empty()
+    }
+
     /// Accomulate information to the correct flow given a packet and its
     /// respective link type. On success, returns the number of valid packets
     /// and invalid packets. This will usually be (1, 0), but can differ in
@@ -402,9 +581,10 @@ impl FlowGroup {
         sliced_packet: etherparse::SlicedPacket<'_>,
         reasembly_information: Option<&FragmentReasemblyInformation>,
     ) {
-        match self.transport_flows.get_mut(&transport_flow_identifier) {
+        let is_counted = match self.transport_flows.get_mut(&transport_flow_identifier) {
             None => {
                 let flow = TransportFlow::from(
+                    &self.stat_settings,
                     transport_flow_identifier,
                     packet_header,
                     sliced_packet,
@@ -415,14 +595,41 @@ impl FlowGroup {
                     Reverse(flow.flow_times.last_packet_time),
                 );
                 self.transport_flows.insert(transport_flow_identifier, flow);
+                true
             }
             Some(flow) => {
-                flow.include(packet_header, sliced_packet, reasembly_information);
+                let is_counted = flow.include(packet_header, sliced_packet, reasembly_information);
                 self.transport_flows_queue.change_priority(
                     &transport_flow_identifier,
                     Reverse(flow.flow_times.last_packet_time),
                 );
+                is_counted
             }
+        };
+
+        // Generate early record if a threshold was reached, only once as the
+        // packets that are not counted keep the same count
+        let flow = &self.transport_flows[&transport_flow_identifier];
+        if is_counted
+            && self
+                .early_record_thresholds
+                .binary_search(&flow.packet_count)
+                .is_ok()
+        {
+            self.early_records.push_back(flow.record(flow.packet_count));
+        }
+    }
+
+    /// Generate the early records of a closed flow for the thresholds it did
+    /// not reach
+    fn close_early_records(&mut self, flow: TransportFlow<S>) {
+        let pending_thresholds = self
+            .early_record_thresholds
+            .iter()
+            .filter(|threshold| flow.packet_count < **threshold);
+
+        for threshold in pending_thresholds {
+            self.early_records.push_back(flow.record(*threshold));
         }
     }
 
@@ -521,33 +728,47 @@ impl FlowGroup {
 
     /// Try popping oldest transport flow if it has passed more time than
     /// `time_delta` between last packet received on it and the last packet in
-    /// general
+    /// general. If the group generates early records, the pending early
+    /// records are returned instead, including the ones of the flows closed
     pub fn pop_oldest_transport_flow_if_older_than(
         &mut self,
         time_delta: TimeDelta,
-    ) -> Option<TransportFlow> {
-        if let Some((oldest_time, latest_time)) =
-            self.get_oldest_time_transport().zip(self.latest_time)
-        {
-            if time_delta < latest_time - oldest_time {
-                let (flow_identifier, _) = self.transport_flows_queue.pop().unwrap();
-                let flow = self.transport_flows.remove(&flow_identifier).unwrap();
-                Some(flow)
-            } else {
-                None
+    ) -> Option<TransportFlow<S>> {
+        loop {
+            if let Some(record) = self.early_records.pop_front() {
+                return Some(record);
             }
-        } else {
-            None
+
+            let (oldest_time, latest_time) =
+                self.get_oldest_time_transport().zip(self.latest_time)?;
+            if latest_time - oldest_time <= time_delta {
+                return None;
+            }
+
+            let (flow_identifier, _) = self.transport_flows_queue.pop().unwrap();
+            let flow = self.transport_flows.remove(&flow_identifier).unwrap();
+            if !self.generates_early_records() {
+                return Some(flow);
+            }
+            self.close_early_records(flow);
         }
     }
 
-    /// Try popping oldest transport flow
-    pub fn pop_oldest_transport_flow(&mut self) -> Option<TransportFlow> {
-        if let Some((flow_identifier, _)) = self.transport_flows_queue.pop() {
+    /// Try popping oldest transport flow. If the group generates early
+    /// records, the pending early records are returned instead, including the
+    /// ones of the flows closed
+    pub fn pop_oldest_transport_flow(&mut self) -> Option<TransportFlow<S>> {
+        loop {
+            if let Some(record) = self.early_records.pop_front() {
+                return Some(record);
+            }
+
+            let (flow_identifier, _) = self.transport_flows_queue.pop()?;
             let flow = self.transport_flows.remove(&flow_identifier).unwrap();
-            Some(flow)
-        } else {
-            None
+            if !self.generates_early_records() {
+                return Some(flow);
+            }
+            self.close_early_records(flow);
         }
     }
 
@@ -772,4 +993,87 @@ mod tests {
             .pop_oldest_transport_flow_if_older_than(TimeDelta::microseconds(3))
             .is_some());
     }
+
+    #[test]
+    fn test_early_records() {
+        // Create flow group
+        let mut flow_group = FlowGroup::new().with_early_records(vec![5, 2, 2]);
+
+        assert_eq!(flow_group.early_record_thresholds, vec![2, 5]);
+
+        // Include three packets of the same flow
+        let link_type = pcap::Linktype::ETHERNET;
+        let payload = [1, 2, 3, 4, 5, 6, 7, 8];
+
+        for tv_usec in 0..3 {
+            let origin = PacketBuilder::ethernet2([1, 2, 3, 4, 5, 6], [7, 8, 9, 10, 11, 12])
+                .ipv4([192, 168, 1, 1], [192, 168, 1, 2], 20)
+                .udp(21, 1234);
+
+            let mut packet_payload = Vec::<u8>::with_capacity(origin.size(payload.len()));
+            origin.write(&mut packet_payload, &payload).unwrap();
+            let packet = pcap::Packet {
+                header: &pcap::PacketHeader {
+                    ts: timeval { tv_sec: 0, tv_usec },
+                    caplen: packet_payload.len().try_into().unwrap(),
+                    len: packet_payload.len().try_into().unwrap(),
+                },
+                data: &packet_payload,
+            };
+
+            let _ = flow_group.include(link_type, &packet);
+        }
+
+        // The record of the reached threshold is available right away
+        let record = flow_group
+            .pop_oldest_transport_flow_if_older_than(TimeDelta::seconds(120))
+            .unwrap();
+        assert_eq!(record.record_packet_threshold(), Some(2));
+        assert_eq!(record.packet_count, 2);
+        assert!(flow_group
+            .pop_oldest_transport_flow_if_older_than(TimeDelta::seconds(120))
+            .is_none());
+
+        // The record of the unreached threshold is generated on closing
+        let record = flow_group.pop_oldest_transport_flow().unwrap();
+        assert_eq!(record.record_packet_threshold(), Some(5));
+        assert_eq!(record.packet_count, 3);
+        assert!(flow_group.pop_oldest_transport_flow().is_none());
+        assert!(flow_group.transport_flows.is_empty());
+    }
+
+    #[test]
+    fn test_early_records_out_of_order() {
+        // Create flow group
+        let mut flow_group = FlowGroup::new().with_early_records(vec![2]);
+
+        // Include a packet that went back in time right after the threshold
+        let link_type = pcap::Linktype::ETHERNET;
+        let payload = [1, 2, 3, 4, 5, 6, 7, 8];
+
+        for tv_usec in [10, 20, 5] {
+            let origin = PacketBuilder::ethernet2([1, 2, 3, 4, 5, 6], [7, 8, 9, 10, 11, 12])
+                .ipv4([192, 168, 1, 1], [192, 168, 1, 2], 20)
+                .udp(21, 1234);
+
+            let mut packet_payload = Vec::<u8>::with_capacity(origin.size(payload.len()));
+            origin.write(&mut packet_payload, &payload).unwrap();
+            let packet = pcap::Packet {
+                header: &pcap::PacketHeader {
+                    ts: timeval { tv_sec: 0, tv_usec },
+                    caplen: packet_payload.len().try_into().unwrap(),
+                    len: packet_payload.len().try_into().unwrap(),
+                },
+                data: &packet_payload,
+            };
+
+            let _ = flow_group.include(link_type, &packet);
+        }
+
+        // The ignored packet does not generate the record again
+        let record = flow_group.pop_oldest_transport_flow().unwrap();
+        assert_eq!(record.record_packet_threshold(), Some(2));
+        assert_eq!(record.packet_count, 2);
+        assert!(flow_group.pop_oldest_transport_flow().is_none());
+    }
 }
@@ -182,6 +182,46 @@ impl TransportFlowIdentifier {
         }
     }
 
+    /// The IP address of the host that sent the first packet of the flow
+    pub fn source_ip(&self) -> IpAddr {
+        self.source_ip
+    }
+
+    /// The port of the host that sent the first packet of the flow
+    pub fn source_port(&self) -> u16 {
+        self.source_port
+    }
+
+    /// The IP address of the host that received the first packet of the flow
+    pub fn dest_ip(&self) -> IpAddr {
+        self.dest_ip
+    }
+
+    /// The port of the host that received the first packet of the flow
+    pub fn dest_port(&self) -> u16 {
+        self.dest_port
+    }
+
+    /// The transport protocol of the flow
+    pub fn transport_protocol(&self) -> IpNumber {
+        self.transport_protocol
+    }
+
+    /// Check if a sliced packet of the flow goes in the forward direction,
+    /// which is the one of the first packet of the flow. Panics if the packet
+    /// does not have a network layer
+    pub fn is_forward(&self, sliced_packet: &etherparse::SlicedPacket) -> bool {
+        let src_ip = match &sliced_packet.net {
+            Some(header) => match header {
+                etherparse::NetSlice::Ipv4(v) => IpAddr::V4(v.header().source_addr()),
+                etherparse::NetSlice::Ipv6(v) => IpAddr::V6(v.header().source_addr()),
+            },
+            None => panic!("Unexpected sliced packet without net layer"),
+        };
+
+        src_ip == self.source_ip
+    }
+
     pub(crate) fn write_csv_header<T: ?Sized + std::io::Write>(
         writer: &mut BufWriter<T>,
     ) -> Result<(), Error> {
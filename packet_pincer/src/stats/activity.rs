@@ -2,15 +2,12 @@ use chrono::{DateTime, TimeDelta, Utc};
 
 use super::{
     extract_byte_count, extract_packet_count, running_stat::RunningStat,
-    FlowStat, FlowTimes,
+    FlowStat, FlowTimes, StatSettings,
 };
 use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
-use std::{
-    io::{BufWriter, Error, Write},
-    net::IpAddr,
-};
+use std::io::{BufWriter, Error, Write};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Activity {
     idle_microseconds: RunningStat,
     active_microseconds: RunningStat,
@@ -32,6 +29,7 @@ impl Activity {
 
 impl FlowStat for Activity {
     fn from_packet(
+        _settings: &StatSettings,
         _identifier: &TransportFlowIdentifier,
         flow_times: &FlowTimes,
         packet_header: &pcap::PacketHeader,
@@ -61,17 +59,7 @@ impl FlowStat for Activity {
         // Extract parts
         let byte_count = extract_byte_count(packet_header, reasembly_information);
         let packet_count = extract_packet_count(reasembly_information);
-        let is_forward = match &sliced_packet.net {
-            Some(header) => match header {
-                etherparse::NetSlice::Ipv4(v) => {
-                    IpAddr::V4(v.header().source_addr()) == identifier.source_ip
-                }
-                etherparse::NetSlice::Ipv6(v) => {
-                    IpAddr::V6(v.header().source_addr()) == identifier.source_ip
-                }
-            },
-            None => panic!("Unexpected sliced packet without net layer"),
-        };
+        let is_forward = identifier.is_forward(sliced_packet);
         let last_active_duration = self.last_active_end - self.last_active_start;
         let time_since_last_active = flow_times.last_packet_time - self.last_active_end;
 
@@ -112,6 +100,7 @@ impl FlowStat for Activity {
     }
     fn write_csv_header<T: ?Sized + std::io::Write>(
         writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
     ) -> Result<(), Error> {
         write!(writer, "idle_seconds_min,")?;
         write!(writer, "idle_seconds_max,")?;
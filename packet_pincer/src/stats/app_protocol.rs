@@ -0,0 +1,102 @@
+use super::{app_signatures::MAX_PREFIX_LEN, AppSignatures, FlowStat, FlowTimes, StatSettings};
+use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
+use std::{
+    io::{BufWriter, Error, Write},
+    rc::Rc,
+};
+
+/// Application protocol of the flow, identified with the signatures of the
+/// settings from the first payload of each direction regardless of the
+/// ports. The confidence is `dpi` if a signature matched, or `port` if the
+/// protocol was only guessed from the ports
+#[derive(Debug, Clone)]
+pub struct AppProtocol {
+    signatures: Rc<AppSignatures>,
+    transport: etherparse::IpNumber,
+    /// Source and destination ports of the flow
+    ports: [u16; 2],
+    forward_prefix: Option<Vec<u8>>,
+    backward_prefix: Option<Vec<u8>>,
+}
+
+impl AppProtocol {
+    fn include_packet(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        sliced_packet: &etherparse::SlicedPacket,
+    ) {
+        let payload = match &sliced_packet.transport {
+            Some(etherparse::TransportSlice::Tcp(tcp_slice)) => tcp_slice.payload(),
+            Some(etherparse::TransportSlice::Udp(udp_slice)) => udp_slice.payload(),
+            _ => return,
+        };
+        if payload.is_empty() {
+            return;
+        }
+
+        let prefix = if identifier.is_forward(sliced_packet) {
+            &mut self.forward_prefix
+        } else {
+            &mut self.backward_prefix
+        };
+        prefix.get_or_insert_with(|| payload[..payload.len().min(MAX_PREFIX_LEN)].to_vec());
+    }
+}
+
+impl FlowStat for AppProtocol {
+    fn from_packet(
+        settings: &StatSettings,
+        identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        let mut app_protocol = AppProtocol {
+            signatures: settings.shared_app_signatures(),
+            transport: identifier.transport_protocol(),
+            ports: [identifier.source_port(), identifier.dest_port()],
+            forward_prefix: None,
+            backward_prefix: None,
+        };
+
+        app_protocol.include_packet(identifier, sliced_packet);
+        app_protocol
+    }
+    fn include(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        if self.forward_prefix.is_none() || self.backward_prefix.is_none() {
+            self.include_packet(identifier, sliced_packet);
+        }
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        write!(writer, "app_protocol,app_confidence,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        let protocol = self.signatures.classify(
+            self.transport,
+            self.ports,
+            self.forward_prefix.as_deref().unwrap_or_default(),
+            self.backward_prefix.as_deref().unwrap_or_default(),
+        );
+        match protocol {
+            Some((name, confidence)) => write!(writer, "{},{},", name, confidence.name())?,
+            None => write!(writer, ",,")?,
+        }
+        Ok(())
+    }
+}
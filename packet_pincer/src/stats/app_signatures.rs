@@ -0,0 +1,352 @@
+use etherparse::IpNumber;
+use std::{error::Error, fs, path::PathBuf, rc::Rc};
+
+/// Signatures of the common application protocols
+const BUILTIN_SIGNATURES: &str = include_str!("../../assets/app_signatures.rules");
+
+/// Largest prefix of the first payload of each direction kept, where the
+/// conditions of the signatures must end
+pub(crate) const MAX_PREFIX_LEN: usize = 256;
+
+/// How the application protocol of a flow was identified
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Confidence {
+    /// A signature matched the payload
+    Dpi,
+    /// Only the port is known to be used by the protocol
+    Port,
+}
+
+impl Confidence {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Confidence::Dpi => "dpi",
+            Confidence::Port => "port",
+        }
+    }
+}
+
+/// Direction of the payload a signature is matched against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Forward,
+    Backward,
+    Any,
+}
+
+/// Bytes expected from an offset of the payload, with the mask of the bits
+/// compared
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Condition {
+    offset: usize,
+    bytes: Vec<u8>,
+    mask: Vec<u8>,
+}
+
+impl Condition {
+    fn matches(&self, payload: &[u8]) -> bool {
+        let Some(actual) = payload.get(self.offset..self.offset + self.bytes.len()) else {
+            return false;
+        };
+        actual
+            .iter()
+            .zip(&self.bytes)
+            .zip(&self.mask)
+            .all(|((actual, expected), mask)| actual & mask == expected & mask)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Signature {
+    direction: Direction,
+    conditions: Vec<Condition>,
+}
+
+impl Signature {
+    /// Split a signature in tokens, keeping the quoted text together
+    fn tokens(value: &str) -> Result<Vec<&str>, String> {
+        let mut tokens = Vec::new();
+        let mut rest = value.trim_start();
+        while !rest.is_empty() {
+            let end = if let Some(quoted) = rest.strip_prefix('"') {
+                quoted.find('"').ok_or("unterminated quoted text")? + 2
+            } else {
+                rest.find(char::is_whitespace).unwrap_or(rest.len())
+            };
+            tokens.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+        Ok(tokens)
+    }
+
+    fn parse(value: &str) -> Result<Signature, String> {
+        let tokens = Signature::tokens(value)?;
+        let (direction, tokens) = tokens.split_first().ok_or("empty signature")?;
+        let direction = match *direction {
+            "fwd" => Direction::Forward,
+            "bwd" => Direction::Backward,
+            "any" => Direction::Any,
+            other => return Err(format!("unknown direction '{}'", other)),
+        };
+
+        let mut conditions: Vec<Condition> = Vec::new();
+        for token in tokens {
+            if let Some(offset) = token.strip_prefix('@') {
+                let offset = offset
+                    .parse()
+                    .map_err(|_| format!("invalid offset '{}'", token))?;
+                conditions.push(Condition {
+                    offset,
+                    bytes: Vec::new(),
+                    mask: Vec::new(),
+                });
+                continue;
+            }
+            let condition = conditions
+                .last_mut()
+                .ok_or("bytes must follow an @offset")?;
+
+            if let Some(text) = token.strip_prefix('"') {
+                let text = text.strip_suffix('"').unwrap_or(text);
+                condition.bytes.extend_from_slice(text.as_bytes());
+                condition.mask.resize(condition.bytes.len(), 0xFF);
+            } else {
+                let (value, mask) = token.split_once('/').unwrap_or((token, "FF"));
+                let parse_byte = |hex: &str| {
+                    (hex.len() == 2)
+                        .then(|| u8::from_str_radix(hex, 16).ok())
+                        .flatten()
+                        .ok_or_else(|| format!("invalid byte '{}'", token))
+                };
+                condition.bytes.push(parse_byte(value)?);
+                condition.mask.push(parse_byte(mask)?);
+            }
+        }
+
+        if conditions.is_empty() || conditions.iter().any(|c| c.bytes.is_empty()) {
+            return Err("expected @offset followed by bytes".to_string());
+        }
+        if conditions
+            .iter()
+            .any(|c| c.offset.saturating_add(c.bytes.len()) > MAX_PREFIX_LEN)
+        {
+            return Err(format!(
+                "conditions must end within the first {} bytes",
+                MAX_PREFIX_LEN
+            ));
+        }
+        Ok(Signature {
+            direction,
+            conditions,
+        })
+    }
+
+    fn matches(&self, forward: &[u8], backward: &[u8]) -> bool {
+        let matches = |payload: &[u8]| self.conditions.iter().all(|c| c.matches(payload));
+        match self.direction {
+            Direction::Forward => matches(forward),
+            Direction::Backward => matches(backward),
+            Direction::Any => matches(forward) || matches(backward),
+        }
+    }
+}
+
+/// Application protocol with its signatures
+#[derive(Debug, Clone)]
+struct ProtocolSignatures {
+    name: Rc<str>,
+    /// Transport protocol, or any if None
+    transport: Option<IpNumber>,
+    ports: Vec<u16>,
+    signatures: Vec<Signature>,
+}
+
+/// Database of signatures of the first payload of each direction of the
+/// flows, used to identify their application protocol
+#[derive(Debug, Clone, Default)]
+pub struct AppSignatures {
+    protocols: Vec<ProtocolSignatures>,
+}
+
+impl AppSignatures {
+    /// Create an empty database, which identifies no flow
+    pub fn new() -> AppSignatures {
+        AppSignatures::default()
+    }
+
+    /// Create a database with the built-in signatures of HTTP, TLS, SSH,
+    /// DNS, QUIC, MQTT, SMB, RDP, BitTorrent and other common protocols
+    pub fn builtin() -> AppSignatures {
+        AppSignatures::parse(BUILTIN_SIGNATURES).expect("Built-in signatures are valid")
+    }
+
+    /// Try loading the signatures of a file. The built-in signatures are kept
+    /// after the ones of the file, which are tried first
+    pub fn from_file(file: PathBuf) -> Result<AppSignatures, Box<dyn Error>> {
+        let content = fs::read_to_string(file)?;
+        let mut signatures = AppSignatures::parse(&content)?;
+        signatures
+            .protocols
+            .extend(AppSignatures::builtin().protocols);
+        Ok(signatures)
+    }
+
+    /// Try parsing signatures in the format of `assets/app_signatures.rules`
+    pub fn parse(content: &str) -> Result<AppSignatures, Box<dyn Error>> {
+        let mut signatures = AppSignatures::new();
+
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            let error = |message: &str| {
+                format!(
+                    "Invalid application signature on line {}: {}",
+                    i + 1,
+                    message
+                )
+            };
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                if name.is_empty() || name.contains([',', '"']) {
+                    return Err(error("names cannot be empty or contain commas or quotes").into());
+                }
+                signatures.protocols.push(ProtocolSignatures {
+                    name: name.into(),
+                    transport: None,
+                    ports: Vec::new(),
+                    signatures: Vec::new(),
+                });
+                continue;
+            }
+
+            let protocol = signatures
+                .protocols
+                .last_mut()
+                .ok_or_else(|| error("expected a [name] first"))?;
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected 'key = value'"))?;
+            let value = value.trim();
+            match key.trim() {
+                "proto" => {
+                    protocol.transport = match value {
+                        "tcp" => Some(IpNumber::TCP),
+                        "udp" => Some(IpNumber::UDP),
+                        "any" => None,
+                        _ => return Err(error("expected tcp, udp or any").into()),
+                    }
+                }
+                "ports" => {
+                    protocol.ports = value
+                        .split(',')
+                        .map(|port| port.trim().parse())
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| error("invalid port"))?;
+                }
+                "sig" => {
+                    let signature = Signature::parse(value).map_err(|e| error(&e))?;
+                    protocol.signatures.push(signature);
+                }
+                _ => {}
+            }
+        }
+        Ok(signatures)
+    }
+
+    /// Find the application protocol of a flow from the first payload of
+    /// each direction, or from its ports if no signature matches
+    pub(crate) fn classify(
+        &self,
+        transport: IpNumber,
+        ports: [u16; 2],
+        forward: &[u8],
+        backward: &[u8],
+    ) -> Option<(Rc<str>, Confidence)> {
+        let protocols = self
+            .protocols
+            .iter()
+            .filter(|protocol| protocol.transport.is_none_or(|t| t == transport));
+
+        let by_signature = protocols.clone().find(|protocol| {
+            protocol
+                .signatures
+                .iter()
+                .any(|signature| signature.matches(forward, backward))
+        });
+        if let Some(protocol) = by_signature {
+            return Some((Rc::clone(&protocol.name), Confidence::Dpi));
+        }
+
+        // The port of the server is more telling than the one of the client
+        ports.into_iter().rev().find_map(|port| {
+            protocols
+                .clone()
+                .find(|protocol| protocol.ports.contains(&port))
+                .map(|protocol| (Rc::clone(&protocol.name), Confidence::Port))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let signatures = AppSignatures::builtin();
+        let classify = |transport, ports, forward: &[u8], backward: &[u8]| {
+            signatures
+                .classify(transport, ports, forward, backward)
+                .map(|(name, confidence)| (name.to_string(), confidence))
+        };
+
+        assert_eq!(
+            classify(IpNumber::TCP, [40000, 8081], b"GET / HTTP/1.1\r\n", b""),
+            Some(("http".to_string(), Confidence::Dpi))
+        );
+        assert_eq!(
+            classify(IpNumber::TCP, [40000, 80], b"", b"SSH-2.0-OpenSSH_9.6\r\n"),
+            Some(("ssh".to_string(), Confidence::Dpi))
+        );
+        assert_eq!(
+            classify(IpNumber::UDP, [40000, 8000], b"OPTIONS sip:a SIP/2.0", b""),
+            Some(("sip".to_string(), Confidence::Dpi))
+        );
+        assert_eq!(
+            classify(IpNumber::UDP, [123, 40000], b"\x00", b""),
+            Some(("ntp".to_string(), Confidence::Port))
+        );
+        assert_eq!(classify(IpNumber::UDP, [40000, 40001], b"", b""), None);
+        assert_eq!(
+            classify(
+                IpNumber::UDP,
+                [40000, 40001],
+                b"\x12\x34\x01\x20\x00\x01\x00\x00\x00\x00\x00\x01",
+                b""
+            ),
+            Some(("dns".to_string(), Confidence::Dpi))
+        );
+        assert_eq!(
+            classify(
+                IpNumber::UDP,
+                [40000, 40001],
+                b"\x12\x34\x01\x00\x00\x01\x7F\x00\x00\x00\x00\x00",
+                b""
+            ),
+            None
+        );
+
+        let signatures =
+            AppSignatures::parse("[custom]\nproto = udp\nsig = any @1 AB/F0 \"x y\"\n").unwrap();
+        assert!(signatures
+            .classify(IpNumber::UDP, [1, 2], b"", b"\x00\xA5x y")
+            .is_some());
+        assert!(AppSignatures::parse("[custom]\nsig = fwd 16 03\n").is_err());
+        assert!(AppSignatures::parse("sig = fwd @0 16\n").is_err());
+        assert!(AppSignatures::parse("[custom]\nsig = fwd @255 16 03\n").is_err());
+        assert!(
+            AppSignatures::parse(&format!("[custom]\nsig = fwd @{} 16\n", usize::MAX)).is_err()
+        );
+    }
+}
@@ -0,0 +1,282 @@
+use chrono::{DateTime, TimeDelta, Utc};
+
+/// Bulk transfers of a single direction of a flow
+#[derive(Debug, Clone)]
+pub struct DirectionBulks {
+    /// Start of the candidate bulk, if there is one
+    candidate_start: Option<DateTime<Utc>>,
+    /// Time of the last packet that was part of the candidate bulk
+    candidate_last_time: Option<DateTime<Utc>>,
+    candidate_packet_count: u64,
+    candidate_byte_count: u64,
+
+    bulk_count: u64,
+    packet_count: u64,
+    byte_count: u64,
+    duration: TimeDelta,
+}
+
+impl DirectionBulks {
+    fn new() -> DirectionBulks {
+        DirectionBulks {
+            candidate_start: None,
+            candidate_last_time: None,
+            candidate_packet_count: 0,
+            candidate_byte_count: 0,
+            bulk_count: 0,
+            packet_count: 0,
+            byte_count: 0,
+            duration: TimeDelta::zero(),
+        }
+    }
+
+    fn include(
+        &mut self,
+        min_packets: u64,
+        max_gap: TimeDelta,
+        time: DateTime<Utc>,
+        payload_bytes: u64,
+    ) {
+        let candidate = self.candidate_start.zip(self.candidate_last_time);
+        let (start, last_time) = match candidate {
+            Some((start, last_time)) if time - last_time <= max_gap => {
+                self.candidate_packet_count += 1;
+                self.candidate_byte_count += payload_bytes;
+                (start, last_time)
+            }
+            _ => {
+                self.start_candidate(time, payload_bytes);
+                (time, time)
+            }
+        };
+
+        // A single packet is the smallest bulk
+        let min_packets = min_packets.max(1);
+        if self.candidate_packet_count == min_packets {
+            // The candidate becomes a bulk
+            self.bulk_count += 1;
+            self.packet_count += self.candidate_packet_count;
+            self.byte_count += self.candidate_byte_count;
+            self.duration += time - start;
+        } else if min_packets < self.candidate_packet_count {
+            // The bulk is extended
+            self.packet_count += 1;
+            self.byte_count += payload_bytes;
+            self.duration += time - last_time;
+        }
+        self.candidate_last_time = Some(time);
+    }
+
+    fn start_candidate(&mut self, time: DateTime<Utc>, payload_bytes: u64) {
+        self.candidate_start = Some(time);
+        self.candidate_last_time = Some(time);
+        self.candidate_packet_count = 1;
+        self.candidate_byte_count = payload_bytes;
+    }
+
+    /// Drop the candidate bulk, keeping the bulks already found
+    fn interrupt(&mut self) {
+        self.candidate_start = None;
+        self.candidate_last_time = None;
+    }
+
+    /// Number of bulks found
+    pub fn bulk_count(&self) -> u64 {
+        self.bulk_count
+    }
+
+    /// Number of payload bytes sent on bulks
+    pub fn byte_count(&self) -> u64 {
+        self.byte_count
+    }
+
+    /// Number of packets sent on bulks
+    pub fn packet_count(&self) -> u64 {
+        self.packet_count
+    }
+
+    /// Total time spent on bulks
+    pub fn duration(&self) -> TimeDelta {
+        self.duration
+    }
+
+    /// Average payload bytes per bulk, or 0 if there are no bulks
+    pub fn mean_bytes_per_bulk(&self) -> f64 {
+        if self.bulk_count == 0 {
+            0.0
+        } else {
+            self.byte_count as f64 / self.bulk_count as f64
+        }
+    }
+
+    /// Average packets per bulk, or 0 if there are no bulks
+    pub fn mean_packets_per_bulk(&self) -> f64 {
+        if self.bulk_count == 0 {
+            0.0
+        } else {
+            self.packet_count as f64 / self.bulk_count as f64
+        }
+    }
+
+    /// Payload bytes per second sent while on bulks, or 0 if the bulks took
+    /// no time
+    pub fn bulk_rate(&self) -> f64 {
+        let microseconds = self.duration.num_microseconds().unwrap_or(i64::MAX);
+        if microseconds <= 0 {
+            0.0
+        } else {
+            self.byte_count as f64 / (microseconds as f64 / 1_000_000.0)
+        }
+    }
+}
+
+/// Detection of bulk transfers on both directions of a flow, following the
+/// definition used by CICFlowMeter. A bulk is a sequence of at least
+/// `min_packets` packets with payload sent on the same direction, without
+/// gaps longer than `max_gap` between them and without packets with payload
+/// on the opposite direction in between
+#[derive(Debug, Clone)]
+pub struct BulkTracker {
+    min_packets: u64,
+    max_gap: TimeDelta,
+    forward: DirectionBulks,
+    backward: DirectionBulks,
+}
+
+impl BulkTracker {
+    /// Create a tracker without bulks, where a `min_packets` of 0 is the
+    /// same as 1
+    pub fn new(min_packets: u32, max_gap: TimeDelta) -> BulkTracker {
+        BulkTracker {
+            min_packets: u64::from(min_packets),
+            max_gap,
+            forward: DirectionBulks::new(),
+            backward: DirectionBulks::new(),
+        }
+    }
+
+    /// Include a packet of the given direction. Packets without payload are
+    /// ignored, and the rest interrupt the candidate bulk of the opposite
+    /// direction
+    pub fn include(&mut self, is_forward: bool, time: DateTime<Utc>, payload_bytes: u64) {
+        if payload_bytes == 0 {
+            return;
+        }
+
+        let (current, opposite) = if is_forward {
+            (&mut self.forward, &mut self.backward)
+        } else {
+            (&mut self.backward, &mut self.forward)
+        };
+        opposite.interrupt();
+        current.include(self.min_packets, self.max_gap, time, payload_bytes);
+    }
+
+    /// The bulks sent on the direction of the first packet of the flow
+    pub fn forward(&self) -> &DirectionBulks {
+        &self.forward
+    }
+
+    /// The bulks sent on the opposite direction
+    pub fn backward(&self) -> &DirectionBulks {
+        &self.backward
+    }
+}
+
+/// Detection of the subflows of a flow, which are the groups of packets
+/// without gaps longer than `max_gap` between them
+#[derive(Debug, Clone)]
+pub struct SubflowTracker {
+    max_gap: TimeDelta,
+    last_time: Option<DateTime<Utc>>,
+    subflow_count: u64,
+}
+
+impl SubflowTracker {
+    /// Create a tracker without subflows
+    pub fn new(max_gap: TimeDelta) -> SubflowTracker {
+        SubflowTracker {
+            max_gap,
+            last_time: None,
+            subflow_count: 0,
+        }
+    }
+
+    /// Include a packet of any direction received at the given time
+    pub fn include(&mut self, time: DateTime<Utc>) {
+        match self.last_time {
+            Some(last_time) if time - last_time <= self.max_gap => {}
+            _ => self.subflow_count += 1,
+        }
+        self.last_time = Some(time);
+    }
+
+    /// Number of subflows found
+    pub fn subflow_count(&self) -> u64 {
+        self.subflow_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bulk_detection() {
+        let start = DateTime::from_timestamp(0, 0).unwrap();
+        let mut v = BulkTracker::new(4, TimeDelta::seconds(1));
+
+        // Three packets are not enough for a bulk
+        for i in 0..3 {
+            v.include(true, start + TimeDelta::milliseconds(i * 100), 100);
+        }
+        assert_eq!(v.forward().bulk_count(), 0);
+
+        // The fourth one turns them into a bulk and the fifth extends it
+        v.include(true, start + TimeDelta::milliseconds(300), 100);
+        v.include(true, start + TimeDelta::milliseconds(400), 100);
+        assert_eq!(v.forward().bulk_count(), 1);
+        assert_eq!(v.forward().packet_count(), 5);
+        assert_eq!(v.forward().byte_count(), 500);
+        assert_eq!(v.forward().duration(), TimeDelta::milliseconds(400));
+        assert_eq!(v.forward().bulk_rate(), 1250.0);
+
+        // Empty packets are ignored and packets on the opposite direction
+        // interrupt the candidate
+        for i in 0..3 {
+            v.include(true, start + TimeDelta::seconds(10 + i), 0);
+            v.include(true, start + TimeDelta::seconds(10 + i), 100);
+            v.include(false, start + TimeDelta::seconds(10 + i), 100);
+        }
+        assert_eq!(v.forward().bulk_count(), 1);
+        assert_eq!(v.backward().bulk_count(), 0);
+        assert_eq!(v.forward().mean_packets_per_bulk(), 5.0);
+        assert_eq!(v.forward().mean_bytes_per_bulk(), 500.0);
+
+        // Single packets are bulks if the minimum is one or less
+        for min_packets in [0, 1] {
+            let mut v = BulkTracker::new(min_packets, TimeDelta::seconds(1));
+            v.include(true, start, 100);
+            v.include(true, start + TimeDelta::seconds(10), 100);
+            v.include(true, start + TimeDelta::milliseconds(10_500), 100);
+            assert_eq!(v.forward().bulk_count(), 2);
+            assert_eq!(v.forward().packet_count(), 3);
+            assert_eq!(v.forward().mean_bytes_per_bulk(), 150.0);
+        }
+    }
+
+    #[test]
+    fn test_subflow_detection() {
+        let start = DateTime::from_timestamp(0, 0).unwrap();
+        let mut v = SubflowTracker::new(TimeDelta::seconds(1));
+
+        assert_eq!(v.subflow_count(), 0);
+
+        v.include(start);
+        v.include(start + TimeDelta::seconds(1));
+        assert_eq!(v.subflow_count(), 1);
+
+        v.include(start + TimeDelta::seconds(3));
+        assert_eq!(v.subflow_count(), 2);
+    }
+}
@@ -0,0 +1,219 @@
+use super::{
+    bulk::{BulkTracker, DirectionBulks, SubflowTracker},
+    FlowStat, FlowTimes, StatSettings,
+};
+use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
+use std::io::{BufWriter, Error, Write};
+
+/// Bulk transfers of each direction and subflows of the flow, with the
+/// thresholds given on the settings. Sizes are transport payload bytes
+#[derive(Debug, Clone)]
+pub struct BulkTransfer {
+    bulks: BulkTracker,
+    subflows: SubflowTracker,
+
+    forward_packet_count: u64,
+    backward_packet_count: u64,
+    forward_payload_bytes: u64,
+    backward_payload_bytes: u64,
+}
+
+fn extract_payload_bytes(sliced_packet: &etherparse::SlicedPacket) -> u64 {
+    let payload_len = match &sliced_packet.transport {
+        Some(etherparse::TransportSlice::Icmpv4(s)) => s.payload().len(),
+        Some(etherparse::TransportSlice::Icmpv6(s)) => s.payload().len(),
+        Some(etherparse::TransportSlice::Udp(s)) => s.payload().len(),
+        Some(etherparse::TransportSlice::Tcp(s)) => s.payload().len(),
+        None => 0,
+    };
+    payload_len.try_into().unwrap()
+}
+
+impl BulkTransfer {
+    fn include_packet(
+        &mut self,
+        is_forward: bool,
+        flow_times: &FlowTimes,
+        sliced_packet: &etherparse::SlicedPacket,
+    ) {
+        let time = flow_times.last_packet_time;
+        let payload_bytes = extract_payload_bytes(sliced_packet);
+
+        if is_forward {
+            self.forward_packet_count += 1;
+            self.forward_payload_bytes += payload_bytes;
+        } else {
+            self.backward_packet_count += 1;
+            self.backward_payload_bytes += payload_bytes;
+        }
+        self.bulks.include(is_forward, time, payload_bytes);
+        self.subflows.include(time);
+    }
+
+    fn write_direction_csv_value<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        bulks: &DirectionBulks,
+        subflow_count: u64,
+        packet_count: u64,
+        payload_bytes: u64,
+    ) -> Result<(), Error> {
+        write!(writer, "{},", bulks.bulk_count())?;
+        write!(writer, "{},", bulks.packet_count())?;
+        write!(writer, "{},", bulks.byte_count())?;
+        write!(writer, "{:.9},", bulks.mean_packets_per_bulk())?;
+        write!(writer, "{:.9},", bulks.mean_bytes_per_bulk())?;
+        write!(
+            writer,
+            "{:.9},",
+            bulks.duration().num_microseconds().unwrap_or(0) as f64 / 1_000_000.0
+        )?;
+        write!(writer, "{:.9},", bulks.bulk_rate())?;
+
+        if subflow_count == 0 {
+            write!(writer, "{},{},", 0, 0)?;
+        } else {
+            write!(
+                writer,
+                "{:.9},{:.9},",
+                packet_count as f64 / subflow_count as f64,
+                payload_bytes as f64 / subflow_count as f64
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl FlowStat for BulkTransfer {
+    fn from_packet(
+        settings: &StatSettings,
+        _identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        let mut bulk_transfer = BulkTransfer {
+            bulks: BulkTracker::new(settings.bulk_min_packets(), settings.bulk_timeout()),
+            subflows: SubflowTracker::new(settings.subflow_timeout()),
+            forward_packet_count: 0,
+            backward_packet_count: 0,
+            forward_payload_bytes: 0,
+            backward_payload_bytes: 0,
+        };
+
+        bulk_transfer.include_packet(true, flow_times, sliced_packet);
+        bulk_transfer
+    }
+    fn include(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        let is_forward = identifier.is_forward(sliced_packet);
+
+        self.include_packet(is_forward, flow_times, sliced_packet);
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        for direction in ["forward", "backward"] {
+            write!(writer, "{}_bulk_count,", direction)?;
+            write!(writer, "{}_bulk_packets_sum,", direction)?;
+            write!(writer, "{}_bulk_bytes_sum,", direction)?;
+            write!(writer, "{}_bulk_packets_mean,", direction)?;
+            write!(writer, "{}_bulk_bytes_mean,", direction)?;
+            write!(writer, "{}_bulk_duration_seconds,", direction)?;
+            write!(writer, "{}_bulk_bytes_s,", direction)?;
+            write!(writer, "{}_subflow_packets_mean,", direction)?;
+            write!(writer, "{}_subflow_bytes_mean,", direction)?;
+        }
+        write!(writer, "subflow_count,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        let subflow_count = self.subflows.subflow_count();
+
+        Self::write_direction_csv_value(
+            writer,
+            self.bulks.forward(),
+            subflow_count,
+            self.forward_packet_count,
+            self.forward_payload_bytes,
+        )?;
+        Self::write_direction_csv_value(
+            writer,
+            self.bulks.backward(),
+            subflow_count,
+            self.backward_packet_count,
+            self.backward_payload_bytes,
+        )?;
+        write!(writer, "{},", subflow_count)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::test_flow::TestFlow;
+    use chrono::TimeDelta;
+
+    #[test]
+    fn test_bulk_threshold() {
+        let settings =
+            StatSettings::default().with_bulk_thresholds(3, TimeDelta::milliseconds(100));
+        let mut v = TestFlow::<BulkTransfer>::new(settings, 40000, 53);
+        v.udp(true, 0, &[0; 100]).udp(true, 100_000, &[0; 100]);
+        assert_eq!(v.value("forward_bulk_count"), "0");
+
+        // The third packet reaches the threshold, while the backward packet
+        // after a longer gap is not part of it
+        v.udp(true, 200_000, &[0; 100])
+            .udp(false, 400_000, &[0; 10]);
+        assert_eq!(v.value("forward_bulk_count"), "1");
+        assert_eq!(v.value("forward_bulk_packets_sum"), "3");
+        assert_eq!(v.value("forward_bulk_bytes_sum"), "300");
+        assert_eq!(v.value("forward_bulk_duration_seconds"), "0.200000000");
+        assert_eq!(v.value("forward_bulk_bytes_s"), "1500.000000000");
+        assert_eq!(v.value("backward_bulk_count"), "0");
+
+        // Every packet with payload is a bulk when the minimum is 0 or 1
+        for min_packets in [0, 1] {
+            let settings = StatSettings::default()
+                .with_bulk_thresholds(min_packets, TimeDelta::milliseconds(100));
+            let mut v = TestFlow::<BulkTransfer>::new(settings, 40000, 53);
+            v.udp(true, 0, &[0; 100])
+                .udp(false, 50_000, &[0; 10])
+                .udp(true, 100_000, b"");
+            assert_eq!(v.value("forward_bulk_count"), "1");
+            assert_eq!(v.value("backward_bulk_count"), "1");
+            assert_eq!(v.value("backward_bulk_bytes_sum"), "10");
+        }
+    }
+
+    #[test]
+    fn test_subflow_timeout() {
+        let settings = StatSettings::default().with_subflow_timeout(TimeDelta::milliseconds(500));
+        let mut v = TestFlow::<BulkTransfer>::new(settings, 40000, 53);
+        // A gap equal to the timeout keeps the subflow
+        v.udp(true, 0, &[0; 100])
+            .udp(false, 500_000, &[0; 20])
+            .udp(true, 1_000_000, &[0; 100]);
+        assert_eq!(v.value("subflow_count"), "1");
+
+        v.udp(true, 1_500_001, &[0; 100]);
+        assert_eq!(v.value("subflow_count"), "2");
+        assert_eq!(v.value("forward_subflow_packets_mean"), "1.500000000");
+        assert_eq!(v.value("forward_subflow_bytes_mean"), "150.000000000");
+        assert_eq!(v.value("backward_subflow_packets_mean"), "0.500000000");
+        assert_eq!(v.value("backward_subflow_bytes_mean"), "10.000000000");
+    }
+}
@@ -1,17 +1,23 @@
-use super::{running_stat::RunningStat, FlowStat, FlowTimes};
+use super::{running_stat::RunningStat, FlowStat, FlowTimes, StatSettings};
 use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
 use std::{
     io::{BufWriter, Error, Write},
-    net::IpAddr,
+    rc::Rc,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ByteCount {
     bidirectional: RunningStat,
     forward: RunningStat,
     backward: RunningStat,
+    /// Percentiles estimated on the packet sizes
+    percentiles: Rc<[u8]>,
+    /// Whether the skewness and kurtosis are written
+    higher_moments: bool,
 }
 
+/// Get the number of bytes received to obtain the given packet, including the
+/// link headers of all the fragments if it was reassembled
 pub fn extract_byte_count(
     packet_header: &pcap::PacketHeader,
     reasembly_information: Option<&FragmentReasemblyInformation>,
@@ -24,6 +30,7 @@ pub fn extract_byte_count(
 
 impl FlowStat for ByteCount {
     fn from_packet(
+        settings: &StatSettings,
         _identifier: &TransportFlowIdentifier,
         _flow_times: &FlowTimes,
         packet_header: &pcap::PacketHeader,
@@ -32,9 +39,16 @@ impl FlowStat for ByteCount {
     ) -> Self {
         let count = extract_byte_count(packet_header, reasembly_information);
 
-        let mut bidirectional = RunningStat::new();
-        let mut forward = RunningStat::new();
-        let backward = RunningStat::new();
+        let percentiles = settings.shared_percentiles();
+        let new_stat = if percentiles.is_empty() {
+            RunningStat::new
+        } else {
+            RunningStat::with_quantiles
+        };
+
+        let mut bidirectional = new_stat();
+        let mut forward = new_stat();
+        let backward = new_stat();
 
         bidirectional.include(count);
         forward.include(count);
@@ -43,6 +57,8 @@ impl FlowStat for ByteCount {
             bidirectional,
             forward,
             backward,
+            percentiles,
+            higher_moments: settings.higher_moments(),
         }
     }
     fn include(
@@ -55,17 +71,11 @@ impl FlowStat for ByteCount {
     ) {
         let count = extract_byte_count(packet_header, reasembly_information);
 
-        let src_ip = match &sliced_packet.net {
-            Some(header) => match header {
-                etherparse::NetSlice::Ipv4(v) => IpAddr::V4(v.header().source_addr()),
-                etherparse::NetSlice::Ipv6(v) => IpAddr::V6(v.header().source_addr()),
-            },
-            None => panic!("Unexpected sliced packet without net layer"),
-        };
+        let is_forward = identifier.is_forward(sliced_packet);
 
         self.bidirectional.include(count);
 
-        if src_ip == identifier.source_ip {
+        if is_forward {
             self.forward.include(count);
         } else {
             self.backward.include(count);
@@ -73,6 +83,7 @@ impl FlowStat for ByteCount {
     }
     fn write_csv_header<T: ?Sized + std::io::Write>(
         writer: &mut BufWriter<T>,
+        settings: &StatSettings,
     ) -> Result<(), Error> {
         write!(writer, "bidirectional_packet_bytes_sum,")?;
         write!(writer, "bidirectional_packet_bytes_max,")?;
@@ -98,6 +109,19 @@ impl FlowStat for ByteCount {
 
         write!(writer, "down_up_bytes_ratio,")?;
 
+        for direction in ["bidirectional", "forward", "backward"] {
+            for percentile in settings.percentiles() {
+                write!(writer, "{}_packet_bytes_p{},", direction, percentile)?;
+            }
+        }
+
+        if settings.higher_moments() {
+            for direction in ["bidirectional", "forward", "backward"] {
+                write!(writer, "{}_packet_bytes_skewness,", direction)?;
+                write!(writer, "{}_packet_bytes_kurtosis,", direction)?;
+            }
+        }
+
         Ok(())
     }
     fn write_csv_value<T: ?Sized + std::io::Write>(
@@ -156,6 +180,43 @@ impl FlowStat for ByteCount {
             (self.backward.current_sum() as f64) / (self.forward.current_sum() as f64)
         )?;
 
+        for stat in [&self.bidirectional, &self.forward, &self.backward] {
+            for percentile in self.percentiles.iter() {
+                write!(
+                    writer,
+                    "{:.9},",
+                    stat.current_percentile(*percentile).unwrap_or(0.0)
+                )?;
+            }
+        }
+
+        if self.higher_moments {
+            for stat in [&self.bidirectional, &self.forward, &self.backward] {
+                write!(writer, "{:.9},", stat.current_skewness())?;
+                write!(writer, "{:.9},", stat.current_kurtosis())?;
+            }
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::test_flow::{TestFlow, ACK, SYN};
+
+    #[test]
+    fn test_bytes_per_second() {
+        let mut v = TestFlow::<ByteCount>::new(StatSettings::default(), 40000, 80);
+        v.tcp(true, 0, SYN, 100, 0, b"")
+            .tcp(false, 250_000, SYN | ACK, 500, 101, b"")
+            .tcp(true, 500_000, ACK, 101, 501, b"");
+
+        // Ethernet, IPv4 and TCP headers of 54 bytes
+        assert_eq!(v.value("bidirectional_packet_bytes_sum"), "162");
+        assert_eq!(v.value("bidirectional_bytes_s"), "324.000000000");
+        assert_eq!(v.value("forward_bytes_s"), "216.000000000");
+        assert_eq!(v.value("backward_bytes_s"), "108.000000000");
+    }
+}
@@ -0,0 +1,84 @@
+/// Cursor over the big endian fields of a message
+#[derive(Debug, Clone)]
+pub struct ByteReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(data: &'a [u8]) -> ByteReader<'a> {
+        ByteReader { data, position: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    /// Number of bytes read so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.position.checked_add(len)?;
+        let bytes = self.data.get(self.position..end)?;
+        self.position = end;
+        Some(bytes)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        let bytes = self.bytes(2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u24(&mut self) -> Option<usize> {
+        let bytes = self.bytes(3)?;
+        Some(usize::from(bytes[0]) << 16 | usize::from(bytes[1]) << 8 | usize::from(bytes[2]))
+    }
+
+    /// The next bytes, prefixed with their length on 1 or 2 bytes
+    pub fn prefixed_bytes(&mut self, prefix_len: usize) -> Option<&'a [u8]> {
+        let len = match prefix_len {
+            1 => usize::from(self.u8()?),
+            _ => usize::from(self.u16()?),
+        };
+        self.bytes(len)
+    }
+
+    /// Reader over the next bytes, prefixed with their length on 1 or 2 bytes
+    pub fn prefixed(&mut self, prefix_len: usize) -> Option<ByteReader<'a>> {
+        Some(ByteReader::new(self.prefixed_bytes(prefix_len)?))
+    }
+
+    /// The remaining bytes read as a list of u16
+    pub fn u16_list(&mut self) -> Option<Vec<u16>> {
+        let mut values = Vec::new();
+        while !self.is_empty() {
+            values.push(self.u16()?);
+        }
+        Some(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_reader() {
+        let mut v = ByteReader::new(&[1, 0, 2, 0, 0, 3, 2, 0, 4, 0]);
+        assert_eq!(v.u8(), Some(1));
+        assert_eq!(v.u16(), Some(2));
+        assert_eq!(v.u24(), Some(3));
+        assert_eq!(v.prefixed(1).and_then(|mut r| r.u16_list()), Some(vec![4]));
+        assert_eq!(v.position(), 9);
+        assert_eq!(v.bytes(2), None);
+        assert_eq!(v.bytes(usize::MAX), None);
+        assert_eq!(v.prefixed_bytes(1), Some(&[][..]));
+        assert!(v.is_empty());
+    }
+}
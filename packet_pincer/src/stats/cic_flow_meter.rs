@@ -0,0 +1,652 @@
+use super::{
+    bulk::{BulkTracker, DirectionBulks, SubflowTracker},
+    running_stat::RunningStat,
+    FlowStat, FlowTimes, StatSettings,
+};
+use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::io::{BufWriter, Error, Write};
+
+/// The features computed by CICFlowMeter, with its column names and units.
+/// Packet lengths are transport payload lengths and times are microseconds
+#[derive(Debug, Clone)]
+pub struct CicFlowMeter {
+    forward_payload_bytes: RunningStat,
+    backward_payload_bytes: RunningStat,
+    bidirectional_payload_bytes: RunningStat,
+
+    last_time: DateTime<Utc>,
+    forward_last_time: DateTime<Utc>,
+    backward_last_time: Option<DateTime<Utc>>,
+    bidirectional_iat: RunningStat,
+    forward_iat: RunningStat,
+    backward_iat: RunningStat,
+
+    forward_psh_count: u32,
+    backward_psh_count: u32,
+    forward_urg_count: u32,
+    backward_urg_count: u32,
+    fin_count: u32,
+    syn_count: u32,
+    rst_count: u32,
+    psh_count: u32,
+    ack_count: u32,
+    urg_count: u32,
+    cwr_count: u32,
+    ece_count: u32,
+
+    forward_header_bytes: RunningStat,
+    backward_header_bytes: RunningStat,
+    forward_initial_window_bytes: Option<u16>,
+    backward_initial_window_bytes: Option<u16>,
+    forward_packets_with_payload_count: u32,
+
+    bulks: BulkTracker,
+    subflows: SubflowTracker,
+
+    active_start: DateTime<Utc>,
+    active_end: DateTime<Utc>,
+    active_microseconds: RunningStat,
+    idle_microseconds: RunningStat,
+}
+
+/// The parts of a packet used by CICFlowMeter
+struct PacketEval {
+    header_bytes: u64,
+    payload_bytes: u64,
+    window_bytes: Option<u16>,
+    fin: bool,
+    syn: bool,
+    rst: bool,
+    psh: bool,
+    ack: bool,
+    urg: bool,
+    cwr: bool,
+    ece: bool,
+}
+
+fn evaluate(sliced_packet: &etherparse::SlicedPacket) -> PacketEval {
+    let mut eval = PacketEval {
+        header_bytes: 0,
+        payload_bytes: 0,
+        window_bytes: None,
+        fin: false,
+        syn: false,
+        rst: false,
+        psh: false,
+        ack: false,
+        urg: false,
+        cwr: false,
+        ece: false,
+    };
+
+    match &sliced_packet.transport {
+        Some(etherparse::TransportSlice::Tcp(s)) => {
+            eval.header_bytes = s.header_len().try_into().unwrap();
+            eval.payload_bytes = s.payload().len().try_into().unwrap();
+            eval.window_bytes = Some(s.window_size());
+            eval.fin = s.fin();
+            eval.syn = s.syn();
+            eval.rst = s.rst();
+            eval.psh = s.psh();
+            eval.ack = s.ack();
+            eval.urg = s.urg();
+            eval.cwr = s.cwr();
+            eval.ece = s.ece();
+        }
+        Some(etherparse::TransportSlice::Udp(s)) => {
+            eval.header_bytes = s.header_len().try_into().unwrap();
+            eval.payload_bytes = s.payload().len().try_into().unwrap();
+        }
+        Some(etherparse::TransportSlice::Icmpv4(s)) => {
+            eval.header_bytes = s.header_len().try_into().unwrap();
+            eval.payload_bytes = s.payload().len().try_into().unwrap();
+        }
+        Some(etherparse::TransportSlice::Icmpv6(s)) => {
+            eval.header_bytes = s.header_len().try_into().unwrap();
+            eval.payload_bytes = s.payload().len().try_into().unwrap();
+        }
+        None => {}
+    }
+
+    eval
+}
+
+fn microseconds(time_delta: TimeDelta) -> u64 {
+    time_delta
+        .num_microseconds()
+        .expect("Time deltas microseconds should fit in a i64")
+        .try_into()
+        .expect("Time deltas microseconds should convert to u64")
+}
+
+impl CicFlowMeter {
+    /// Gap without packets after which a new subflow starts
+    const SUBFLOW_TIMEOUT: TimeDelta = TimeDelta::seconds(1);
+    /// Gap without packets on the same direction after which a bulk ends
+    const BULK_TIMEOUT: TimeDelta = TimeDelta::seconds(1);
+    /// Minimum number of packets with payload on a bulk
+    const BULK_MIN_PACKETS: u32 = 4;
+    /// Gap without packets after which an active period ends
+    const ACTIVITY_TIMEOUT: TimeDelta = TimeDelta::seconds(5);
+    /// Format of the flow timestamps
+    const TIMESTAMP_FORMAT: &'static str = "%d/%m/%Y %I:%M:%S %p";
+
+    /// Write the columns identifying the flow, which replace the identifier
+    /// and flow times columns
+    pub fn write_flow_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+    ) -> Result<(), Error> {
+        write!(writer, "Flow ID,")?;
+        write!(writer, "Source IP,")?;
+        write!(writer, "Source Port,")?;
+        write!(writer, "Destination IP,")?;
+        write!(writer, "Destination Port,")?;
+        write!(writer, "Protocol,")?;
+        write!(writer, "Timestamp,")?;
+        Ok(())
+    }
+
+    /// Write the values identifying the flow, in the same order as
+    /// `write_flow_csv_header`
+    pub fn write_flow_csv_value<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        write!(
+            writer,
+            "{}-{}-{}-{}-{},",
+            identifier.source_ip(),
+            identifier.dest_ip(),
+            identifier.source_port(),
+            identifier.dest_port(),
+            identifier.transport_protocol().0
+        )?;
+        write!(writer, "{},", identifier.source_ip())?;
+        write!(writer, "{},", identifier.source_port())?;
+        write!(writer, "{},", identifier.dest_ip())?;
+        write!(writer, "{},", identifier.dest_port())?;
+        write!(writer, "{},", identifier.transport_protocol().0)?;
+        write!(
+            writer,
+            "{},",
+            flow_times
+                .first_packet_time()
+                .format(Self::TIMESTAMP_FORMAT)
+        )?;
+        Ok(())
+    }
+
+    fn include_packet(&mut self, is_forward: bool, time: DateTime<Utc>, eval: &PacketEval) {
+        self.bidirectional_payload_bytes.include(eval.payload_bytes);
+        if is_forward {
+            self.forward_payload_bytes.include(eval.payload_bytes);
+            self.forward_header_bytes.include(eval.header_bytes);
+            if 0 < eval.payload_bytes {
+                self.forward_packets_with_payload_count += 1;
+            }
+            if self.forward_initial_window_bytes.is_none() {
+                self.forward_initial_window_bytes = eval.window_bytes;
+            }
+            self.forward_psh_count += u32::from(eval.psh);
+            self.forward_urg_count += u32::from(eval.urg);
+        } else {
+            self.backward_payload_bytes.include(eval.payload_bytes);
+            self.backward_header_bytes.include(eval.header_bytes);
+            if self.backward_initial_window_bytes.is_none() {
+                self.backward_initial_window_bytes = eval.window_bytes;
+            }
+            self.backward_psh_count += u32::from(eval.psh);
+            self.backward_urg_count += u32::from(eval.urg);
+        }
+
+        self.fin_count += u32::from(eval.fin);
+        self.syn_count += u32::from(eval.syn);
+        self.rst_count += u32::from(eval.rst);
+        self.psh_count += u32::from(eval.psh);
+        self.ack_count += u32::from(eval.ack);
+        self.urg_count += u32::from(eval.urg);
+        self.cwr_count += u32::from(eval.cwr);
+        self.ece_count += u32::from(eval.ece);
+
+        self.bulks.include(is_forward, time, eval.payload_bytes);
+        self.subflows.include(time);
+    }
+
+    fn write_length_csv_value<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        lengths: &RunningStat,
+    ) -> Result<(), Error> {
+        write!(writer, "{},", lengths.current_max().unwrap_or(0))?;
+        write!(writer, "{},", lengths.current_min().unwrap_or(0))?;
+        write!(writer, "{:.9},", lengths.current_mean())?;
+        write!(writer, "{:.9},", lengths.current_standard_deviation())?;
+        Ok(())
+    }
+
+    fn write_iat_csv_value<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        iat: &RunningStat,
+        with_total: bool,
+    ) -> Result<(), Error> {
+        if with_total {
+            write!(writer, "{},", iat.current_sum())?;
+        }
+        write!(writer, "{:.9},", iat.current_mean())?;
+        write!(writer, "{:.9},", iat.current_standard_deviation())?;
+        write!(writer, "{},", iat.current_max().unwrap_or(0))?;
+        write!(writer, "{},", iat.current_min().unwrap_or(0))?;
+        Ok(())
+    }
+
+    fn write_bulk_csv_value<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        bulks: &DirectionBulks,
+    ) -> Result<(), Error> {
+        // CICFlowMeter truncates the bulk averages to integers
+        write!(writer, "{},", bulks.mean_bytes_per_bulk() as u64)?;
+        write!(writer, "{},", bulks.mean_packets_per_bulk() as u64)?;
+        write!(writer, "{},", bulks.bulk_rate() as u64)?;
+        Ok(())
+    }
+
+    fn write_activity_csv_value<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        activity: &RunningStat,
+    ) -> Result<(), Error> {
+        write!(writer, "{:.9},", activity.current_mean())?;
+        write!(writer, "{:.9},", activity.current_standard_deviation())?;
+        write!(writer, "{},", activity.current_max().unwrap_or(0))?;
+        write!(writer, "{},", activity.current_min().unwrap_or(0))?;
+        Ok(())
+    }
+}
+
+impl FlowStat for CicFlowMeter {
+    fn from_packet(
+        _settings: &StatSettings,
+        _identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        let time = flow_times.last_packet_time;
+        let mut cic_flow_meter = CicFlowMeter {
+            forward_payload_bytes: RunningStat::new(),
+            backward_payload_bytes: RunningStat::new(),
+            bidirectional_payload_bytes: RunningStat::new(),
+            last_time: time,
+            forward_last_time: time,
+            backward_last_time: None,
+            bidirectional_iat: RunningStat::new(),
+            forward_iat: RunningStat::new(),
+            backward_iat: RunningStat::new(),
+            forward_psh_count: 0,
+            backward_psh_count: 0,
+            forward_urg_count: 0,
+            backward_urg_count: 0,
+            fin_count: 0,
+            syn_count: 0,
+            rst_count: 0,
+            psh_count: 0,
+            ack_count: 0,
+            urg_count: 0,
+            cwr_count: 0,
+            ece_count: 0,
+            forward_header_bytes: RunningStat::new(),
+            backward_header_bytes: RunningStat::new(),
+            forward_initial_window_bytes: None,
+            backward_initial_window_bytes: None,
+            forward_packets_with_payload_count: 0,
+            bulks: BulkTracker::new(Self::BULK_MIN_PACKETS, Self::BULK_TIMEOUT),
+            subflows: SubflowTracker::new(Self::SUBFLOW_TIMEOUT),
+            active_start: time,
+            active_end: time,
+            active_microseconds: RunningStat::new(),
+            idle_microseconds: RunningStat::new(),
+        };
+
+        cic_flow_meter.include_packet(true, time, &evaluate(sliced_packet));
+        cic_flow_meter
+    }
+    fn include(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        let is_forward = identifier.is_forward(sliced_packet);
+        let time = flow_times.last_packet_time;
+
+        // Update inter arrival times
+        self.bidirectional_iat
+            .include(microseconds(time - self.last_time));
+        self.last_time = time;
+        if is_forward {
+            self.forward_iat
+                .include(microseconds(time - self.forward_last_time));
+            self.forward_last_time = time;
+        } else {
+            if let Some(backward_last_time) = self.backward_last_time {
+                self.backward_iat
+                    .include(microseconds(time - backward_last_time));
+            }
+            self.backward_last_time = Some(time);
+        }
+
+        // Update active and idle periods
+        if Self::ACTIVITY_TIMEOUT < time - self.active_end {
+            let active = microseconds(self.active_end - self.active_start);
+            if 0 < active {
+                self.active_microseconds.include(active);
+            }
+            self.idle_microseconds
+                .include(microseconds(time - self.active_end));
+            self.active_start = time;
+        }
+        self.active_end = time;
+
+        self.include_packet(is_forward, time, &evaluate(sliced_packet));
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        write!(writer, "Flow Duration,")?;
+        write!(writer, "Total Fwd Packets,")?;
+        write!(writer, "Total Backward Packets,")?;
+        write!(writer, "Total Length of Fwd Packets,")?;
+        write!(writer, "Total Length of Bwd Packets,")?;
+        write!(writer, "Fwd Packet Length Max,")?;
+        write!(writer, "Fwd Packet Length Min,")?;
+        write!(writer, "Fwd Packet Length Mean,")?;
+        write!(writer, "Fwd Packet Length Std,")?;
+        write!(writer, "Bwd Packet Length Max,")?;
+        write!(writer, "Bwd Packet Length Min,")?;
+        write!(writer, "Bwd Packet Length Mean,")?;
+        write!(writer, "Bwd Packet Length Std,")?;
+        write!(writer, "Flow Bytes/s,")?;
+        write!(writer, "Flow Packets/s,")?;
+        write!(writer, "Flow IAT Mean,")?;
+        write!(writer, "Flow IAT Std,")?;
+        write!(writer, "Flow IAT Max,")?;
+        write!(writer, "Flow IAT Min,")?;
+        write!(writer, "Fwd IAT Total,")?;
+        write!(writer, "Fwd IAT Mean,")?;
+        write!(writer, "Fwd IAT Std,")?;
+        write!(writer, "Fwd IAT Max,")?;
+        write!(writer, "Fwd IAT Min,")?;
+        write!(writer, "Bwd IAT Total,")?;
+        write!(writer, "Bwd IAT Mean,")?;
+        write!(writer, "Bwd IAT Std,")?;
+        write!(writer, "Bwd IAT Max,")?;
+        write!(writer, "Bwd IAT Min,")?;
+        write!(writer, "Fwd PSH Flags,")?;
+        write!(writer, "Bwd PSH Flags,")?;
+        write!(writer, "Fwd URG Flags,")?;
+        write!(writer, "Bwd URG Flags,")?;
+        write!(writer, "Fwd Header Length,")?;
+        write!(writer, "Bwd Header Length,")?;
+        write!(writer, "Fwd Packets/s,")?;
+        write!(writer, "Bwd Packets/s,")?;
+        write!(writer, "Min Packet Length,")?;
+        write!(writer, "Max Packet Length,")?;
+        write!(writer, "Packet Length Mean,")?;
+        write!(writer, "Packet Length Std,")?;
+        write!(writer, "Packet Length Variance,")?;
+        write!(writer, "FIN Flag Count,")?;
+        write!(writer, "SYN Flag Count,")?;
+        write!(writer, "RST Flag Count,")?;
+        write!(writer, "PSH Flag Count,")?;
+        write!(writer, "ACK Flag Count,")?;
+        write!(writer, "URG Flag Count,")?;
+        write!(writer, "CWE Flag Count,")?;
+        write!(writer, "ECE Flag Count,")?;
+        write!(writer, "Down/Up Ratio,")?;
+        write!(writer, "Average Packet Size,")?;
+        write!(writer, "Avg Fwd Segment Size,")?;
+        write!(writer, "Avg Bwd Segment Size,")?;
+        // CICFlowMeter writes this column twice
+        write!(writer, "Fwd Header Length,")?;
+        write!(writer, "Fwd Avg Bytes/Bulk,")?;
+        write!(writer, "Fwd Avg Packets/Bulk,")?;
+        write!(writer, "Fwd Avg Bulk Rate,")?;
+        write!(writer, "Bwd Avg Bytes/Bulk,")?;
+        write!(writer, "Bwd Avg Packets/Bulk,")?;
+        write!(writer, "Bwd Avg Bulk Rate,")?;
+        write!(writer, "Subflow Fwd Packets,")?;
+        write!(writer, "Subflow Fwd Bytes,")?;
+        write!(writer, "Subflow Bwd Packets,")?;
+        write!(writer, "Subflow Bwd Bytes,")?;
+        write!(writer, "Init_Win_bytes_forward,")?;
+        write!(writer, "Init_Win_bytes_backward,")?;
+        write!(writer, "act_data_pkt_fwd,")?;
+        write!(writer, "min_seg_size_forward,")?;
+        write!(writer, "Active Mean,")?;
+        write!(writer, "Active Std,")?;
+        write!(writer, "Active Max,")?;
+        write!(writer, "Active Min,")?;
+        write!(writer, "Idle Mean,")?;
+        write!(writer, "Idle Std,")?;
+        write!(writer, "Idle Max,")?;
+        write!(writer, "Idle Min,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        let forward_packets = self.forward_payload_bytes.current_count();
+        let backward_packets = self.backward_payload_bytes.current_count();
+        let duration_microseconds = microseconds(flow_times.duration());
+
+        // Sizes
+        write!(writer, "{},", duration_microseconds)?;
+        write!(writer, "{},", forward_packets)?;
+        write!(writer, "{},", backward_packets)?;
+        write!(writer, "{},", self.forward_payload_bytes.current_sum())?;
+        write!(writer, "{},", self.backward_payload_bytes.current_sum())?;
+        Self::write_length_csv_value(writer, &self.forward_payload_bytes)?;
+        Self::write_length_csv_value(writer, &self.backward_payload_bytes)?;
+
+        // Rates
+        let per_second = |count: u64| {
+            if duration_microseconds == 0 {
+                0.0
+            } else {
+                count as f64 / (duration_microseconds as f64 / 1_000_000.0)
+            }
+        };
+        write!(
+            writer,
+            "{:.9},",
+            per_second(self.bidirectional_payload_bytes.current_sum())
+        )?;
+        write!(
+            writer,
+            "{:.9},",
+            per_second(forward_packets + backward_packets)
+        )?;
+
+        // Inter arrival times
+        Self::write_iat_csv_value(writer, &self.bidirectional_iat, false)?;
+        Self::write_iat_csv_value(writer, &self.forward_iat, true)?;
+        Self::write_iat_csv_value(writer, &self.backward_iat, true)?;
+
+        // Flags and headers
+        write!(writer, "{},", self.forward_psh_count)?;
+        write!(writer, "{},", self.backward_psh_count)?;
+        write!(writer, "{},", self.forward_urg_count)?;
+        write!(writer, "{},", self.backward_urg_count)?;
+        write!(writer, "{},", self.forward_header_bytes.current_sum())?;
+        write!(writer, "{},", self.backward_header_bytes.current_sum())?;
+        write!(writer, "{:.9},", per_second(forward_packets))?;
+        write!(writer, "{:.9},", per_second(backward_packets))?;
+
+        // Bidirectional sizes
+        write!(
+            writer,
+            "{},",
+            self.bidirectional_payload_bytes.current_min().unwrap_or(0)
+        )?;
+        write!(
+            writer,
+            "{},",
+            self.bidirectional_payload_bytes.current_max().unwrap_or(0)
+        )?;
+        write!(
+            writer,
+            "{:.9},",
+            self.bidirectional_payload_bytes.current_mean()
+        )?;
+        write!(
+            writer,
+            "{:.9},",
+            self.bidirectional_payload_bytes
+                .current_standard_deviation()
+        )?;
+        write!(
+            writer,
+            "{:.9},",
+            self.bidirectional_payload_bytes.current_variance()
+        )?;
+
+        // Flag counts
+        write!(writer, "{},", self.fin_count)?;
+        write!(writer, "{},", self.syn_count)?;
+        write!(writer, "{},", self.rst_count)?;
+        write!(writer, "{},", self.psh_count)?;
+        write!(writer, "{},", self.ack_count)?;
+        write!(writer, "{},", self.urg_count)?;
+        write!(writer, "{},", self.cwr_count)?;
+        write!(writer, "{},", self.ece_count)?;
+
+        // Ratios and segment sizes
+        write!(
+            writer,
+            "{},",
+            backward_packets.checked_div(forward_packets).unwrap_or(0)
+        )?;
+        write!(
+            writer,
+            "{:.9},",
+            self.bidirectional_payload_bytes.current_mean()
+        )?;
+        write!(writer, "{:.9},", self.forward_payload_bytes.current_mean())?;
+        write!(writer, "{:.9},", self.backward_payload_bytes.current_mean())?;
+        write!(writer, "{},", self.forward_header_bytes.current_sum())?;
+
+        // Bulks
+        Self::write_bulk_csv_value(writer, self.bulks.forward())?;
+        Self::write_bulk_csv_value(writer, self.bulks.backward())?;
+
+        // Subflows
+        let subflow_count = self.subflows.subflow_count().max(1);
+        write!(writer, "{},", forward_packets / subflow_count)?;
+        write!(
+            writer,
+            "{},",
+            self.forward_payload_bytes.current_sum() / subflow_count
+        )?;
+        write!(writer, "{},", backward_packets / subflow_count)?;
+        write!(
+            writer,
+            "{},",
+            self.backward_payload_bytes.current_sum() / subflow_count
+        )?;
+
+        // Transport
+        match self.forward_initial_window_bytes {
+            Some(window) => write!(writer, "{},", window)?,
+            None => write!(writer, "{},", -1)?,
+        }
+        match self.backward_initial_window_bytes {
+            Some(window) => write!(writer, "{},", window)?,
+            None => write!(writer, "{},", -1)?,
+        }
+        write!(writer, "{},", self.forward_packets_with_payload_count)?;
+        write!(
+            writer,
+            "{},",
+            self.forward_header_bytes.current_min().unwrap_or(0)
+        )?;
+
+        // Activity, including the active period still open
+        let mut active_microseconds = self.active_microseconds.clone();
+        let last_active = microseconds(self.active_end - self.active_start);
+        if 0 < last_active {
+            active_microseconds.include(last_active);
+        }
+        Self::write_activity_csv_value(writer, &active_microseconds)?;
+        Self::write_activity_csv_value(writer, &self.idle_microseconds)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::test_flow::{TestFlow, ACK, PSH, SYN};
+
+    #[test]
+    fn test_columns() {
+        let mut v = TestFlow::<CicFlowMeter>::new(StatSettings::default(), 40000, 80);
+        v.tcp(true, 0, SYN, 100, 0, b"")
+            .tcp(false, 1000, SYN | ACK, 500, 101, b"");
+        for i in 0..4 {
+            v.tcp(
+                true,
+                2000 + i * 1000,
+                PSH | ACK,
+                101 + i as u32 * 100,
+                501,
+                &[0; 100],
+            );
+        }
+        // The backward packet interrupts the forward bulk after its fourth
+        // packet, and the last packet starts a new subflow
+        v.tcp(false, 6000, PSH | ACK, 501, 501, &[0; 50]).tcp(
+            true,
+            2_006_000,
+            PSH | ACK,
+            501,
+            551,
+            &[0; 10],
+        );
+
+        let columns = v.columns();
+        assert_eq!(columns.len(), 77);
+        assert_eq!(
+            columns[0],
+            ("Flow Duration".to_string(), "2006000".to_string())
+        );
+
+        // Times are microseconds and rates are per second
+        assert_eq!(v.value("Flow Packets/s"), "3.988035892");
+        assert_eq!(v.value("Flow Bytes/s"), "229.312063809");
+        assert_eq!(v.value("Flow IAT Max"), "2000000");
+        assert_eq!(v.value("Flow IAT Min"), "1000");
+        assert_eq!(v.value("Fwd IAT Total"), "2006000");
+        assert_eq!(v.value("Fwd IAT Max"), "2001000");
+        assert_eq!(v.value("Bwd IAT Total"), "5000");
+        assert_eq!(v.value("Active Max"), "2006000");
+
+        // A bulk of 400 bytes sent in 3 milliseconds
+        assert_eq!(v.value("Fwd Avg Bytes/Bulk"), "400");
+        assert_eq!(v.value("Fwd Avg Packets/Bulk"), "4");
+        assert_eq!(v.value("Fwd Avg Bulk Rate"), "133333");
+        assert_eq!(v.value("Bwd Avg Bytes/Bulk"), "0");
+
+        // The counts are averaged on two subflows
+        assert_eq!(v.value("Subflow Fwd Packets"), "3");
+        assert_eq!(v.value("Subflow Fwd Bytes"), "205");
+        assert_eq!(v.value("Subflow Bwd Packets"), "1");
+        assert_eq!(v.value("Subflow Bwd Bytes"), "25");
+    }
+}
@@ -0,0 +1,192 @@
+use super::{
+    coap_message::{CoapMessage, COAP_PORT, MESSAGE_TYPES},
+    csv_text::escape_text,
+    running_stat::RunningStat,
+    FlowStat, FlowTimes, StatSettings,
+};
+use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
+use std::io::{BufWriter, Error, Write};
+
+/// Request methods with a dedicated count, in the order of their codes
+const METHODS: [&str; 4] = ["get", "post", "put", "delete"];
+
+/// Response code classes with a dedicated count
+const RESPONSE_CLASSES: [u8; 3] = [2, 4, 5];
+
+/// Largest number of different tokens kept
+const MAX_TOKENS: usize = 256;
+
+/// CoAP messages of UDP flows on the CoAP port or starting with a CoAP
+/// request: message types, methods, response classes, observe usage, tokens
+/// and the URI path of the first request
+#[derive(Debug, Clone)]
+pub struct Coap {
+    /// Whether the flow is CoAP, unknown until the first datagram when it is
+    /// not on the CoAP port
+    is_coap: Option<bool>,
+
+    message_count: u32,
+    malformed_count: u32,
+    message_type_counts: [u32; MESSAGE_TYPES.len()],
+    method_counts: [u32; METHODS.len()],
+    other_method_count: u32,
+    response_class_counts: [u32; RESPONSE_CLASSES.len()],
+    first_response_code: u16,
+    observe_count: u32,
+    token_length: RunningStat,
+    empty_token_count: u32,
+    tokens: Vec<Vec<u8>>,
+    first_uri_path: Option<Vec<u8>>,
+    payload_bytes: u64,
+}
+
+impl Coap {
+    fn include_packet(&mut self, sliced_packet: &etherparse::SlicedPacket) {
+        let Some(etherparse::TransportSlice::Udp(udp_slice)) = &sliced_packet.transport else {
+            return;
+        };
+        let message = CoapMessage::parse(udp_slice.payload());
+        let is_coap = *self
+            .is_coap
+            .get_or_insert_with(|| message.as_ref().is_some_and(CoapMessage::is_request));
+        if !is_coap {
+            return;
+        }
+        let Some(message) = message else {
+            self.malformed_count += 1;
+            return;
+        };
+
+        self.message_count += 1;
+        self.message_type_counts[usize::from(message.message_type)] += 1;
+        if message.is_request() {
+            // Method codes start at 0.01
+            match self
+                .method_counts
+                .get_mut(usize::from(message.code_detail) - 1)
+            {
+                Some(count) => *count += 1,
+                None => self.other_method_count += 1,
+            }
+            if self.first_uri_path.is_none() {
+                self.first_uri_path = Some(message.uri_path.join(&b'/'));
+            }
+        } else if let Some(i) = RESPONSE_CLASSES
+            .iter()
+            .position(|class| *class == message.code_class)
+        {
+            self.response_class_counts[i] += 1;
+            if self.first_response_code == 0 {
+                self.first_response_code =
+                    u16::from(message.code_class) * 100 + u16::from(message.code_detail);
+            }
+        }
+
+        if message.observe {
+            self.observe_count += 1;
+        }
+        // Empty messages carry no token
+        if message.code_class != 0 || message.code_detail != 0 {
+            self.token_length.include(message.token.len() as u64);
+            if message.token.is_empty() {
+                self.empty_token_count += 1;
+            } else if self.tokens.len() < MAX_TOKENS && !self.tokens.contains(&message.token) {
+                self.tokens.push(message.token);
+            }
+        }
+        self.payload_bytes += message.payload_len as u64;
+    }
+}
+
+impl FlowStat for Coap {
+    fn from_packet(
+        _settings: &StatSettings,
+        identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        let mut coap = Coap {
+            is_coap: (identifier.source_port() == COAP_PORT || identifier.dest_port() == COAP_PORT)
+                .then_some(true),
+            message_count: 0,
+            malformed_count: 0,
+            message_type_counts: [0; MESSAGE_TYPES.len()],
+            method_counts: [0; METHODS.len()],
+            other_method_count: 0,
+            response_class_counts: [0; RESPONSE_CLASSES.len()],
+            first_response_code: 0,
+            observe_count: 0,
+            token_length: RunningStat::new(),
+            empty_token_count: 0,
+            tokens: Vec::new(),
+            first_uri_path: None,
+            payload_bytes: 0,
+        };
+
+        coap.include_packet(sliced_packet);
+        coap
+    }
+    fn include(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        self.include_packet(sliced_packet);
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        write!(writer, "coap_message_count,coap_malformed_count,")?;
+        for message_type in MESSAGE_TYPES {
+            write!(writer, "coap_{}_count,", message_type)?;
+        }
+        for method in METHODS {
+            write!(writer, "coap_method_{}_count,", method)?;
+        }
+        write!(writer, "coap_method_other_count,")?;
+        for class in RESPONSE_CLASSES {
+            write!(writer, "coap_response_{}xx_count,", class)?;
+        }
+        write!(writer, "coap_first_response_code,coap_observe_count,")?;
+        write!(writer, "coap_token_length_mean,coap_token_length_max,")?;
+        write!(writer, "coap_empty_token_count,coap_token_count,")?;
+        write!(writer, "coap_first_uri_path,coap_payload_bytes,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        write!(writer, "{},{},", self.message_count, self.malformed_count)?;
+        for count in self.message_type_counts {
+            write!(writer, "{},", count)?;
+        }
+        for count in self.method_counts {
+            write!(writer, "{},", count)?;
+        }
+        write!(writer, "{},", self.other_method_count)?;
+        for count in self.response_class_counts {
+            write!(writer, "{},", count)?;
+        }
+        write!(writer, "{},", self.first_response_code)?;
+        write!(writer, "{},", self.observe_count)?;
+        write!(writer, "{:.9},", self.token_length.current_mean())?;
+        write!(writer, "{},", self.token_length.current_max().unwrap_or(0))?;
+        write!(writer, "{},", self.empty_token_count)?;
+        write!(writer, "{},", self.tokens.len())?;
+        write!(
+            writer,
+            "{},",
+            escape_text(self.first_uri_path.as_deref().unwrap_or_default())
+        )?;
+        write!(writer, "{},", self.payload_bytes)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+/// Port of CoAP without DTLS
+pub const COAP_PORT: u16 = 5683;
+
+/// Names of the message types, in the order of their values
+pub const MESSAGE_TYPES: [&str; 4] = ["confirmable", "non_confirmable", "ack", "reset"];
+
+const OBSERVE: u16 = 6;
+const URI_PATH: u16 = 11;
+
+/// Largest token length allowed
+const MAX_TOKEN_LEN: usize = 8;
+
+/// The parts of a CoAP message over UDP used by the stats
+#[derive(Debug, Clone)]
+pub struct CoapMessage {
+    /// Index of the type on `MESSAGE_TYPES`
+    pub message_type: u8,
+    /// Class of the code, 0 for requests and 2, 4 or 5 for responses
+    pub code_class: u8,
+    pub code_detail: u8,
+    pub token: Vec<u8>,
+    pub observe: bool,
+    pub uri_path: Vec<Vec<u8>>,
+    pub payload_len: usize,
+}
+
+/// Read the extended value of an option delta or length nibble
+fn extended_value(nibble: u8, message: &[u8], position: &mut usize) -> Option<u16> {
+    match nibble {
+        13 => {
+            let value = *message.get(*position)?;
+            *position += 1;
+            Some(u16::from(value) + 13)
+        }
+        14 => {
+            let bytes = message.get(*position..*position + 2)?;
+            *position += 2;
+            u16::from_be_bytes([bytes[0], bytes[1]]).checked_add(269)
+        }
+        15 => None,
+        _ => Some(u16::from(nibble)),
+    }
+}
+
+impl CoapMessage {
+    pub fn parse(message: &[u8]) -> Option<CoapMessage> {
+        let header = message.get(..4)?;
+        let token_len = usize::from(header[0] & 0x0F);
+        if header[0] >> 6 != 1 || token_len > MAX_TOKEN_LEN {
+            return None;
+        }
+        let code_class = header[1] >> 5;
+        let code_detail = header[1] & 0x1F;
+        if !matches!(code_class, 0 | 2 | 4 | 5) {
+            return None;
+        }
+        // Empty messages have nothing after the header
+        if header[1] == 0 && message.len() != 4 {
+            return None;
+        }
+
+        let mut coap_message = CoapMessage {
+            message_type: (header[0] >> 4) & 0b11,
+            code_class,
+            code_detail,
+            token: message.get(4..4 + token_len)?.to_vec(),
+            observe: false,
+            uri_path: Vec::new(),
+            payload_len: 0,
+        };
+
+        let mut position = 4 + token_len;
+        let mut option_number: u16 = 0;
+        while let Some(byte) = message.get(position) {
+            position += 1;
+            if *byte == 0xFF {
+                coap_message.payload_len = message.len() - position;
+                if coap_message.payload_len == 0 {
+                    return None;
+                }
+                break;
+            }
+
+            let delta = extended_value(byte >> 4, message, &mut position)?;
+            let len = usize::from(extended_value(byte & 0x0F, message, &mut position)?);
+            option_number = option_number.checked_add(delta)?;
+            let value = message.get(position..position + len)?;
+            position += len;
+
+            match option_number {
+                OBSERVE => coap_message.observe = true,
+                URI_PATH => coap_message.uri_path.push(value.to_vec()),
+                _ => {}
+            }
+        }
+        Some(coap_message)
+    }
+
+    pub fn is_request(&self) -> bool {
+        self.code_class == 0 && self.code_detail != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        // Confirmable GET of /sensors/temp with observe and a 2 byte token
+        let message = b"\x42\x01\x12\x34\xAB\xCD\x60\x57sensors\x04temp";
+        let v = CoapMessage::parse(message).unwrap();
+        assert_eq!(MESSAGE_TYPES[usize::from(v.message_type)], "confirmable");
+        assert!(v.is_request());
+        assert_eq!(v.token, vec![0xAB, 0xCD]);
+        assert!(v.observe);
+        assert_eq!(v.uri_path, vec![b"sensors".to_vec(), b"temp".to_vec()]);
+
+        // Acknowledgement with 2.05 content and a payload
+        let v = CoapMessage::parse(b"\x60\x45\x12\x34\xFF22.5").unwrap();
+        assert_eq!((v.code_class, v.code_detail), (2, 5));
+        assert_eq!(v.payload_len, 4);
+
+        assert!(CoapMessage::parse(b"\x60\x45\x12\x34\xFF").is_none());
+        assert!(CoapMessage::parse(b"GET / HTTP/1.1").is_none());
+    }
+}
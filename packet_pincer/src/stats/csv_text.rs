@@ -0,0 +1,28 @@
+/// Representation of free text taken from the packets that can be written as
+/// a single CSV value. Bytes that are not printable ASCII, commas, quotes and
+/// backslashes are escaped as `\xHH`
+pub fn escape_text(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len());
+    for byte in bytes {
+        match byte {
+            b',' | b'"' | b'\\' => text.push_str(&format!("\\x{:02x}", byte)),
+            0x20..=0x7E => text.push(char::from(*byte)),
+            _ => text.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_text() {
+        assert_eq!(escape_text(b"example.com"), "example.com");
+        assert_eq!(
+            escape_text(b"a,b\"c\\d\n\xff"),
+            "a\\x2cb\\x22c\\x5cd\\x0a\\xff"
+        );
+    }
+}
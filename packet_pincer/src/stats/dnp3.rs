@@ -0,0 +1,224 @@
+use super::{
+    dnp3_message::{frame_len, Dnp3Frame, DNP3_PORT, FUNCTION_CODES, OBJECT_GROUPS},
+    stream_frames::{Directional, FrameLength, FrameReader},
+    FlowStat, FlowTimes, StatSettings,
+};
+use crate::{
+    packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier,
+    stream_reassembly::StreamChunk,
+};
+use std::{
+    collections::BTreeSet,
+    io::{BufWriter, Error, Write},
+};
+
+/// DNP3 frames of the flows on the DNP3 port, over UDP or TCP: link layer
+/// addresses, application function codes, including unsolicited responses,
+/// and the groups of the objects
+#[derive(Debug, Clone)]
+pub struct Dnp3 {
+    readers: Directional<FrameReader>,
+    is_dnp3: bool,
+
+    frame_count: u32,
+    first_addresses: Option<(u16, u16)>,
+    fragment_count: u32,
+    function_code_counts: [u32; FUNCTION_CODES.len()],
+    other_function_code_count: u32,
+    object_group_counts: [u32; OBJECT_GROUPS.len()],
+    other_object_group_count: u32,
+    object_groups: BTreeSet<u8>,
+}
+
+impl Dnp3 {
+    fn include_frame(&mut self, frame: &[u8]) {
+        let Some(frame) = Dnp3Frame::parse(frame) else {
+            return;
+        };
+        self.frame_count += 1;
+        self.first_addresses
+            .get_or_insert((frame.source, frame.destination));
+
+        let Some(header) = frame.application_header() else {
+            return;
+        };
+        self.fragment_count += 1;
+        match FUNCTION_CODES
+            .iter()
+            .position(|(_, code)| *code == header.function_code)
+        {
+            Some(i) => self.function_code_counts[i] += 1,
+            None => self.other_function_code_count += 1,
+        }
+        for group in header.object_groups {
+            match OBJECT_GROUPS.iter().position(|(_, known)| *known == group) {
+                Some(i) => self.object_group_counts[i] += 1,
+                None => self.other_object_group_count += 1,
+            }
+            self.object_groups.insert(group);
+        }
+    }
+
+    fn include_packet(&mut self, sliced_packet: &etherparse::SlicedPacket) {
+        if !self.is_dnp3 {
+            return;
+        }
+        let Some(etherparse::TransportSlice::Udp(udp_slice)) = &sliced_packet.transport else {
+            return;
+        };
+
+        let mut payload = udp_slice.payload();
+        while let FrameLength::Length(len) = frame_len(payload) {
+            let Some(frame) = payload.get(..len) else {
+                break;
+            };
+            self.include_frame(frame);
+            payload = &payload[len..];
+        }
+    }
+}
+
+impl FlowStat for Dnp3 {
+    fn from_packet(
+        _settings: &StatSettings,
+        identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        let mut dnp3 = Dnp3 {
+            readers: Directional::default(),
+            is_dnp3: identifier.source_port() == DNP3_PORT || identifier.dest_port() == DNP3_PORT,
+            frame_count: 0,
+            first_addresses: None,
+            fragment_count: 0,
+            function_code_counts: [0; FUNCTION_CODES.len()],
+            other_function_code_count: 0,
+            object_group_counts: [0; OBJECT_GROUPS.len()],
+            other_object_group_count: 0,
+            object_groups: BTreeSet::new(),
+        };
+
+        dnp3.include_packet(sliced_packet);
+        dnp3
+    }
+    fn include(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        self.include_packet(sliced_packet);
+    }
+    fn uses_streams(_settings: &StatSettings) -> bool {
+        true
+    }
+    fn include_stream(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        chunk: &StreamChunk,
+    ) {
+        if !self.is_dnp3 {
+            return;
+        }
+
+        Directional::with(
+            self,
+            |dnp3| &mut dnp3.readers,
+            chunk.is_forward,
+            |dnp3, reader| {
+                reader.include(chunk.gap != 0, chunk.data, frame_len, |frame| {
+                    dnp3.include_frame(frame);
+                    true
+                })
+            },
+        );
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        write!(writer, "dnp3_frame_count,")?;
+        write!(writer, "dnp3_first_source,dnp3_first_destination,")?;
+        write!(writer, "dnp3_fragment_count,")?;
+        for (name, _) in FUNCTION_CODES {
+            write!(writer, "dnp3_fc_{}_count,", name)?;
+        }
+        write!(writer, "dnp3_fc_other_count,")?;
+        for (name, _) in OBJECT_GROUPS {
+            write!(writer, "dnp3_group_{}_count,", name)?;
+        }
+        write!(writer, "dnp3_group_other_count,dnp3_object_group_count,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        write!(writer, "{},", self.frame_count)?;
+        let (source, destination) = self.first_addresses.unwrap_or((0, 0));
+        write!(writer, "{},{},", source, destination)?;
+        write!(writer, "{},", self.fragment_count)?;
+        for count in self.function_code_counts {
+            write!(writer, "{},", count)?;
+        }
+        write!(writer, "{},", self.other_function_code_count)?;
+        for count in self.object_group_counts {
+            write!(writer, "{},", count)?;
+        }
+        write!(writer, "{},", self.other_object_group_count)?;
+        write!(writer, "{},", self.object_groups.len())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::test_flow::TestFlow;
+
+    /// Read of class 1, 2 and 3 data from 3 to 4, with placeholder CRCs
+    const READ_REQUEST: [u8; 27] = [
+        0x05, 0x64, 0x14, 0xC4, 0x04, 0x00, 0x03, 0x00, 0xFF, 0xFF, 0xC0, 0xC1, 0x01, 0x3C, 0x02,
+        0x06, 0x3C, 0x03, 0x06, 0x3C, 0x04, 0x06, 0x3C, 0x01, 0x06, 0xFF, 0xFF,
+    ];
+    /// Response with an analog input from 4 to 3, with placeholder CRCs
+    const RESPONSE: [u8; 22] = [
+        0x05, 0x64, 0x0F, 0x44, 0x03, 0x00, 0x04, 0x00, 0xFF, 0xFF, 0xC0, 0xC1, 0x81, 0x00, 0x00,
+        0x1E, 0x01, 0x00, 0x00, 0x00, 0xFF, 0xFF,
+    ];
+
+    #[test]
+    fn test_udp_frames() {
+        let mut v = TestFlow::<Dnp3>::new(StatSettings::default(), 40000, DNP3_PORT);
+        v.udp(true, 0, &READ_REQUEST).udp(false, 1000, &RESPONSE);
+        // Datagrams may carry several frames, and the bytes after the last
+        // complete one are ignored
+        let mut datagram = [READ_REQUEST, READ_REQUEST].concat();
+        datagram.extend_from_slice(&RESPONSE[..12]);
+        v.udp(true, 2000, &datagram);
+
+        assert_eq!(v.value("dnp3_frame_count"), "4");
+        assert_eq!(v.value("dnp3_first_source"), "3");
+        assert_eq!(v.value("dnp3_first_destination"), "4");
+        assert_eq!(v.value("dnp3_fragment_count"), "4");
+        assert_eq!(v.value("dnp3_fc_read_count"), "3");
+        assert_eq!(v.value("dnp3_fc_response_count"), "1");
+        assert_eq!(v.value("dnp3_group_class_count"), "12");
+        assert_eq!(v.value("dnp3_group_analog_input_count"), "1");
+        assert_eq!(v.value("dnp3_object_group_count"), "2");
+    }
+
+    #[test]
+    fn test_other_port() {
+        let mut v = TestFlow::<Dnp3>::new(StatSettings::default(), 40000, 20001);
+        v.udp(true, 0, &READ_REQUEST);
+
+        assert_eq!(v.value("dnp3_frame_count"), "0");
+    }
+}
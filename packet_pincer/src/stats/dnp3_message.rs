@@ -0,0 +1,171 @@
+use super::stream_frames::FrameLength;
+
+/// Port of DNP3 over TCP and UDP
+pub const DNP3_PORT: u16 = 20000;
+
+/// Link layer header length, with its CRC
+const HEADER_LEN: usize = 10;
+
+/// User data bytes between CRCs
+const BLOCK_LEN: usize = 16;
+
+const READ: u8 = 1;
+const ENABLE_UNSOLICITED: u8 = 20;
+const DISABLE_UNSOLICITED: u8 = 21;
+const RESPONSE: u8 = 129;
+const UNSOLICITED_RESPONSE: u8 = 130;
+
+/// Application function codes with a dedicated count, by their name
+pub const FUNCTION_CODES: [(&str, u8); 14] = [
+    ("confirm", 0),
+    ("read", READ),
+    ("write", 2),
+    ("select", 3),
+    ("operate", 4),
+    ("direct_operate", 5),
+    ("direct_operate_no_ack", 6),
+    ("cold_restart", 13),
+    ("warm_restart", 14),
+    ("stop_application", 18),
+    ("enable_unsolicited", ENABLE_UNSOLICITED),
+    ("disable_unsolicited", DISABLE_UNSOLICITED),
+    ("response", RESPONSE),
+    ("unsolicited_response", UNSOLICITED_RESPONSE),
+];
+
+/// Object groups with a dedicated count, by their name
+pub const OBJECT_GROUPS: [(&str, u8); 13] = [
+    ("binary_input", 1),
+    ("binary_input_event", 2),
+    ("binary_output", 10),
+    ("control_relay_output", 12),
+    ("counter", 20),
+    ("analog_input", 30),
+    ("analog_input_event", 32),
+    ("analog_output_status", 40),
+    ("analog_output", 41),
+    ("time", 50),
+    ("class", 60),
+    ("file", 70),
+    ("internal_indications", 80),
+];
+
+/// Length of the DNP3 frame at the start of the bytes, from its link layer
+/// header
+pub fn frame_len(bytes: &[u8]) -> FrameLength {
+    let Some(header) = bytes.get(..3) else {
+        return FrameLength::Incomplete;
+    };
+    if header[0] != 0x05 || header[1] != 0x64 || header[2] < 5 {
+        return FrameLength::Invalid;
+    }
+    let data_len = usize::from(header[2]) - 5;
+    FrameLength::Length(HEADER_LEN + data_len + 2 * data_len.div_ceil(BLOCK_LEN))
+}
+
+/// Link layer addresses and user data of a DNP3 frame
+#[derive(Debug, Clone)]
+pub struct Dnp3Frame {
+    pub destination: u16,
+    pub source: u16,
+    /// User data without the CRCs
+    pub user_data: Vec<u8>,
+}
+
+/// Header of an application fragment
+#[derive(Debug, Clone)]
+pub struct ApplicationHeader {
+    pub function_code: u8,
+    /// Groups of the object headers that could be read. All of them are read
+    /// on requests without object data, only the first one on the rest
+    pub object_groups: Vec<u8>,
+}
+
+/// Length of the range field of an object header, by its qualifier code
+fn range_len(qualifier: u8) -> Option<usize> {
+    match qualifier & 0x0F {
+        0x00 => Some(2),
+        0x01 => Some(4),
+        0x02 => Some(8),
+        0x06 => Some(0),
+        0x07 => Some(1),
+        0x08 => Some(2),
+        0x09 => Some(4),
+        _ => None,
+    }
+}
+
+impl Dnp3Frame {
+    pub fn parse(frame: &[u8]) -> Option<Dnp3Frame> {
+        let header = frame.get(..HEADER_LEN)?;
+        let user_data = frame[HEADER_LEN..]
+            .chunks(BLOCK_LEN + 2)
+            .flat_map(|block| &block[..block.len().saturating_sub(2)])
+            .copied()
+            .collect();
+        Some(Dnp3Frame {
+            destination: u16::from_le_bytes([header[4], header[5]]),
+            source: u16::from_le_bytes([header[6], header[7]]),
+            user_data,
+        })
+    }
+
+    /// Header of the application fragment starting on the frame, if the
+    /// frame is the first segment of one
+    pub fn application_header(&self) -> Option<ApplicationHeader> {
+        let transport = *self.user_data.first()?;
+        if transport & 0x40 == 0 {
+            return None;
+        }
+        let function_code = *self.user_data.get(2)?;
+        // Responses have internal indications before the objects
+        let mut position = match function_code {
+            RESPONSE | UNSOLICITED_RESPONSE => 5,
+            _ => 3,
+        };
+
+        let without_data = matches!(
+            function_code,
+            READ | ENABLE_UNSOLICITED | DISABLE_UNSOLICITED
+        );
+        let mut object_groups = Vec::new();
+        while let Some(header) = self.user_data.get(position..position + 3) {
+            object_groups.push(header[0]);
+            let Some(len) = range_len(header[2]).filter(|_| without_data) else {
+                break;
+            };
+            position += 3 + len;
+        }
+
+        Some(ApplicationHeader {
+            function_code,
+            object_groups,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame() {
+        // Read of class 1, 2 and 3 data from 3 to 4, with placeholder CRCs
+        let mut frame = vec![0x05, 0x64, 0x14, 0xC4, 0x04, 0x00, 0x03, 0x00, 0xFF, 0xFF];
+        frame.extend_from_slice(&[
+            0xC0, 0xC1, 0x01, 0x3C, 0x02, 0x06, 0x3C, 0x03, 0x06, 0x3C, 0x04, 0x06, 0x3C, 0x01,
+            0x06, 0xFF, 0xFF,
+        ]);
+        assert_eq!(frame_len(&frame), FrameLength::Length(27));
+
+        let v = Dnp3Frame::parse(&frame).unwrap();
+        assert_eq!((v.source, v.destination), (3, 4));
+        assert_eq!(v.user_data.len(), 15);
+
+        let header = v.application_header().unwrap();
+        assert_eq!(header.function_code, READ);
+        assert_eq!(header.object_groups, vec![60; 4]);
+
+        assert_eq!(frame_len(&[0x05, 0x65, 0x05]), FrameLength::Invalid);
+    }
+}
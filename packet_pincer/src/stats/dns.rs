@@ -0,0 +1,371 @@
+use super::{
+    dns_message::{DnsMessage, DnsName, DNS_PORTS, NXDOMAIN, QUERY_TYPES, RESPONSE_CODES},
+    running_stat::RunningStat,
+    stream_frames::{Directional, FrameLength, FrameReader},
+    ByteDistribution, FlowStat, FlowTimes, StatSettings,
+};
+use crate::{
+    packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier,
+    stream_reassembly::StreamChunk,
+};
+use std::io::{BufWriter, Error, Write};
+
+/// Shannon entropy in bits per character of the labels of a name
+fn label_entropy(name: &DnsName) -> f64 {
+    let mut distribution = ByteDistribution::new();
+    for label in name.labels() {
+        distribution.include(label);
+    }
+    distribution.entropy()
+}
+
+/// Features of the first name queried on the flow
+#[derive(Debug, Clone)]
+struct FirstQuestion {
+    name: String,
+    query_type: u16,
+    length: usize,
+    label_count: usize,
+    entropy: f64,
+}
+
+/// Length of the DNS message over TCP at the start of the bytes, prefixed
+/// with its length
+fn tcp_message_len(bytes: &[u8]) -> FrameLength {
+    match bytes.get(..2) {
+        Some(len) => FrameLength::Length(2 + usize::from(u16::from_be_bytes([len[0], len[1]]))),
+        None => FrameLength::Incomplete,
+    }
+}
+
+/// DNS messages of the flows using the DNS, mDNS or LLMNR ports, over UDP or
+/// TCP: message counts, query types, response codes, the first queried name
+/// and the length and entropy of the names, record counts, answer TTLs and
+/// the size of the responses compared to the queries
+#[derive(Debug, Clone)]
+pub struct Dns {
+    is_dns: bool,
+    readers: Directional<FrameReader>,
+
+    query_count: u32,
+    response_count: u32,
+    malformed_count: u32,
+    query_type_counts: [u32; QUERY_TYPES.len()],
+    other_query_type_count: u32,
+    response_code_counts: [u32; RESPONSE_CODES.len()],
+    other_response_code_count: u32,
+
+    first_question: Option<FirstQuestion>,
+    qname_length: RunningStat,
+    qname_entropy_sum: f64,
+
+    answer_count: u64,
+    authority_count: u64,
+    additional_count: u64,
+    max_answer_count: u16,
+    ttl: RunningStat,
+
+    query_bytes: u64,
+    response_bytes: u64,
+}
+
+impl Dns {
+    fn new(is_dns: bool) -> Dns {
+        Dns {
+            is_dns,
+            readers: Directional::default(),
+            query_count: 0,
+            response_count: 0,
+            malformed_count: 0,
+            query_type_counts: [0; QUERY_TYPES.len()],
+            other_query_type_count: 0,
+            response_code_counts: [0; RESPONSE_CODES.len()],
+            other_response_code_count: 0,
+            first_question: None,
+            qname_length: RunningStat::new(),
+            qname_entropy_sum: 0.0,
+            answer_count: 0,
+            authority_count: 0,
+            additional_count: 0,
+            max_answer_count: 0,
+            ttl: RunningStat::new(),
+            query_bytes: 0,
+            response_bytes: 0,
+        }
+    }
+
+    fn include_message(&mut self, bytes: &[u8]) {
+        let Some(message) = DnsMessage::parse(bytes) else {
+            self.malformed_count += 1;
+            return;
+        };
+
+        if self.first_question.is_none() {
+            if let Some(question) = message.questions.first() {
+                self.first_question = Some(FirstQuestion {
+                    name: question.name.to_presentation(),
+                    query_type: question.query_type,
+                    length: question.name.len(),
+                    label_count: question.name.labels().len(),
+                    entropy: label_entropy(&question.name),
+                });
+            }
+        }
+
+        if message.is_response {
+            self.include_response(&message, bytes.len());
+        } else {
+            self.include_query(&message, bytes.len());
+        }
+    }
+
+    fn include_query(&mut self, message: &DnsMessage, len: usize) {
+        self.query_count += 1;
+        self.query_bytes += len as u64;
+
+        for question in &message.questions {
+            match QUERY_TYPES
+                .iter()
+                .position(|(_, query_type)| *query_type == question.query_type)
+            {
+                Some(i) => self.query_type_counts[i] += 1,
+                None => self.other_query_type_count += 1,
+            }
+            self.qname_length.include(question.name.len() as u64);
+            self.qname_entropy_sum += label_entropy(&question.name);
+        }
+    }
+
+    fn include_response(&mut self, message: &DnsMessage, len: usize) {
+        self.response_count += 1;
+        self.response_bytes += len as u64;
+
+        match RESPONSE_CODES
+            .iter()
+            .position(|(_, code)| *code == message.response_code)
+        {
+            Some(i) => self.response_code_counts[i] += 1,
+            None => self.other_response_code_count += 1,
+        }
+
+        self.answer_count += u64::from(message.answer_count);
+        self.authority_count += u64::from(message.authority_count);
+        self.additional_count += u64::from(message.additional_count);
+        self.max_answer_count = self.max_answer_count.max(message.answer_count);
+        for ttl in &message.answer_ttls {
+            self.ttl.include(u64::from(*ttl));
+        }
+    }
+
+    fn include_packet(&mut self, sliced_packet: &etherparse::SlicedPacket) {
+        if !self.is_dns {
+            return;
+        }
+        if let Some(etherparse::TransportSlice::Udp(udp_slice)) = &sliced_packet.transport {
+            self.include_message(udp_slice.payload());
+        }
+    }
+
+    fn nxdomain_ratio(&self) -> f64 {
+        let nxdomain = RESPONSE_CODES
+            .iter()
+            .position(|(_, code)| *code == NXDOMAIN)
+            .map_or(0, |i| self.response_code_counts[i]);
+        if self.response_count == 0 {
+            0.0
+        } else {
+            f64::from(nxdomain) / f64::from(self.response_count)
+        }
+    }
+}
+
+impl FlowStat for Dns {
+    fn from_packet(
+        _settings: &StatSettings,
+        identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        let mut dns = Dns::new(
+            DNS_PORTS.contains(&identifier.source_port())
+                || DNS_PORTS.contains(&identifier.dest_port()),
+        );
+
+        dns.include_packet(sliced_packet);
+        dns
+    }
+    fn include(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        self.include_packet(sliced_packet);
+    }
+    fn uses_streams(_settings: &StatSettings) -> bool {
+        true
+    }
+    fn include_stream(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        chunk: &StreamChunk,
+    ) {
+        if !self.is_dns {
+            return;
+        }
+
+        Directional::with(
+            self,
+            |dns| &mut dns.readers,
+            chunk.is_forward,
+            |dns, reader| {
+                reader.include(chunk.gap != 0, chunk.data, tcp_message_len, |frame| {
+                    dns.include_message(&frame[2..]);
+                    true
+                })
+            },
+        );
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        write!(writer, "dns_query_count,dns_response_count,")?;
+        write!(writer, "dns_malformed_count,")?;
+        for (name, _) in QUERY_TYPES {
+            write!(writer, "dns_qtype_{}_count,", name)?;
+        }
+        write!(writer, "dns_qtype_other_count,")?;
+        for (name, _) in RESPONSE_CODES {
+            write!(writer, "dns_rcode_{}_count,", name)?;
+        }
+        write!(writer, "dns_rcode_other_count,")?;
+        write!(writer, "dns_nxdomain_ratio,")?;
+        write!(writer, "dns_first_qname,dns_first_qtype,")?;
+        write!(writer, "dns_first_qname_length,")?;
+        write!(writer, "dns_first_qname_label_count,")?;
+        write!(writer, "dns_first_qname_entropy,")?;
+        write!(writer, "dns_qname_length_mean,")?;
+        write!(writer, "dns_qname_length_max,")?;
+        write!(writer, "dns_qname_entropy_mean,")?;
+        write!(writer, "dns_answer_count,dns_authority_count,")?;
+        write!(writer, "dns_additional_count,dns_answer_count_max,")?;
+        write!(writer, "dns_ttl_min,dns_ttl_mean,dns_ttl_max,")?;
+        write!(writer, "dns_query_bytes,dns_response_bytes,")?;
+        write!(writer, "dns_response_query_ratio,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        write!(writer, "{},{},", self.query_count, self.response_count)?;
+        write!(writer, "{},", self.malformed_count)?;
+        for count in self.query_type_counts {
+            write!(writer, "{},", count)?;
+        }
+        write!(writer, "{},", self.other_query_type_count)?;
+        for count in self.response_code_counts {
+            write!(writer, "{},", count)?;
+        }
+        write!(writer, "{},", self.other_response_code_count)?;
+        write!(writer, "{:.9},", self.nxdomain_ratio())?;
+
+        match &self.first_question {
+            Some(first) => {
+                write!(writer, "{},{},", first.name, first.query_type)?;
+                write!(writer, "{},{},", first.length, first.label_count)?;
+                write!(writer, "{:.9},", first.entropy)?;
+            }
+            None => write!(writer, ",0,0,0,0,")?,
+        }
+        write!(writer, "{:.9},", self.qname_length.current_mean())?;
+        write!(writer, "{},", self.qname_length.current_max().unwrap_or(0))?;
+        if self.qname_length.current_count() == 0 {
+            write!(writer, "{},", 0)?;
+        } else {
+            write!(
+                writer,
+                "{:.9},",
+                self.qname_entropy_sum / self.qname_length.current_count() as f64
+            )?;
+        }
+
+        write!(writer, "{},{},", self.answer_count, self.authority_count)?;
+        write!(
+            writer,
+            "{},{},",
+            self.additional_count, self.max_answer_count
+        )?;
+        write!(writer, "{},", self.ttl.current_min().unwrap_or(0))?;
+        write!(writer, "{:.9},", self.ttl.current_mean())?;
+        write!(writer, "{},", self.ttl.current_max().unwrap_or(0))?;
+
+        write!(writer, "{},{},", self.query_bytes, self.response_bytes)?;
+        if self.query_bytes == 0 {
+            write!(writer, "{},", 0)?;
+        } else {
+            write!(
+                writer,
+                "{:.9},",
+                self.response_bytes as f64 / self.query_bytes as f64
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn test_tcp_messages() {
+        let identifier = TransportFlowIdentifier {
+            source_ip: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2)),
+            source_port: 1234,
+            dest_ip: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)),
+            dest_port: 53,
+            transport_protocol: etherparse::IpNumber::TCP,
+        };
+        let time = DateTime::from_timestamp(0, 0).unwrap();
+        let flow_times = FlowTimes {
+            first_packet_time: time,
+            last_packet_time: time,
+        };
+        let chunk = |offset: u64, gap: u64, data: &'static [u8]| StreamChunk {
+            is_forward: true,
+            time,
+            offset,
+            gap,
+            data,
+        };
+
+        // Query of www.example.com, prefixed with its length
+        const QUERY: [u8; 35] = [
+            0x00, 0x21, 0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x03, b'w', b'w', b'w', 0x07, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0x03, b'c',
+            b'o', b'm', 0x00, 0x00, 0x01, 0x00, 0x01,
+        ];
+        let mut v = Dns::new(true);
+
+        v.include_stream(&identifier, &flow_times, &chunk(0, 0, &QUERY[..10]));
+        assert_eq!(v.query_count, 0);
+        v.include_stream(&identifier, &flow_times, &chunk(10, 0, &QUERY[10..]));
+        assert_eq!(v.query_count, 1);
+        assert_eq!(v.first_question.as_ref().unwrap().name, "www.example.com");
+        assert_eq!(v.query_bytes, 33);
+
+        v.include_stream(&identifier, &flow_times, &chunk(45, 10, &QUERY));
+        assert_eq!(v.query_count, 1);
+        assert_eq!(v.malformed_count, 0);
+    }
+}
@@ -0,0 +1,237 @@
+/// Ports used by DNS, multicast DNS and LLMNR
+pub const DNS_PORTS: [u16; 3] = [53, 5353, 5355];
+
+/// Size of the DNS header
+const HEADER_LEN: usize = 12;
+
+/// Largest number of compression pointers followed on a single name, to stop
+/// on pointer loops
+const MAX_POINTERS: usize = 16;
+
+/// Query types with a dedicated count, by their name
+pub const QUERY_TYPES: [(&str, u16); 10] = [
+    ("a", 1),
+    ("ns", 2),
+    ("cname", 5),
+    ("soa", 6),
+    ("ptr", 12),
+    ("mx", 15),
+    ("txt", 16),
+    ("aaaa", 28),
+    ("srv", 33),
+    ("any", 255),
+];
+
+/// Response codes with a dedicated count, by their name
+pub const RESPONSE_CODES: [(&str, u8); 4] = [
+    ("noerror", 0),
+    ("servfail", 2),
+    ("nxdomain", 3),
+    ("refused", 5),
+];
+
+/// Response code of a name that does not exist
+pub const NXDOMAIN: u8 = 3;
+
+/// Domain name as a sequence of labels, without the root label
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsName {
+    labels: Vec<Vec<u8>>,
+}
+
+impl DnsName {
+    pub fn labels(&self) -> &[Vec<u8>] {
+        &self.labels
+    }
+
+    /// Number of characters of the name in dotted form, without escapes
+    pub fn len(&self) -> usize {
+        let dots = self.labels.len().saturating_sub(1);
+        self.labels.iter().map(|label| label.len()).sum::<usize>() + dots
+    }
+
+    /// Name in dotted form. Bytes other than letters, digits, hyphens and
+    /// underscores are escaped as `\DDD`, so it never contains commas, quotes
+    /// or dots inside a label. The root name is written as `.`
+    pub fn to_presentation(&self) -> String {
+        if self.labels.is_empty() {
+            return ".".to_string();
+        }
+
+        let mut name = String::new();
+        for (i, label) in self.labels.iter().enumerate() {
+            if i != 0 {
+                name.push('.');
+            }
+            for byte in label {
+                if byte.is_ascii_alphanumeric() || *byte == b'-' || *byte == b'_' {
+                    name.push(char::from(*byte));
+                } else {
+                    name.push_str(&format!("\\{:03}", byte));
+                }
+            }
+        }
+        name
+    }
+}
+
+/// Question section entry
+#[derive(Debug, Clone)]
+pub struct DnsQuestion {
+    pub name: DnsName,
+    pub query_type: u16,
+}
+
+/// The parts of a DNS message used by the stats
+#[derive(Debug, Clone)]
+pub struct DnsMessage {
+    pub is_response: bool,
+    pub response_code: u8,
+    pub questions: Vec<DnsQuestion>,
+    pub answer_count: u16,
+    pub authority_count: u16,
+    pub additional_count: u16,
+    /// TTLs of the answer section records
+    pub answer_ttls: Vec<u32>,
+}
+
+fn read_u16(message: &[u8], position: usize) -> Option<u16> {
+    let bytes = message.get(position..position + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(message: &[u8], position: usize) -> Option<u32> {
+    let bytes = message.get(position..position + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Read the name starting at the given position, following compression
+/// pointers. Returns it with the position right after it
+fn read_name(message: &[u8], position: usize) -> Option<(DnsName, usize)> {
+    let mut labels = Vec::new();
+    let mut current = position;
+    let mut end = None;
+    let mut pointers = 0;
+
+    loop {
+        let len = *message.get(current)?;
+        match len & 0xC0 {
+            0x00 if len == 0 => {
+                return Some((DnsName { labels }, end.unwrap_or(current + 1)));
+            }
+            0x00 => {
+                let label = message.get(current + 1..current + 1 + usize::from(len))?;
+                labels.push(label.to_vec());
+                current += 1 + usize::from(len);
+            }
+            0xC0 => {
+                pointers += 1;
+                if pointers > MAX_POINTERS {
+                    return None;
+                }
+                let target = usize::from(read_u16(message, current)? & 0x3FFF);
+                end.get_or_insert(current + 2);
+                current = target;
+            }
+            _ => return None,
+        }
+    }
+}
+
+impl DnsMessage {
+    /// Parse a DNS message, without the length prefix used over TCP.
+    /// Records after the answer section are only counted
+    pub fn parse(message: &[u8]) -> Option<DnsMessage> {
+        if message.len() < HEADER_LEN {
+            return None;
+        }
+
+        let flags = read_u16(message, 2)?;
+        let question_count = read_u16(message, 4)?;
+        let answer_count = read_u16(message, 6)?;
+        let authority_count = read_u16(message, 8)?;
+        let additional_count = read_u16(message, 10)?;
+
+        let mut position = HEADER_LEN;
+        let mut questions = Vec::new();
+        for _ in 0..question_count {
+            let (name, next) = read_name(message, position)?;
+            let query_type = read_u16(message, next)?;
+            read_u16(message, next + 2)?;
+            questions.push(DnsQuestion { name, query_type });
+            position = next + 4;
+        }
+
+        // Answers are parsed on a best effort, as long as they are complete
+        let mut answer_ttls = Vec::new();
+        for _ in 0..answer_count {
+            let Some((_, next)) = read_name(message, position) else {
+                break;
+            };
+            let (Some(ttl), Some(data_len)) =
+                (read_u32(message, next + 4), read_u16(message, next + 8))
+            else {
+                break;
+            };
+            answer_ttls.push(ttl);
+            position = next + 10 + usize::from(data_len);
+        }
+
+        Some(DnsMessage {
+            is_response: flags & 0x8000 != 0,
+            response_code: (flags & 0x000F) as u8,
+            questions,
+            answer_count,
+            authority_count,
+            additional_count,
+            answer_ttls,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Response to an A query of `www.example.com` with two compressed answers
+    const RESPONSE: [u8; 65] = [
+        0x12, 0x34, 0x81, 0x83, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, // header
+        0x03, b'w', b'w', b'w', 0x07, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0x03, b'c', b'o',
+        b'm', 0x00, 0x00, 0x01, 0x00, 0x01, // question
+        0xC0, 0x0C, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x04, 10, 0, 0,
+        1, // answer
+        0xC0, 0x10, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3C, 0x00, 0x04, 10, 0, 0,
+        2, // answer
+    ];
+
+    #[test]
+    fn test_parse() {
+        let message = DnsMessage::parse(&RESPONSE).unwrap();
+
+        assert!(message.is_response);
+        assert_eq!(message.response_code, NXDOMAIN);
+        assert_eq!(message.questions.len(), 1);
+        assert_eq!(message.questions[0].query_type, 1);
+        assert_eq!(
+            message.questions[0].name.to_presentation(),
+            "www.example.com"
+        );
+        assert_eq!(message.questions[0].name.len(), 15);
+        assert_eq!(message.answer_ttls, vec![3600, 60]);
+
+        assert!(DnsMessage::parse(&RESPONSE[..20]).is_none());
+    }
+
+    #[test]
+    fn test_name_escapes_and_loops() {
+        let message = [0x03, b'a', b',', b'.', 0x00];
+        let (name, end) = read_name(&message, 0).unwrap();
+        assert_eq!(name.to_presentation(), "a\\044\\046");
+        assert_eq!(end, 5);
+
+        let (root, _) = read_name(&[0x00], 0).unwrap();
+        assert_eq!(root.to_presentation(), ".");
+
+        assert!(read_name(&[0xC0, 0x00], 0).is_none());
+    }
+}
@@ -1,16 +1,28 @@
-use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
+use crate::{
+    packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier,
+    stream_reassembly::StreamChunk,
+};
 use std::io::{BufWriter, Error};
 
-use super::FlowTimes;
+use super::{FlowTimes, StatSettings};
 
+/// A set of features computed incrementally from the packets of a flow and
+/// written as comma separated values. Each value written must be followed by
+/// a comma, including the last one
 pub trait FlowStat {
+    /// Create the statistic from the first packet of the flow. The
+    /// `flow_times` already include the packet
     fn from_packet(
+        settings: &StatSettings,
         flow_identifier: &TransportFlowIdentifier,
         flow_times: &FlowTimes,
         packet_header: &pcap::PacketHeader,
         sliced_packet: &etherparse::SlicedPacket,
         reasembly_information: Option<&FragmentReasemblyInformation>,
     ) -> Self;
+    /// Accomulate the next packet of the flow. The `flow_times` already
+    /// include the packet. If the packet was reassembled from IPv4 fragments,
+    /// `reasembly_information` describes the original fragments
     fn include(
         &mut self,
         flow_identifier: &TransportFlowIdentifier,
@@ -19,8 +31,28 @@ pub trait FlowStat {
         sliced_packet: &etherparse::SlicedPacket,
         reasembly_information: Option<&FragmentReasemblyInformation>,
     );
-    fn write_csv_header<T: ?Sized + std::io::Write>(writer: &mut BufWriter<T>)
-        -> Result<(), Error>;
+    /// Check if the statistic needs the reassembled TCP streams of the flows
+    /// with the given settings. By default, it does not
+    fn uses_streams(_settings: &StatSettings) -> bool {
+        false
+    }
+    /// Accomulate the next ordered bytes of a direction of the TCP stream.
+    /// Only called if `uses_streams` is true, after including the packet that
+    /// completed the bytes
+    fn include_stream(
+        &mut self,
+        _flow_identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _chunk: &StreamChunk,
+    ) {
+    }
+    /// Write the names of the features to the given writer
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        settings: &StatSettings,
+    ) -> Result<(), Error>;
+    /// Write the values of the features to the given writer, in the same order
+    /// as the header
     fn write_csv_value<T: ?Sized + std::io::Write>(
         &self,
         writer: &mut BufWriter<T>,
@@ -1,14 +1,19 @@
 use super::interarrival::Interarrival;
 use super::{
-    Activity, ByteCount, FlowStat, FlowTimes, PacketCount, Protocols, TcpFlags, Transport
+    Activity, AppProtocol, BulkTransfer, ByteCount, CicFlowMeter, Coap, Dnp3, Dns, FeatureGroup,
+    FlowStat, FlowTimes, Histograms, Http, IpHeader, Modbus, Mqtt, OsFingerprint, PacketCount,
+    PacketSequence, PayloadContent, Protocols, Reflection, Rtp, Sip, Ssh, StatSettings,
+    TcpAnalysis, TcpConnection, TcpFlags, TcpOptions, Tls, Transport,
 };
 use crate::packet_flow::FragmentReasemblyInformation;
 use crate::packet_parse::TransportFlowIdentifier;
+use crate::stream_reassembly::StreamChunk;
 
 macro_rules! impl_flow_stat {
-    ($struct_name:ident { $($field:ident : $field_type:ty),* $(,)? }) => {
+    ($struct_name:ident { $($field:ident : $field_type:ty => $group:expr),* $(,)? }) => {
         impl FlowStat for $struct_name {
             fn from_packet(
+                settings: &StatSettings,
                 identifier: &TransportFlowIdentifier,
                 flow_times: &FlowTimes,
                 packet_header: &pcap::PacketHeader,
@@ -17,7 +22,9 @@ macro_rules! impl_flow_stat {
             ) -> Self {
                 $struct_name {
                     $(
-                        $field: <$field_type>::from_packet(identifier, flow_times, packet_header, sliced_packet, reasembly_information),
+                        $field: settings.is_selected($group).then(|| {
+                            Box::new(<$field_type>::from_packet(settings, identifier, flow_times, packet_header, sliced_packet, reasembly_information))
+                        }),
                     )*
                 }
             }
@@ -31,15 +38,37 @@ macro_rules! impl_flow_stat {
                 reasembly_information: Option<&FragmentReasemblyInformation>,
             ) {
                 $(
-                    self.$field.include(identifier, flow_times, packet_header, sliced_packet, reasembly_information);
+                    if let Some(stat) = &mut self.$field {
+                        stat.include(identifier, flow_times, packet_header, sliced_packet, reasembly_information);
+                    }
+                )*
+            }
+
+            fn uses_streams(settings: &StatSettings) -> bool {
+                false $(|| (settings.is_selected($group) && <$field_type>::uses_streams(settings)))*
+            }
+
+            fn include_stream(
+                &mut self,
+                identifier: &TransportFlowIdentifier,
+                flow_times: &FlowTimes,
+                chunk: &StreamChunk,
+            ) {
+                $(
+                    if let Some(stat) = &mut self.$field {
+                        stat.include_stream(identifier, flow_times, chunk);
+                    }
                 )*
             }
 
             fn write_csv_header<T: ?Sized + std::io::Write>(
-                writer: &mut std::io::BufWriter<T>
+                writer: &mut std::io::BufWriter<T>,
+                settings: &StatSettings,
             ) -> Result<(), std::io::Error> {
                 $(
-                    <$field_type>::write_csv_header(writer)?;
+                    if settings.is_selected($group) {
+                        <$field_type>::write_csv_header(writer, settings)?;
+                    }
                 )*
                 Ok(())
             }
@@ -50,7 +79,9 @@ macro_rules! impl_flow_stat {
                 flow_times: &FlowTimes,
             ) -> Result<(), std::io::Error> {
                 $(
-                    self.$field.write_csv_value(writer, flow_times)?;
+                    if let Some(stat) = &self.$field {
+                        stat.write_csv_value(writer, flow_times)?;
+                    }
                 )*
                 Ok(())
             }
@@ -58,23 +89,83 @@ macro_rules! impl_flow_stat {
     };
 }
 
-#[derive(Debug)]
+/// The statistics of each selected feature group. Unselected feature groups
+/// are neither allocated nor computed, and the selected ones are boxed so the
+/// flows only hold a pointer for each group
+#[derive(Debug, Clone)]
 pub struct FlowStatistics {
-    protocols: Protocols,
-    packet_count: PacketCount,
-    byte_count: ByteCount,
-    interrarival: Interarrival,
-    tcp_flags: TcpFlags,
-    transport: Transport,
-    activity: Activity,
+    protocols: Option<Box<Protocols>>,
+    packet_count: Option<Box<PacketCount>>,
+    byte_count: Option<Box<ByteCount>>,
+    interrarival: Option<Box<Interarrival>>,
+    tcp_flags: Option<Box<TcpFlags>>,
+    transport: Option<Box<Transport>>,
+    activity: Option<Box<Activity>>,
+    packet_sequence: Option<Box<PacketSequence>>,
+    payload_content: Option<Box<PayloadContent>>,
+    cic_flow_meter: Option<Box<CicFlowMeter>>,
+    histograms: Option<Box<Histograms>>,
+    bulk_transfer: Option<Box<BulkTransfer>>,
+    tcp_analysis: Option<Box<TcpAnalysis>>,
+    tcp_connection: Option<Box<TcpConnection>>,
+    tcp_options: Option<Box<TcpOptions>>,
+    ip_header: Option<Box<IpHeader>>,
+    os_fingerprint: Option<Box<OsFingerprint>>,
+    dns: Option<Box<Dns>>,
+    tls: Option<Box<Tls>>,
+    http: Option<Box<Http>>,
+    mqtt: Option<Box<Mqtt>>,
+    coap: Option<Box<Coap>>,
+    modbus: Option<Box<Modbus>>,
+    dnp3: Option<Box<Dnp3>>,
+    reflection: Option<Box<Reflection>>,
+    ssh: Option<Box<Ssh>>,
+    sip: Option<Box<Sip>>,
+    rtp: Option<Box<Rtp>>,
+    app_protocol: Option<Box<AppProtocol>>,
 }
 
 impl_flow_stat!(FlowStatistics {
-    protocols: Protocols,
-    packet_count: PacketCount,
-    byte_count: ByteCount,
-    interrarival: Interarrival,
-    tcp_flags: TcpFlags,
-    transport: Transport,
-    activity: Activity,
+    protocols: Protocols => FeatureGroup::Protocols,
+    packet_count: PacketCount => FeatureGroup::PacketCount,
+    byte_count: ByteCount => FeatureGroup::ByteCount,
+    interrarival: Interarrival => FeatureGroup::Interarrival,
+    tcp_flags: TcpFlags => FeatureGroup::TcpFlags,
+    transport: Transport => FeatureGroup::Transport,
+    activity: Activity => FeatureGroup::Activity,
+    packet_sequence: PacketSequence => FeatureGroup::PacketSequence,
+    payload_content: PayloadContent => FeatureGroup::PayloadContent,
+    cic_flow_meter: CicFlowMeter => FeatureGroup::CicFlowMeter,
+    histograms: Histograms => FeatureGroup::Histograms,
+    bulk_transfer: BulkTransfer => FeatureGroup::BulkTransfer,
+    tcp_analysis: TcpAnalysis => FeatureGroup::TcpAnalysis,
+    tcp_connection: TcpConnection => FeatureGroup::TcpConnection,
+    tcp_options: TcpOptions => FeatureGroup::TcpOptions,
+    ip_header: IpHeader => FeatureGroup::IpHeader,
+    os_fingerprint: OsFingerprint => FeatureGroup::OsFingerprint,
+    dns: Dns => FeatureGroup::Dns,
+    tls: Tls => FeatureGroup::Tls,
+    http: Http => FeatureGroup::Http,
+    mqtt: Mqtt => FeatureGroup::Mqtt,
+    coap: Coap => FeatureGroup::Coap,
+    modbus: Modbus => FeatureGroup::Modbus,
+    dnp3: Dnp3 => FeatureGroup::Dnp3,
+    reflection: Reflection => FeatureGroup::Reflection,
+    ssh: Ssh => FeatureGroup::Ssh,
+    sip: Sip => FeatureGroup::Sip,
+    rtp: Rtp => FeatureGroup::Rtp,
+    app_protocol: AppProtocol => FeatureGroup::AppProtocol,
 });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boxed_groups() {
+        assert_eq!(
+            std::mem::size_of::<FlowStatistics>(),
+            29 * std::mem::size_of::<usize>()
+        );
+    }
+}
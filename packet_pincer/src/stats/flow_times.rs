@@ -7,14 +7,15 @@ use crate::{
     packet_parse::{get_datetime_of_packet, TransportFlowIdentifier},
 };
 
-#[derive(Debug)]
+/// The times of the first and last packets of a flow
+#[derive(Debug, Clone)]
 pub struct FlowTimes {
     pub(crate) first_packet_time: DateTime<Utc>,
     pub(crate) last_packet_time: DateTime<Utc>,
 }
 
 impl FlowTimes {
-    pub fn from_packet(
+    pub(crate) fn from_packet(
         _identifier: &TransportFlowIdentifier,
         packet_header: &pcap::PacketHeader,
         _sliced_packet: &etherparse::SlicedPacket,
@@ -36,7 +37,7 @@ impl FlowTimes {
             last_packet_time,
         }
     }
-    pub fn include(
+    pub(crate) fn include(
         &mut self,
         _identifier: &TransportFlowIdentifier,
         packet_header: &pcap::PacketHeader,
@@ -54,7 +55,7 @@ impl FlowTimes {
 
         self.last_packet_time = next_time
     }
-    pub fn write_csv_header<T: ?Sized + std::io::Write>(
+    pub(crate) fn write_csv_header<T: ?Sized + std::io::Write>(
         writer: &mut BufWriter<T>,
     ) -> Result<(), Error> {
         write!(writer, "first_packet_time,")?;
@@ -62,7 +63,7 @@ impl FlowTimes {
         write!(writer, "duration_seconds,")?;
         Ok(())
     }
-    pub fn write_csv_value<T: ?Sized + std::io::Write>(
+    pub(crate) fn write_csv_value<T: ?Sized + std::io::Write>(
         &self,
         writer: &mut BufWriter<T>,
     ) -> Result<(), Error> {
@@ -77,11 +78,22 @@ impl FlowTimes {
         )?;
         Ok(())
     }
+    /// The time the first packet of the flow was received
+    pub fn first_packet_time(&self) -> DateTime<Utc> {
+        self.first_packet_time
+    }
+    /// The time the last packet of the flow was received, which is the
+    /// current packet while it is being included
+    pub fn last_packet_time(&self) -> DateTime<Utc> {
+        self.last_packet_time
+    }
+    /// The time between the first and the last packets of the flow
     pub fn duration(&self) -> TimeDelta {
         self.last_packet_time - self.first_packet_time
     }
+    /// The duration of the flow in seconds as a floating point value
     pub fn duration_seconds_f64(&self) -> f64 {
         let duration = self.duration();
-        duration.subsec_nanos() as f64 + (duration.num_seconds() as f64) * 1_000_000_000.0
+        duration.num_seconds() as f64 + f64::from(duration.subsec_nanos()) / 1_000_000_000.0
     }
 }
@@ -0,0 +1,196 @@
+use super::{extract_byte_count, FlowStat, FlowTimes, StatSettings};
+use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
+use chrono::{DateTime, Utc};
+use std::{
+    io::{BufWriter, Error, Write},
+    rc::Rc,
+};
+
+/// Counts of values on fixed bins. Given the sorted edges `e_1, ..., e_n`,
+/// the bin `i` counts the values in `[e_i, e_{i+1})`, with the first bin
+/// starting at 0 and the last one having no upper limit
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    counts: Vec<u32>,
+}
+
+impl Histogram {
+    pub fn new(edges: &[u64]) -> Histogram {
+        Histogram {
+            counts: vec![0; edges.len() + 1],
+        }
+    }
+
+    pub fn include(&mut self, edges: &[u64], value: u64) {
+        let bin = edges.partition_point(|edge| *edge <= value);
+        self.counts[bin] += 1;
+    }
+
+    pub fn counts(&self) -> &[u32] {
+        &self.counts
+    }
+}
+
+/// Create `edge_count` bin edges, equally spaced on a logarithmic scale
+/// between `first` and `last`, both included
+pub fn log_scaled_bin_edges(first: u64, last: u64, edge_count: usize) -> Vec<u64> {
+    if edge_count < 2 || first == 0 || last <= first {
+        return vec![first];
+    }
+
+    let ratio = (last as f64 / first as f64).powf(1.0 / (edge_count - 1) as f64);
+    let mut edges: Vec<u64> = (0..edge_count)
+        .map(|i| (first as f64 * ratio.powi(i as i32)).round() as u64)
+        .collect();
+    edges.dedup();
+    edges
+}
+
+/// Histograms of the packet sizes and inter arrival times of each direction,
+/// over the bins given on the settings
+#[derive(Debug, Clone)]
+pub struct Histograms {
+    packet_bytes_edges: Rc<[u64]>,
+    inter_arrival_edges: Rc<[u64]>,
+
+    forward_packet_bytes: Histogram,
+    backward_packet_bytes: Histogram,
+    forward_inter_arrival: Histogram,
+    backward_inter_arrival: Histogram,
+
+    forward_last_time: DateTime<Utc>,
+    backward_last_time: Option<DateTime<Utc>>,
+}
+
+impl FlowStat for Histograms {
+    fn from_packet(
+        settings: &StatSettings,
+        _identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        packet_header: &pcap::PacketHeader,
+        _sliced_packet: &etherparse::SlicedPacket,
+        reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        let packet_bytes_edges = settings.shared_packet_bytes_bin_edges();
+        let inter_arrival_edges = settings.shared_inter_arrival_bin_edges();
+
+        let mut forward_packet_bytes = Histogram::new(&packet_bytes_edges);
+        forward_packet_bytes.include(
+            &packet_bytes_edges,
+            extract_byte_count(packet_header, reasembly_information),
+        );
+
+        Histograms {
+            backward_packet_bytes: Histogram::new(&packet_bytes_edges),
+            forward_inter_arrival: Histogram::new(&inter_arrival_edges),
+            backward_inter_arrival: Histogram::new(&inter_arrival_edges),
+            forward_packet_bytes,
+            packet_bytes_edges,
+            inter_arrival_edges,
+            forward_last_time: flow_times.last_packet_time,
+            backward_last_time: None,
+        }
+    }
+    fn include(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        let is_forward = identifier.is_forward(sliced_packet);
+        let byte_count = extract_byte_count(packet_header, reasembly_information);
+        let time = flow_times.last_packet_time;
+
+        let (packet_bytes, inter_arrival, last_time) = if is_forward {
+            (
+                &mut self.forward_packet_bytes,
+                &mut self.forward_inter_arrival,
+                Some(self.forward_last_time),
+            )
+        } else {
+            (
+                &mut self.backward_packet_bytes,
+                &mut self.backward_inter_arrival,
+                self.backward_last_time,
+            )
+        };
+
+        packet_bytes.include(&self.packet_bytes_edges, byte_count);
+        if let Some(last_time) = last_time {
+            let increment: u64 = (time - last_time)
+                .num_microseconds()
+                .expect("IAT increments microseconds should fit in a i64")
+                .try_into()
+                .expect("IAT increments microseconds should convert to u64");
+            inter_arrival.include(&self.inter_arrival_edges, increment);
+        }
+
+        if is_forward {
+            self.forward_last_time = time;
+        } else {
+            self.backward_last_time = Some(time);
+        }
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        settings: &StatSettings,
+    ) -> Result<(), Error> {
+        for direction in ["forward", "backward"] {
+            for i in 0..=settings.packet_bytes_bin_edges().len() {
+                write!(writer, "{}_packet_bytes_bin_{},", direction, i)?;
+            }
+        }
+        for direction in ["forward", "backward"] {
+            for i in 0..=settings.inter_arrival_bin_edges().len() {
+                write!(writer, "{}_inter_arrival_time_bin_{},", direction, i)?;
+            }
+        }
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        for histogram in [
+            &self.forward_packet_bytes,
+            &self.backward_packet_bytes,
+            &self.forward_inter_arrival,
+            &self.backward_inter_arrival,
+        ] {
+            for count in histogram.counts() {
+                write!(writer, "{},", count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_bins() {
+        let edges = [64, 128, 256];
+        let mut v = Histogram::new(&edges);
+
+        for value in [0, 63, 64, 127, 200, 256, 1500] {
+            v.include(&edges, value);
+        }
+
+        assert_eq!(v.counts(), &[2, 2, 1, 2]);
+    }
+
+    #[test]
+    fn test_log_scaled_bin_edges() {
+        assert_eq!(
+            log_scaled_bin_edges(64, 1024, 5),
+            vec![64, 128, 256, 512, 1024]
+        );
+        assert_eq!(log_scaled_bin_edges(1, 1000, 4), vec![1, 10, 100, 1000]);
+        assert_eq!(log_scaled_bin_edges(1, 2, 4), vec![1, 2]);
+    }
+}
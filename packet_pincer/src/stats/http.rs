@@ -0,0 +1,217 @@
+use super::{
+    csv_text::escape_text,
+    http_message::{HttpHead, HttpStreamReader, StartLine},
+    stream_frames::Directional,
+    FlowStat, FlowTimes, StatSettings,
+};
+use crate::{
+    packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier,
+    stream_reassembly::StreamChunk,
+};
+use std::{
+    collections::VecDeque,
+    io::{BufWriter, Error, Write},
+};
+
+/// Request methods with a dedicated count
+const METHODS: [&str; 7] = ["GET", "POST", "HEAD", "PUT", "DELETE", "OPTIONS", "CONNECT"];
+
+/// Largest number of requests waiting for their response that are tracked
+const MAX_PENDING_REQUESTS: usize = 64;
+
+/// Largest number of different content types kept
+const MAX_CONTENT_TYPES: usize = 8;
+
+/// HTTP/1.x requests and responses of TCP flows, read from the reassembled
+/// streams: the version of the first message, message counts, methods, the
+/// host, URI and user agent of the first request that has them, response
+/// status classes, body sizes and the content types seen, written separated
+/// by `;`
+#[derive(Debug, Clone)]
+pub struct Http {
+    readers: Directional<HttpStreamReader>,
+    /// Methods of the requests waiting for their response
+    pending_methods: VecDeque<String>,
+
+    request_count: u32,
+    response_count: u32,
+    method_counts: [u32; METHODS.len()],
+    other_method_count: u32,
+    status_class_counts: [u32; 5],
+    first_status: u16,
+    minor_version: Option<u8>,
+
+    host: Option<Vec<u8>>,
+    first_uri: Option<Vec<u8>>,
+    user_agent: Option<Vec<u8>>,
+    content_types: Vec<String>,
+}
+
+impl Http {
+    /// Include the head of a message, returning whether it can have a body
+    fn include_head(&mut self, head: &HttpHead) -> bool {
+        self.minor_version.get_or_insert(head.minor_version);
+        if let Some(content_type) = head.header("content-type") {
+            let media_type = content_type.split(|byte| *byte == b';').next();
+            let media_type =
+                escape_text(media_type.unwrap_or_default().trim_ascii()).to_ascii_lowercase();
+            if !media_type.is_empty()
+                && self.content_types.len() < MAX_CONTENT_TYPES
+                && !self.content_types.contains(&media_type)
+            {
+                self.content_types.push(media_type);
+            }
+        }
+
+        match &head.start_line {
+            StartLine::Request { method, uri } => {
+                self.request_count += 1;
+                match METHODS.iter().position(|name| name == method) {
+                    Some(i) => self.method_counts[i] += 1,
+                    None => self.other_method_count += 1,
+                }
+                if self.pending_methods.len() < MAX_PENDING_REQUESTS {
+                    self.pending_methods.push_back(method.clone());
+                }
+
+                self.first_uri.get_or_insert_with(|| uri.clone());
+                if self.host.is_none() {
+                    self.host = head.header("host").map(<[u8]>::to_vec);
+                }
+                if self.user_agent.is_none() {
+                    self.user_agent = head.header("user-agent").map(<[u8]>::to_vec);
+                }
+                true
+            }
+            StartLine::Response { status } => {
+                // Interim responses are followed by the final one
+                let method = if *status < 200 {
+                    None
+                } else {
+                    self.pending_methods.pop_front()
+                };
+
+                self.response_count += 1;
+                if self.first_status == 0 {
+                    self.first_status = *status;
+                }
+                if (100..600).contains(status) {
+                    self.status_class_counts[usize::from(*status / 100 - 1)] += 1;
+                }
+
+                !(*status < 200
+                    || *status == 204
+                    || *status == 304
+                    || method.as_deref() == Some("HEAD")
+                    || (method.as_deref() == Some("CONNECT") && *status < 300))
+            }
+        }
+    }
+
+    fn write_text<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        text: Option<&Vec<u8>>,
+    ) -> Result<(), Error> {
+        write!(writer, "{},", escape_text(text.map_or(&[], Vec::as_slice)))
+    }
+}
+
+impl FlowStat for Http {
+    fn from_packet(
+        _settings: &StatSettings,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        _sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        Http {
+            readers: Directional::default(),
+            pending_methods: VecDeque::new(),
+            request_count: 0,
+            response_count: 0,
+            method_counts: [0; METHODS.len()],
+            other_method_count: 0,
+            status_class_counts: [0; 5],
+            first_status: 0,
+            minor_version: None,
+            host: None,
+            first_uri: None,
+            user_agent: None,
+            content_types: Vec::new(),
+        }
+    }
+    fn include(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        _sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+    }
+    fn uses_streams(_settings: &StatSettings) -> bool {
+        true
+    }
+    fn include_stream(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        chunk: &StreamChunk,
+    ) {
+        Directional::with(
+            self,
+            |http| &mut http.readers,
+            chunk.is_forward,
+            |http, reader| {
+                reader.include(chunk.gap != 0, chunk.data, |head| http.include_head(head))
+            },
+        );
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        write!(writer, "http_version,")?;
+        write!(writer, "http_request_count,http_response_count,")?;
+        for method in METHODS {
+            write!(writer, "http_method_{}_count,", method.to_ascii_lowercase())?;
+        }
+        write!(writer, "http_method_other_count,")?;
+        write!(writer, "http_host,http_first_uri,http_first_uri_length,")?;
+        write!(writer, "http_user_agent,")?;
+        for class in 1..=5 {
+            write!(writer, "http_status_{}xx_count,", class)?;
+        }
+        write!(writer, "http_first_status,http_content_types,")?;
+        write!(writer, "http_forward_body_bytes,http_backward_body_bytes,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        match self.minor_version {
+            Some(minor_version) => write!(writer, "1.{},", minor_version)?,
+            None => write!(writer, ",")?,
+        }
+        write!(writer, "{},{},", self.request_count, self.response_count)?;
+        for count in self.method_counts {
+            write!(writer, "{},", count)?;
+        }
+        write!(writer, "{},", self.other_method_count)?;
+        Self::write_text(writer, self.host.as_ref())?;
+        Self::write_text(writer, self.first_uri.as_ref())?;
+        write!(writer, "{},", self.first_uri.as_ref().map_or(0, Vec::len))?;
+        Self::write_text(writer, self.user_agent.as_ref())?;
+        for count in self.status_class_counts {
+            write!(writer, "{},", count)?;
+        }
+        write!(writer, "{},", self.first_status)?;
+        write!(writer, "{},", self.content_types.join(";"))?;
+        write!(writer, "{},", self.readers.get(true).body_bytes())?;
+        write!(writer, "{},", self.readers.get(false).body_bytes())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,338 @@
+/// Largest message head buffered, with the start line and the headers
+const MAX_HEAD_LEN: usize = 16384;
+
+/// Largest chunk size or trailer line buffered
+const MAX_LINE_LEN: usize = 1024;
+
+/// Check if the bytes can be the start of a message, to stop reading the
+/// streams of other protocols early
+fn can_start_message(bytes: &[u8]) -> bool {
+    let token = bytes.split(|byte| *byte == b' ').next().unwrap_or_default();
+    token.iter().all(u8::is_ascii_uppercase)
+        || token.starts_with(b"HTTP/1.")
+        || b"HTTP/1.".starts_with(token)
+}
+
+/// Start line of a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartLine {
+    Request { method: String, uri: Vec<u8> },
+    Response { status: u16 },
+}
+
+/// Start line and headers of a HTTP/1.x message
+#[derive(Debug, Clone)]
+pub struct HttpHead {
+    pub start_line: StartLine,
+    /// Minor version of HTTP/1.x
+    pub minor_version: u8,
+    /// Headers with their names in lowercase
+    pub headers: Vec<(String, Vec<u8>)>,
+}
+
+impl HttpHead {
+    /// Value of the first header with the given lowercase name
+    pub fn header(&self, name: &str) -> Option<&[u8]> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    fn parse(head: &[u8]) -> Option<HttpHead> {
+        let mut lines = head
+            .split(|byte| *byte == b'\n')
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+        let first_line = lines.next()?;
+
+        let (start_line, minor_version) = if let Some(rest) = first_line.strip_prefix(b"HTTP/1.") {
+            let minor_version = rest.first()?.checked_sub(b'0').filter(|v| *v <= 9)?;
+            let status = std::str::from_utf8(rest.get(2..5)?).ok()?.parse().ok()?;
+            (StartLine::Response { status }, minor_version)
+        } else {
+            let mut parts = first_line.split(|byte| *byte == b' ');
+            let method = parts.next()?;
+            let uri = parts.next()?;
+            let version = parts.next()?.strip_prefix(b"HTTP/1.")?;
+            if method.is_empty() || !method.iter().all(u8::is_ascii_uppercase) {
+                return None;
+            }
+            let minor_version = version.first()?.checked_sub(b'0').filter(|v| *v <= 9)?;
+            (
+                StartLine::Request {
+                    method: String::from_utf8(method.to_vec()).ok()?,
+                    uri: uri.to_vec(),
+                },
+                minor_version,
+            )
+        };
+
+        let headers = lines
+            .filter(|line| !line.is_empty())
+            .filter_map(|line| {
+                let separator = line.iter().position(|byte| *byte == b':')?;
+                let name = std::str::from_utf8(&line[..separator]).ok()?;
+                Some((
+                    name.trim().to_ascii_lowercase(),
+                    line[separator + 1..].trim_ascii().to_vec(),
+                ))
+            })
+            .collect();
+
+        Some(HttpHead {
+            start_line,
+            minor_version,
+            headers,
+        })
+    }
+
+    /// Framing of the body of the message, given whether the message can
+    /// have a body at all
+    fn body(&self, can_have_body: bool) -> Body {
+        if !can_have_body {
+            return Body::Length(0);
+        }
+        let chunked = self.header("transfer-encoding").is_some_and(|value| {
+            value
+                .to_ascii_lowercase()
+                .windows(7)
+                .any(|window| window == b"chunked")
+        });
+        if chunked {
+            return Body::ChunkSize;
+        }
+        let length = self
+            .header("content-length")
+            .and_then(|value| std::str::from_utf8(value).ok()?.parse().ok());
+        match (length, &self.start_line) {
+            (Some(length), _) => Body::Length(length),
+            (None, StartLine::Request { .. }) => Body::Length(0),
+            (None, StartLine::Response { .. }) => Body::UntilClose,
+        }
+    }
+}
+
+/// Part of the stream expected next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Body {
+    /// Remaining bytes of a body with a known length
+    Length(u64),
+    /// Line with the size of the next chunk
+    ChunkSize,
+    /// Remaining bytes of a chunk, including its line ending
+    ChunkData(u64),
+    /// Trailer lines after the last chunk
+    Trailers,
+    /// Body that lasts until the connection is closed
+    UntilClose,
+}
+
+/// Reader of the HTTP/1.x messages of a direction of a TCP stream
+#[derive(Debug, Clone, Default)]
+pub struct HttpStreamReader {
+    buffer: Vec<u8>,
+    body: Option<Body>,
+    body_bytes: u64,
+    /// Whether the stream is not HTTP, a message was malformed or bytes were
+    /// missed, so no more messages can be read
+    done: bool,
+}
+
+impl HttpStreamReader {
+    /// Number of body bytes skipped, without the chunked encoding
+    pub fn body_bytes(&self) -> u64 {
+        self.body_bytes
+    }
+
+    /// Include the next bytes of the stream and call `consume` with the head
+    /// of each message they complete. `consume` returns whether the message
+    /// can have a body, which is false for responses to HEAD requests, among
+    /// others
+    pub fn include<F: FnMut(&HttpHead) -> bool>(&mut self, gap: bool, data: &[u8], mut consume: F) {
+        if gap {
+            self.finish();
+        }
+        if self.done {
+            return;
+        }
+
+        let mut data = data;
+        while !data.is_empty() && !self.done {
+            data = match self.body {
+                None => self.read_head(data, &mut consume),
+                Some(body) => self.read_body(body, data),
+            };
+        }
+    }
+
+    fn read_head<'a, F: FnMut(&HttpHead) -> bool>(
+        &mut self,
+        data: &'a [u8],
+        consume: &mut F,
+    ) -> &'a [u8] {
+        // Search from the end of the previous bytes, in case the blank line
+        // was split between them
+        let search_start = self.buffer.len().saturating_sub(3);
+        self.buffer.extend_from_slice(data);
+        let searched = &self.buffer[search_start..];
+        let end = [&b"\n\n"[..], b"\r\n\r\n"]
+            .into_iter()
+            .filter_map(|blank_line| {
+                searched
+                    .windows(blank_line.len())
+                    .position(|window| window == blank_line)
+                    .map(|position| search_start + position + blank_line.len())
+            })
+            .min();
+
+        let Some(end) = end else {
+            if self.buffer.len() > MAX_HEAD_LEN || !can_start_message(&self.buffer) {
+                self.finish();
+            }
+            return &[];
+        };
+
+        let Some(head) = HttpHead::parse(&self.buffer[..end]) else {
+            self.finish();
+            return &[];
+        };
+        let body = head.body(consume(&head));
+        self.body = (body != Body::Length(0)).then_some(body);
+
+        // Bytes after the head are given back to be read
+        let consumed = data.len() - (self.buffer.len() - end);
+        self.buffer.clear();
+        &data[consumed..]
+    }
+
+    fn read_body<'a>(&mut self, body: Body, data: &'a [u8]) -> &'a [u8] {
+        match body {
+            Body::Length(remaining) => {
+                let len = remaining.min(data.len() as u64);
+                self.body_bytes += len;
+                self.body = (remaining != len).then_some(Body::Length(remaining - len));
+                &data[len as usize..]
+            }
+            Body::ChunkData(remaining) => {
+                // The line ending after the chunk is not part of the body
+                let len = remaining.min(data.len() as u64);
+                self.body_bytes += len.min(remaining.saturating_sub(2));
+                self.body = Some(if remaining == len {
+                    Body::ChunkSize
+                } else {
+                    Body::ChunkData(remaining - len)
+                });
+                &data[len as usize..]
+            }
+            Body::ChunkSize | Body::Trailers => {
+                let Some(line_end) = data.iter().position(|byte| *byte == b'\n') else {
+                    self.buffer.extend_from_slice(data);
+                    if self.buffer.len() > MAX_LINE_LEN {
+                        self.finish();
+                    }
+                    return &[];
+                };
+                self.buffer.extend_from_slice(&data[..line_end]);
+                let line = std::mem::take(&mut self.buffer);
+                let line = line.strip_suffix(b"\r").unwrap_or(&line);
+
+                self.body = if body == Body::Trailers {
+                    (!line.is_empty()).then_some(Body::Trailers)
+                } else {
+                    let size = line.split(|byte| *byte == b';').next().and_then(|size| {
+                        u64::from_str_radix(std::str::from_utf8(size).ok()?.trim(), 16).ok()
+                    });
+                    // The chunk data is followed by a CRLF
+                    match size.map(|size| (size, size.checked_add(2))) {
+                        Some((0, _)) => Some(Body::Trailers),
+                        Some((_, Some(len))) => Some(Body::ChunkData(len)),
+                        _ => {
+                            self.finish();
+                            None
+                        }
+                    }
+                };
+                &data[line_end + 1..]
+            }
+            Body::UntilClose => {
+                self.body_bytes += data.len() as u64;
+                &[]
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        self.done = true;
+        self.buffer = Vec::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_head() {
+        let head = HttpHead::parse(
+            b"GET /bins/x86 HTTP/1.0\r\nHost: 10.0.0.1\r\nUser-Agent:  wget \r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            head.start_line,
+            StartLine::Request {
+                method: "GET".to_string(),
+                uri: b"/bins/x86".to_vec()
+            }
+        );
+        assert_eq!(head.minor_version, 0);
+        assert_eq!(head.header("user-agent"), Some(&b"wget"[..]));
+
+        let head = HttpHead::parse(b"HTTP/1.1 404 Not Found\n\n").unwrap();
+        assert_eq!(head.start_line, StartLine::Response { status: 404 });
+
+        assert!(HttpHead::parse(b"SSH-2.0-OpenSSH\r\n\r\n").is_none());
+        assert!(can_start_message(b"POS"));
+        assert!(can_start_message(b"HTTP/1.1 2"));
+        assert!(!can_start_message(b"\x16\x03\x01"));
+    }
+
+    #[test]
+    fn test_stream_reader() {
+        let mut v = HttpStreamReader::default();
+        let mut statuses = Vec::new();
+        let mut consume = |head: &HttpHead| {
+            if let StartLine::Response { status } = head.start_line {
+                statuses.push(status);
+            }
+            true
+        };
+
+        v.include(
+            false,
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r",
+            &mut consume,
+        );
+        v.include(false, b"\n\r\nhelloHTTP/1.1 200 OK\r\n", &mut consume);
+        v.include(
+            false,
+            b"Transfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2;x=y\r\nde\r\n0\r\n\r\n",
+            &mut consume,
+        );
+        v.include(false, b"HTTP/1.1 204 No Content\r\n\r\n", &mut consume);
+
+        assert_eq!(statuses, vec![200, 200, 204]);
+        assert_eq!(v.body_bytes(), 10);
+        assert!(!v.done);
+    }
+
+    #[test]
+    fn test_stream_reader_malformed_chunk_size() {
+        let mut v = HttpStreamReader::default();
+        v.include(
+            false,
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffff\r\n",
+            |_| true,
+        );
+        assert!(v.done);
+    }
+}
@@ -1,12 +1,12 @@
-use super::{running_stat::RunningStat, FlowStat, FlowTimes};
+use super::{running_stat::RunningStat, FlowStat, FlowTimes, StatSettings};
 use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
 use chrono::{DateTime, Utc};
 use std::{
     io::{BufWriter, Error, Write},
-    net::IpAddr,
+    rc::Rc,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Interarrival {
     bidirectional_last_time: DateTime<Utc>,
     forward_last_time: DateTime<Utc>,
@@ -14,10 +14,15 @@ pub struct Interarrival {
     bidirectional_iat: RunningStat,
     forward_iat: RunningStat,
     backward_iat: RunningStat,
+    /// Percentiles estimated on the inter arrival times
+    percentiles: Rc<[u8]>,
+    /// Whether the skewness and kurtosis are written
+    higher_moments: bool,
 }
 
 impl FlowStat for Interarrival {
     fn from_packet(
+        settings: &StatSettings,
         _identifier: &TransportFlowIdentifier,
         flow_times: &FlowTimes,
         _packet_header: &pcap::PacketHeader,
@@ -27,9 +32,15 @@ impl FlowStat for Interarrival {
         let bidirectional_last_time = flow_times.last_packet_time;
         let forward_last_time = flow_times.last_packet_time;
         let backward_last_time = None;
-        let bidirectional_iat = RunningStat::new();
-        let forward_iat = RunningStat::new();
-        let backward_iat = RunningStat::new();
+        let percentiles = settings.shared_percentiles();
+        let new_stat = if percentiles.is_empty() {
+            RunningStat::new
+        } else {
+            RunningStat::with_quantiles
+        };
+        let bidirectional_iat = new_stat();
+        let forward_iat = new_stat();
+        let backward_iat = new_stat();
 
         Interarrival {
             bidirectional_last_time,
@@ -38,6 +49,8 @@ impl FlowStat for Interarrival {
             bidirectional_iat,
             forward_iat,
             backward_iat,
+            percentiles,
+            higher_moments: settings.higher_moments(),
         }
     }
     fn include(
@@ -48,13 +61,7 @@ impl FlowStat for Interarrival {
         sliced_packet: &etherparse::SlicedPacket,
         _reasembly_information: Option<&FragmentReasemblyInformation>,
     ) {
-        let src_ip = match &sliced_packet.net {
-            Some(header) => match header {
-                etherparse::NetSlice::Ipv4(v) => IpAddr::V4(v.header().source_addr()),
-                etherparse::NetSlice::Ipv6(v) => IpAddr::V6(v.header().source_addr()),
-            },
-            None => panic!("Unexpected sliced packet without net layer"),
-        };
+        let is_forward = identifier.is_forward(sliced_packet);
         let time = flow_times.last_packet_time;
 
         // Update bidirectional
@@ -68,7 +75,7 @@ impl FlowStat for Interarrival {
         self.bidirectional_last_time = time;
 
         // Update direction
-        if src_ip == identifier.source_ip {
+        if is_forward {
             let forward_increment: u64 =
                 (time - self.forward_last_time).num_microseconds().unwrap() as u64;
             self.forward_iat.include(forward_increment);
@@ -84,6 +91,7 @@ impl FlowStat for Interarrival {
     }
     fn write_csv_header<T: ?Sized + std::io::Write>(
         writer: &mut BufWriter<T>,
+        settings: &StatSettings,
     ) -> Result<(), Error> {
         write!(writer, "bidirectional_inter_arrival_time_max,")?;
         write!(writer, "bidirectional_inter_arrival_time_min,")?;
@@ -100,6 +108,19 @@ impl FlowStat for Interarrival {
         write!(writer, "backward_inter_arrival_time_mean,")?;
         write!(writer, "backward_inter_arrival_time_std,")?;
 
+        for direction in ["bidirectional", "forward", "backward"] {
+            for percentile in settings.percentiles() {
+                write!(writer, "{}_inter_arrival_time_p{},", direction, percentile)?;
+            }
+        }
+
+        if settings.higher_moments() {
+            for direction in ["bidirectional", "forward", "backward"] {
+                write!(writer, "{}_inter_arrival_time_skewness,", direction)?;
+                write!(writer, "{}_inter_arrival_time_kurtosis,", direction)?;
+            }
+        }
+
         Ok(())
     }
     fn write_csv_value<T: ?Sized + std::io::Write>(
@@ -170,6 +191,31 @@ impl FlowStat for Interarrival {
             self.backward_iat.current_standard_deviation() / 1_000_000.0
         )?;
 
+        for stat in [
+            &self.bidirectional_iat,
+            &self.forward_iat,
+            &self.backward_iat,
+        ] {
+            for percentile in self.percentiles.iter() {
+                write!(
+                    writer,
+                    "{:.9},",
+                    stat.current_percentile(*percentile).unwrap_or(0.0) / 1_000_000.0
+                )?;
+            }
+        }
+
+        if self.higher_moments {
+            for stat in [
+                &self.bidirectional_iat,
+                &self.forward_iat,
+                &self.backward_iat,
+            ] {
+                write!(writer, "{:.9},", stat.current_skewness())?;
+                write!(writer, "{:.9},", stat.current_kurtosis())?;
+            }
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,326 @@
+use super::{running_stat::RunningStat, FlowStat, FlowTimes, StatSettings};
+use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
+use std::io::{BufWriter, Error, Write};
+
+/// Largest IP ID increment still considered sequential, leaving room for the
+/// packets the host sends to other destinations in between
+const SEQUENTIAL_MAX_INCREMENT: u16 = 1024;
+
+/// Initial TTL values used by the common network stacks
+const ORIGINAL_TTLS: [u8; 4] = [32, 64, 128, 255];
+
+/// Infer the TTL a packet was sent with from the one it was received with
+fn infer_original_ttl(ttl: u8) -> u8 {
+    ORIGINAL_TTLS
+        .into_iter()
+        .find(|original| ttl <= *original)
+        .unwrap_or(u8::MAX)
+}
+
+/// The IP header fields of a packet used by the stat
+#[derive(Debug, Clone)]
+struct IpFields {
+    /// TTL or hop limit
+    ttl: u8,
+    /// Identification, only on IPv4
+    identification: Option<u16>,
+    dont_fragment: bool,
+    /// Type of service or traffic class, holding the DSCP and ECN codepoints
+    traffic_class: u8,
+    /// Flow label, only on IPv6
+    flow_label: Option<u32>,
+}
+
+impl IpFields {
+    fn from_packet(sliced_packet: &etherparse::SlicedPacket) -> Option<IpFields> {
+        match &sliced_packet.net {
+            Some(etherparse::NetSlice::Ipv4(v)) => {
+                let header = v.header();
+                Some(IpFields {
+                    ttl: header.ttl(),
+                    identification: Some(header.identification()),
+                    dont_fragment: header.dont_fragment(),
+                    traffic_class: header.slice()[1],
+                    flow_label: None,
+                })
+            }
+            Some(etherparse::NetSlice::Ipv6(v)) => {
+                let header = v.header();
+                let slice = header.slice();
+                Some(IpFields {
+                    ttl: header.hop_limit(),
+                    identification: None,
+                    dont_fragment: false,
+                    traffic_class: header.traffic_class(),
+                    flow_label: Some(
+                        u32::from_be_bytes([0, slice[1], slice[2], slice[3]]) & 0x000F_FFFF,
+                    ),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn dscp(&self) -> u8 {
+        self.traffic_class >> 2
+    }
+
+    fn ecn(&self) -> u8 {
+        self.traffic_class & 0b11
+    }
+}
+
+/// IP header fields seen on a single direction
+#[derive(Debug, Clone)]
+struct DirectionIpHeader {
+    first: Option<IpFields>,
+    ttl: RunningStat,
+
+    last_identification: Option<u16>,
+    ip_id_zero_count: u32,
+    ip_id_constant_count: u32,
+    ip_id_sequential_count: u32,
+    ip_id_random_count: u32,
+
+    dont_fragment_count: u32,
+    dscp_change_count: u32,
+    ecn_capable_count: u32,
+    ecn_congestion_count: u32,
+    flow_label_change_count: u32,
+    last_dscp: u8,
+    last_flow_label: Option<u32>,
+}
+
+impl DirectionIpHeader {
+    fn new() -> DirectionIpHeader {
+        DirectionIpHeader {
+            first: None,
+            ttl: RunningStat::new(),
+            last_identification: None,
+            ip_id_zero_count: 0,
+            ip_id_constant_count: 0,
+            ip_id_sequential_count: 0,
+            ip_id_random_count: 0,
+            dont_fragment_count: 0,
+            dscp_change_count: 0,
+            ecn_capable_count: 0,
+            ecn_congestion_count: 0,
+            flow_label_change_count: 0,
+            last_dscp: 0,
+            last_flow_label: None,
+        }
+    }
+
+    fn include(&mut self, fields: IpFields) {
+        self.ttl.include(u64::from(fields.ttl));
+
+        if let Some(identification) = fields.identification {
+            if let Some(last) = self.last_identification {
+                let increment = identification.wrapping_sub(last);
+                if identification == 0 && last == 0 {
+                    self.ip_id_zero_count += 1;
+                } else if increment == 0 {
+                    self.ip_id_constant_count += 1;
+                } else if increment <= SEQUENTIAL_MAX_INCREMENT {
+                    self.ip_id_sequential_count += 1;
+                } else {
+                    self.ip_id_random_count += 1;
+                }
+            }
+            self.last_identification = Some(identification);
+        }
+
+        if fields.dont_fragment {
+            self.dont_fragment_count += 1;
+        }
+        match fields.ecn() {
+            0 => {}
+            3 => self.ecn_congestion_count += 1,
+            _ => self.ecn_capable_count += 1,
+        }
+
+        if self.first.is_some() {
+            if fields.dscp() != self.last_dscp {
+                self.dscp_change_count += 1;
+            }
+            if fields.flow_label != self.last_flow_label {
+                self.flow_label_change_count += 1;
+            }
+        }
+        self.last_dscp = fields.dscp();
+        self.last_flow_label = fields.flow_label;
+
+        if self.first.is_none() {
+            self.first = Some(fields);
+        }
+    }
+
+    /// Most common behaviour of the IP ID increments, or `none` if there are
+    /// no increments
+    fn ip_id_behaviour(&self) -> &'static str {
+        [
+            ("zero", self.ip_id_zero_count),
+            ("constant", self.ip_id_constant_count),
+            ("sequential", self.ip_id_sequential_count),
+            ("random", self.ip_id_random_count),
+        ]
+        .into_iter()
+        .filter(|(_, count)| *count != 0)
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map_or("none", |(name, _)| name)
+    }
+
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+    ) -> Result<(), Error> {
+        // Directions never seen have no TTLs to infer from
+        let (ttl, original_ttl, ttl_variance, dscp, flow_label) = match &self.first {
+            Some(first) => (
+                first.ttl,
+                infer_original_ttl(first.ttl),
+                self.ttl.current_variance(),
+                first.dscp(),
+                first.flow_label.unwrap_or(0),
+            ),
+            None => (0, 0, 0.0, 0, 0),
+        };
+        write!(writer, "{},", ttl)?;
+        write!(writer, "{},", original_ttl)?;
+        write!(writer, "{:.9},", ttl_variance)?;
+        write!(writer, "{},", self.ip_id_zero_count)?;
+        write!(writer, "{},", self.ip_id_constant_count)?;
+        write!(writer, "{},", self.ip_id_sequential_count)?;
+        write!(writer, "{},", self.ip_id_random_count)?;
+        write!(writer, "{},", self.ip_id_behaviour())?;
+        write!(writer, "{},", self.dont_fragment_count)?;
+        write!(writer, "{},", dscp)?;
+        write!(writer, "{},", self.dscp_change_count)?;
+        write!(writer, "{},", self.ecn_capable_count)?;
+        write!(writer, "{},", self.ecn_congestion_count)?;
+        write!(writer, "{},", flow_label)?;
+        write!(writer, "{},", self.flow_label_change_count)?;
+        Ok(())
+    }
+}
+
+/// IP header fields of each direction: initial and inferred original TTL or
+/// hop limit, TTL variance, IP ID increments, DF flags, DSCP and ECN
+/// codepoints and IPv6 flow label changes
+#[derive(Debug, Clone)]
+pub struct IpHeader {
+    forward: DirectionIpHeader,
+    backward: DirectionIpHeader,
+}
+
+impl FlowStat for IpHeader {
+    fn from_packet(
+        _settings: &StatSettings,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        let mut ip_header = IpHeader {
+            forward: DirectionIpHeader::new(),
+            backward: DirectionIpHeader::new(),
+        };
+
+        if let Some(fields) = IpFields::from_packet(sliced_packet) {
+            ip_header.forward.include(fields);
+        }
+        ip_header
+    }
+    fn include(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        if let Some(fields) = IpFields::from_packet(sliced_packet) {
+            if identifier.is_forward(sliced_packet) {
+                self.forward.include(fields);
+            } else {
+                self.backward.include(fields);
+            }
+        }
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        for direction in ["forward", "backward"] {
+            write!(writer, "{}_ttl_initial,", direction)?;
+            write!(writer, "{}_ttl_original,", direction)?;
+            write!(writer, "{}_ttl_variance,", direction)?;
+            write!(writer, "{}_ip_id_zero_count,", direction)?;
+            write!(writer, "{}_ip_id_constant_count,", direction)?;
+            write!(writer, "{}_ip_id_sequential_count,", direction)?;
+            write!(writer, "{}_ip_id_random_count,", direction)?;
+            write!(writer, "{}_ip_id_behaviour,", direction)?;
+            write!(writer, "{}_dont_fragment_count,", direction)?;
+            write!(writer, "{}_dscp,", direction)?;
+            write!(writer, "{}_dscp_change_count,", direction)?;
+            write!(writer, "{}_ecn_capable_count,", direction)?;
+            write!(writer, "{}_ecn_congestion_count,", direction)?;
+            write!(writer, "{}_flow_label,", direction)?;
+            write!(writer, "{}_flow_label_change_count,", direction)?;
+        }
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        self.forward.write_csv_value(writer)?;
+        self.backward.write_csv_value(writer)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ipv4_fields(ttl: u8, identification: u16) -> IpFields {
+        IpFields {
+            ttl,
+            identification: Some(identification),
+            dont_fragment: true,
+            traffic_class: 0b1011_1001,
+            flow_label: None,
+        }
+    }
+
+    #[test]
+    fn test_original_ttl() {
+        assert_eq!(infer_original_ttl(1), 32);
+        assert_eq!(infer_original_ttl(50), 64);
+        assert_eq!(infer_original_ttl(64), 64);
+        assert_eq!(infer_original_ttl(117), 128);
+        assert_eq!(infer_original_ttl(240), 255);
+    }
+
+    #[test]
+    fn test_ip_id_behaviour() {
+        let mut v = DirectionIpHeader::new();
+        assert_eq!(v.ip_id_behaviour(), "none");
+
+        for (ttl, identification) in [(64, 65534), (64, 65535), (63, 3), (64, 40000)] {
+            v.include(ipv4_fields(ttl, identification));
+        }
+
+        assert_eq!(v.ip_id_sequential_count, 2);
+        assert_eq!(v.ip_id_random_count, 1);
+        assert_eq!(v.ip_id_behaviour(), "sequential");
+        assert_eq!(v.dont_fragment_count, 4);
+        assert_eq!(v.ecn_capable_count, 4);
+        assert_eq!(v.first.as_ref().unwrap().dscp(), 46);
+        assert_eq!(v.dscp_change_count, 0);
+    }
+}
@@ -0,0 +1,62 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    rc::Rc,
+};
+
+/// Largest number of media endpoints remembered. The oldest ones are
+/// forgotten first
+const MAX_ENDPOINTS: usize = 65536;
+
+/// Media session negotiated by a SIP dialog
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaSession {
+    /// Call-ID of the dialog, escaped to be written
+    pub call_id: Rc<str>,
+    /// Clock rates of the dynamic payload types, from the rtpmap attributes
+    pub clock_rates: Vec<(u8, u32)>,
+}
+
+#[derive(Debug, Default)]
+struct Endpoints {
+    sessions: HashMap<(IpAddr, u16), MediaSession>,
+    order: VecDeque<(IpAddr, u16)>,
+}
+
+/// Media endpoints announced with SDP on the SIP flows, shared between all
+/// the flows so the RTP flows can be linked to the dialog that negotiated
+/// them
+#[derive(Debug, Default)]
+pub struct MediaSessions {
+    endpoints: RefCell<Endpoints>,
+}
+
+impl MediaSessions {
+    /// Remember the session of the media received on the given address and
+    /// port
+    pub fn insert(&self, address: IpAddr, port: u16, session: MediaSession) {
+        let mut endpoints = self.endpoints.borrow_mut();
+        if endpoints
+            .sessions
+            .insert((address, port), session)
+            .is_none()
+        {
+            endpoints.order.push_back((address, port));
+        }
+        if endpoints.order.len() > MAX_ENDPOINTS {
+            if let Some(oldest) = endpoints.order.pop_front() {
+                endpoints.sessions.remove(&oldest);
+            }
+        }
+    }
+
+    /// Session of the media received on the given address and port
+    pub fn get(&self, address: IpAddr, port: u16) -> Option<MediaSession> {
+        self.endpoints
+            .borrow()
+            .sessions
+            .get(&(address, port))
+            .cloned()
+    }
+}
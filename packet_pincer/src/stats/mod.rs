@@ -1,6 +1,27 @@
+mod app_protocol;
+pub use app_protocol::*;
+
+mod app_signatures;
+pub use app_signatures::*;
+
+mod bulk_transfer;
+pub use bulk_transfer::*;
+
 mod byte_count;
 pub use byte_count::*;
 
+mod cic_flow_meter;
+pub use cic_flow_meter::*;
+
+mod coap;
+pub use coap::*;
+
+mod dnp3;
+pub use dnp3::*;
+
+mod dns;
+pub use dns::*;
+
 mod flow_stat;
 pub use flow_stat::*;
 
@@ -10,20 +31,98 @@ pub use flow_statistics::*;
 mod flow_times;
 pub use flow_times::*;
 
+mod histograms;
+pub use histograms::*;
+
+mod http;
+pub use http::*;
+
+mod ip_header;
+pub use ip_header::*;
+
+mod modbus;
+pub use modbus::*;
+
+mod mqtt;
+pub use mqtt::*;
+
+mod os_fingerprint;
+pub use os_fingerprint::*;
+
+mod os_signatures;
+pub use os_signatures::*;
+
 mod packet_count;
 pub use packet_count::*;
 
+mod packet_sequence;
+pub use packet_sequence::*;
+
+mod payload_content;
+pub use payload_content::*;
+
 mod protocols;
 pub use protocols::*;
 
+mod reflection;
+pub use reflection::*;
+
+mod rtp;
+pub use rtp::*;
+
+mod running_stat;
+pub use running_stat::*;
+
+mod sip;
+pub use sip::*;
+
+mod ssh;
+pub use ssh::*;
+
+mod stat_settings;
+pub use stat_settings::*;
+
+mod tcp_analysis;
+pub use tcp_analysis::*;
+
+mod tcp_connection;
+pub use tcp_connection::*;
+
 mod tcp_flags;
 pub use tcp_flags::*;
 
+mod tcp_options;
+pub use tcp_options::*;
+
+mod tls;
+pub use tls::*;
+
 mod transport;
 pub use transport::*;
 
 mod activity;
 pub use activity::*;
 
+mod bulk;
+mod byte_reader;
+mod coap_message;
+mod csv_text;
+mod digest;
+mod dnp3_message;
+mod dns_message;
+mod http_message;
 mod interarrival;
-mod running_stat;
+mod media_sessions;
+mod modbus_message;
+mod mqtt_message;
+mod quantile_sketch;
+mod reflection_message;
+mod rtp_message;
+mod sip_message;
+mod ssh_message;
+mod stream_frames;
+mod tcp_option_parse;
+mod tcp_sequence;
+#[cfg(test)]
+mod test_flow;
+mod tls_message;
@@ -0,0 +1,217 @@
+use super::{
+    modbus_message::{frame_len, ModbusFrame, FUNCTION_CODES, MODBUS_PORT},
+    stream_frames::{Directional, FrameReader},
+    FlowStat, FlowTimes, StatSettings,
+};
+use crate::{
+    packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier,
+    stream_reassembly::StreamChunk,
+};
+use std::{
+    collections::BTreeSet,
+    io::{BufWriter, Error, Write},
+};
+
+/// Modbus/TCP frames of the flows on the Modbus port, read from the
+/// reassembled streams: function codes of the requests, exception responses,
+/// unit identifiers and the number of registers and coils read and written
+#[derive(Debug, Clone)]
+pub struct Modbus {
+    readers: Directional<FrameReader>,
+    is_modbus: bool,
+    /// Whether the requests are sent on the forward direction
+    client_is_forward: bool,
+
+    request_count: u32,
+    response_count: u32,
+    function_code_counts: [u32; FUNCTION_CODES.len()],
+    other_function_code_count: u32,
+    exception_count: u32,
+    first_exception_code: u8,
+    unit_ids: BTreeSet<u8>,
+    first_unit_id: Option<u8>,
+    /// Registers read and written and coils read and written
+    quantities: [u64; 4],
+}
+
+impl Modbus {
+    fn include_frame(&mut self, is_request: bool, frame: &[u8]) {
+        let Some(frame) = ModbusFrame::parse(frame) else {
+            return;
+        };
+        self.unit_ids.insert(frame.unit_id);
+        self.first_unit_id.get_or_insert(frame.unit_id);
+
+        if is_request {
+            self.request_count += 1;
+            match FUNCTION_CODES
+                .iter()
+                .position(|(_, code)| *code == frame.function_code)
+            {
+                Some(i) => self.function_code_counts[i] += 1,
+                None => self.other_function_code_count += 1,
+            }
+            for (quantity, added) in self.quantities.iter_mut().zip(frame.request_quantities()) {
+                *quantity += added;
+            }
+        } else {
+            self.response_count += 1;
+            if frame.is_exception() {
+                self.exception_count += 1;
+                if self.first_exception_code == 0 {
+                    self.first_exception_code = frame.data.first().copied().unwrap_or(0);
+                }
+            }
+        }
+    }
+}
+
+impl FlowStat for Modbus {
+    fn from_packet(
+        _settings: &StatSettings,
+        identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        _sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        Modbus {
+            readers: Directional::default(),
+            is_modbus: identifier.source_port() == MODBUS_PORT
+                || identifier.dest_port() == MODBUS_PORT,
+            client_is_forward: identifier.dest_port() == MODBUS_PORT,
+            request_count: 0,
+            response_count: 0,
+            function_code_counts: [0; FUNCTION_CODES.len()],
+            other_function_code_count: 0,
+            exception_count: 0,
+            first_exception_code: 0,
+            unit_ids: BTreeSet::new(),
+            first_unit_id: None,
+            quantities: [0; 4],
+        }
+    }
+    fn include(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        _sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+    }
+    fn uses_streams(_settings: &StatSettings) -> bool {
+        true
+    }
+    fn include_stream(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        chunk: &StreamChunk,
+    ) {
+        if !self.is_modbus {
+            return;
+        }
+
+        let is_request = chunk.is_forward == self.client_is_forward;
+        Directional::with(
+            self,
+            |modbus| &mut modbus.readers,
+            chunk.is_forward,
+            |modbus, reader| {
+                reader.include(chunk.gap != 0, chunk.data, frame_len, |frame| {
+                    modbus.include_frame(is_request, frame);
+                    true
+                })
+            },
+        );
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        write!(writer, "modbus_request_count,modbus_response_count,")?;
+        for (name, _) in FUNCTION_CODES {
+            write!(writer, "modbus_fc_{}_count,", name)?;
+        }
+        write!(writer, "modbus_fc_other_count,")?;
+        write!(
+            writer,
+            "modbus_exception_count,modbus_first_exception_code,"
+        )?;
+        write!(writer, "modbus_unit_id_count,modbus_first_unit_id,")?;
+        write!(
+            writer,
+            "modbus_read_register_count,modbus_write_register_count,"
+        )?;
+        write!(writer, "modbus_read_coil_count,modbus_write_coil_count,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        write!(writer, "{},{},", self.request_count, self.response_count)?;
+        for count in self.function_code_counts {
+            write!(writer, "{},", count)?;
+        }
+        write!(writer, "{},", self.other_function_code_count)?;
+        write!(writer, "{},", self.exception_count)?;
+        write!(writer, "{},", self.first_exception_code)?;
+        write!(writer, "{},", self.unit_ids.len())?;
+        write!(writer, "{},", self.first_unit_id.unwrap_or(0))?;
+        for quantity in self.quantities {
+            write!(writer, "{},", quantity)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::test_flow::{TestFlow, ACK, PSH, SYN};
+
+    const READ_REQUEST: [u8; 12] = [0, 1, 0, 0, 0, 6, 1, 3, 0, 0, 0, 10];
+    const EXCEPTION_RESPONSE: [u8; 9] = [0, 1, 0, 0, 0, 3, 1, 0x83, 2];
+    const WRITE_REQUEST: [u8; 12] = [0, 2, 0, 0, 0, 6, 1, 6, 0, 1, 0, 3];
+
+    #[test]
+    fn test_requests_and_responses() {
+        let mut v = TestFlow::<Modbus>::new(StatSettings::default(), 40000, MODBUS_PORT);
+        v.tcp(true, 0, SYN, 100, 0, b"")
+            .tcp(false, 1000, SYN | ACK, 500, 101, b"")
+            .tcp(true, 2000, ACK, 101, 501, b"")
+            .tcp(true, 3000, PSH | ACK, 101, 501, &READ_REQUEST)
+            .tcp(false, 4000, PSH | ACK, 501, 113, &EXCEPTION_RESPONSE);
+        // The write request is split between two segments
+        v.tcp(true, 5000, PSH | ACK, 113, 510, &WRITE_REQUEST[..5])
+            .tcp(true, 6000, PSH | ACK, 118, 510, &WRITE_REQUEST[5..])
+            .tcp(false, 7000, PSH | ACK, 510, 125, &WRITE_REQUEST);
+
+        assert_eq!(v.value("modbus_request_count"), "2");
+        assert_eq!(v.value("modbus_response_count"), "2");
+        assert_eq!(v.value("modbus_fc_read_holding_registers_count"), "1");
+        assert_eq!(v.value("modbus_fc_write_single_register_count"), "1");
+        assert_eq!(v.value("modbus_exception_count"), "1");
+        assert_eq!(v.value("modbus_first_exception_code"), "2");
+        assert_eq!(v.value("modbus_unit_id_count"), "1");
+        assert_eq!(v.value("modbus_read_register_count"), "10");
+        assert_eq!(v.value("modbus_write_register_count"), "1");
+    }
+
+    #[test]
+    fn test_server_first() {
+        // The capture starts with a response, so the forward direction is
+        // the one of the server
+        let mut v = TestFlow::<Modbus>::new(StatSettings::default(), MODBUS_PORT, 40000);
+        v.tcp(true, 0, PSH | ACK, 500, 101, &EXCEPTION_RESPONSE)
+            .tcp(false, 1000, PSH | ACK, 101, 509, &READ_REQUEST);
+
+        assert_eq!(v.value("modbus_request_count"), "1");
+        assert_eq!(v.value("modbus_response_count"), "1");
+        assert_eq!(v.value("modbus_exception_count"), "1");
+        assert_eq!(v.value("modbus_read_register_count"), "10");
+    }
+}
@@ -0,0 +1,114 @@
+use super::stream_frames::FrameLength;
+
+/// Port of Modbus/TCP
+pub const MODBUS_PORT: u16 = 502;
+
+/// Largest length field of the MBAP header, with the unit identifier and
+/// the largest PDU
+const MAX_LENGTH: usize = 254;
+
+const READ_COILS: u8 = 1;
+const READ_DISCRETE_INPUTS: u8 = 2;
+const READ_HOLDING_REGISTERS: u8 = 3;
+const READ_INPUT_REGISTERS: u8 = 4;
+const WRITE_SINGLE_COIL: u8 = 5;
+const WRITE_SINGLE_REGISTER: u8 = 6;
+const WRITE_MULTIPLE_COILS: u8 = 15;
+const WRITE_MULTIPLE_REGISTERS: u8 = 16;
+const READ_WRITE_REGISTERS: u8 = 23;
+
+/// Function codes with a dedicated count, by their name
+pub const FUNCTION_CODES: [(&str, u8); 12] = [
+    ("read_coils", READ_COILS),
+    ("read_discrete_inputs", READ_DISCRETE_INPUTS),
+    ("read_holding_registers", READ_HOLDING_REGISTERS),
+    ("read_input_registers", READ_INPUT_REGISTERS),
+    ("write_single_coil", WRITE_SINGLE_COIL),
+    ("write_single_register", WRITE_SINGLE_REGISTER),
+    ("diagnostics", 8),
+    ("write_multiple_coils", WRITE_MULTIPLE_COILS),
+    ("write_multiple_registers", WRITE_MULTIPLE_REGISTERS),
+    ("report_server_id", 17),
+    ("read_write_registers", READ_WRITE_REGISTERS),
+    ("device_identification", 43),
+];
+
+/// Length of the Modbus/TCP frame at the start of the bytes, from its MBAP
+/// header
+pub fn frame_len(bytes: &[u8]) -> FrameLength {
+    let Some(header) = bytes.get(..6) else {
+        return FrameLength::Incomplete;
+    };
+    let protocol = u16::from_be_bytes([header[2], header[3]]);
+    let length = usize::from(u16::from_be_bytes([header[4], header[5]]));
+    if protocol != 0 || !(2..=MAX_LENGTH).contains(&length) {
+        return FrameLength::Invalid;
+    }
+    FrameLength::Length(6 + length)
+}
+
+/// Unit and PDU of a Modbus/TCP frame
+#[derive(Debug, Clone)]
+pub struct ModbusFrame<'a> {
+    pub unit_id: u8,
+    /// Function code, with the highest bit set on exception responses
+    pub function_code: u8,
+    pub data: &'a [u8],
+}
+
+impl ModbusFrame<'_> {
+    pub fn parse(frame: &[u8]) -> Option<ModbusFrame<'_>> {
+        Some(ModbusFrame {
+            unit_id: *frame.get(6)?,
+            function_code: *frame.get(7)?,
+            data: frame.get(8..)?,
+        })
+    }
+
+    pub fn is_exception(&self) -> bool {
+        self.function_code & 0x80 != 0
+    }
+
+    fn u16_at(&self, position: usize) -> u64 {
+        self.data.get(position..position + 2).map_or(0, |bytes| {
+            u64::from(u16::from_be_bytes([bytes[0], bytes[1]]))
+        })
+    }
+
+    /// Number of registers and coils read and written by a request, in that
+    /// order. Discrete inputs are counted as coils
+    pub fn request_quantities(&self) -> [u64; 4] {
+        match self.function_code {
+            READ_HOLDING_REGISTERS | READ_INPUT_REGISTERS => [self.u16_at(2), 0, 0, 0],
+            WRITE_SINGLE_REGISTER => [0, 1, 0, 0],
+            WRITE_MULTIPLE_REGISTERS => [0, self.u16_at(2), 0, 0],
+            READ_WRITE_REGISTERS => [self.u16_at(2), self.u16_at(6), 0, 0],
+            READ_COILS | READ_DISCRETE_INPUTS => [0, 0, self.u16_at(2), 0],
+            WRITE_SINGLE_COIL => [0, 0, 0, 1],
+            WRITE_MULTIPLE_COILS => [0, 0, 0, self.u16_at(2)],
+            _ => [0; 4],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame() {
+        // Write of 2 registers starting at 0x0010 on unit 1
+        let frame = [
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x0B, 0x01, 0x10, 0x00, 0x10, 0x00, 0x02, 0x04, 0x00,
+            0x0A, 0x01, 0x02,
+        ];
+        assert_eq!(frame_len(&frame), FrameLength::Length(17));
+        assert_eq!(frame_len(&frame[..5]), FrameLength::Incomplete);
+        assert_eq!(frame_len(b"GET / HTTP/1.1"), FrameLength::Invalid);
+
+        let v = ModbusFrame::parse(&frame).unwrap();
+        assert_eq!(v.unit_id, 1);
+        assert!(!v.is_exception());
+        assert_eq!(v.request_quantities(), [0, 2, 0, 0]);
+    }
+}
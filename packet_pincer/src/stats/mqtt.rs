@@ -0,0 +1,239 @@
+use super::{
+    csv_text::escape_text,
+    mqtt_message::{
+        connack_code, is_auth_failure, publish_topic, subscribe_topics, Connect, MqttStreamReader,
+        CONNACK, CONNECT, DISCONNECT, MQTT_PORT, PINGREQ, PUBLISH, SUBSCRIBE, UNSUBSCRIBE,
+    },
+    stream_frames::Directional,
+    FlowStat, FlowTimes, StatSettings,
+};
+use crate::{
+    packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier,
+    stream_reassembly::StreamChunk,
+};
+use std::io::{BufWriter, Error, Write};
+
+/// Largest number of different topics kept
+const MAX_TOPICS: usize = 64;
+
+/// MQTT packets of TCP flows on the MQTT port or starting with a CONNECT
+/// packet, read from the reassembled streams: the connection parameters and
+/// result, publish counts by QoS, topics and subscriptions
+#[derive(Debug, Clone)]
+pub struct Mqtt {
+    readers: Directional<MqttStreamReader>,
+    /// Whether the flow is on the MQTT port or a CONNECT packet was seen
+    is_mqtt: bool,
+
+    connect: Option<Connect>,
+    connect_count: u32,
+    connack_count: u32,
+    connect_refused_count: u32,
+    auth_failure_count: u32,
+
+    publish_count: u32,
+    publish_qos_counts: [u32; 3],
+    retain_count: u32,
+    publish_payload_bytes: u64,
+    topics: Vec<Vec<u8>>,
+
+    subscribe_count: u32,
+    subscription_topic_count: u32,
+    wildcard_subscription_count: u32,
+    unsubscribe_count: u32,
+    ping_count: u32,
+    disconnect_count: u32,
+}
+
+impl Mqtt {
+    fn level(&self) -> u8 {
+        self.connect.as_ref().map_or(0, |connect| connect.level)
+    }
+
+    /// Include a packet, returning whether more packets should be read
+    fn include_packet(&mut self, packet_type: u8, flags: u8, body: &[u8], body_len: usize) -> bool {
+        if packet_type == CONNECT {
+            let connect = Connect::parse(body);
+            self.is_mqtt |= connect.is_some();
+            if self.connect.is_none() {
+                self.connect = connect;
+            }
+        }
+        if !self.is_mqtt {
+            return false;
+        }
+
+        match packet_type {
+            CONNECT => self.connect_count += 1,
+            CONNACK => {
+                self.connack_count += 1;
+                if let Some(code) = connack_code(body).filter(|code| *code != 0) {
+                    self.connect_refused_count += 1;
+                    if is_auth_failure(self.level(), code) {
+                        self.auth_failure_count += 1;
+                    }
+                }
+            }
+            PUBLISH => {
+                let qos = (flags >> 1) & 0b11;
+                self.publish_count += 1;
+                if let Some(count) = self.publish_qos_counts.get_mut(usize::from(qos)) {
+                    *count += 1;
+                }
+                if flags & 0b1 != 0 {
+                    self.retain_count += 1;
+                }
+                if let Some((topic, payload_len)) = publish_topic(body, body_len, qos, self.level())
+                {
+                    self.publish_payload_bytes += payload_len as u64;
+                    if self.topics.len() < MAX_TOPICS && !self.topics.iter().any(|t| t == topic) {
+                        self.topics.push(topic.to_vec());
+                    }
+                }
+            }
+            SUBSCRIBE => {
+                self.subscribe_count += 1;
+                for topic in subscribe_topics(body, self.level()).unwrap_or_default() {
+                    self.subscription_topic_count += 1;
+                    if topic.contains(&b'#') || topic.contains(&b'+') {
+                        self.wildcard_subscription_count += 1;
+                    }
+                }
+            }
+            UNSUBSCRIBE => self.unsubscribe_count += 1,
+            PINGREQ => self.ping_count += 1,
+            DISCONNECT => self.disconnect_count += 1,
+            _ => {}
+        }
+        true
+    }
+}
+
+impl FlowStat for Mqtt {
+    fn from_packet(
+        _settings: &StatSettings,
+        identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        _sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        Mqtt {
+            readers: Directional::default(),
+            is_mqtt: identifier.source_port() == MQTT_PORT || identifier.dest_port() == MQTT_PORT,
+            connect: None,
+            connect_count: 0,
+            connack_count: 0,
+            connect_refused_count: 0,
+            auth_failure_count: 0,
+            publish_count: 0,
+            publish_qos_counts: [0; 3],
+            retain_count: 0,
+            publish_payload_bytes: 0,
+            topics: Vec::new(),
+            subscribe_count: 0,
+            subscription_topic_count: 0,
+            wildcard_subscription_count: 0,
+            unsubscribe_count: 0,
+            ping_count: 0,
+            disconnect_count: 0,
+        }
+    }
+    fn include(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        _sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+    }
+    fn uses_streams(_settings: &StatSettings) -> bool {
+        true
+    }
+    fn include_stream(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        chunk: &StreamChunk,
+    ) {
+        Directional::with(
+            self,
+            |mqtt| &mut mqtt.readers,
+            chunk.is_forward,
+            |mqtt, reader| {
+                reader.include(
+                    chunk.gap != 0,
+                    chunk.data,
+                    |packet_type, flags, body, len| {
+                        mqtt.include_packet(packet_type, flags, body, len)
+                    },
+                )
+            },
+        );
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        write!(writer, "mqtt_protocol_level,mqtt_client_id,")?;
+        write!(writer, "mqtt_username,mqtt_password,mqtt_keep_alive,")?;
+        write!(writer, "mqtt_connect_count,mqtt_connack_count,")?;
+        write!(
+            writer,
+            "mqtt_connect_refused_count,mqtt_auth_failure_count,"
+        )?;
+        write!(writer, "mqtt_publish_count,")?;
+        for qos in 0..3 {
+            write!(writer, "mqtt_publish_qos{}_count,", qos)?;
+        }
+        write!(writer, "mqtt_retain_count,mqtt_publish_payload_bytes,")?;
+        write!(writer, "mqtt_topic_count,mqtt_first_topic,")?;
+        write!(
+            writer,
+            "mqtt_subscribe_count,mqtt_subscription_topic_count,"
+        )?;
+        write!(writer, "mqtt_wildcard_subscription_count,")?;
+        write!(writer, "mqtt_unsubscribe_count,mqtt_ping_count,")?;
+        write!(writer, "mqtt_disconnect_count,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        match &self.connect {
+            Some(connect) => {
+                write!(writer, "{},", connect.level)?;
+                write!(writer, "{},", escape_text(&connect.client_id))?;
+                write!(writer, "{},", u8::from(connect.has_username))?;
+                write!(writer, "{},", u8::from(connect.has_password))?;
+                write!(writer, "{},", connect.keep_alive)?;
+            }
+            None => write!(writer, "0,,0,0,0,")?,
+        }
+        write!(writer, "{},{},", self.connect_count, self.connack_count)?;
+        write!(writer, "{},", self.connect_refused_count)?;
+        write!(writer, "{},", self.auth_failure_count)?;
+        write!(writer, "{},", self.publish_count)?;
+        for count in self.publish_qos_counts {
+            write!(writer, "{},", count)?;
+        }
+        write!(writer, "{},", self.retain_count)?;
+        write!(writer, "{},", self.publish_payload_bytes)?;
+        write!(writer, "{},", self.topics.len())?;
+        write!(
+            writer,
+            "{},",
+            escape_text(self.topics.first().map_or(&[], Vec::as_slice))
+        )?;
+        write!(writer, "{},", self.subscribe_count)?;
+        write!(writer, "{},", self.subscription_topic_count)?;
+        write!(writer, "{},", self.wildcard_subscription_count)?;
+        write!(writer, "{},", self.unsubscribe_count)?;
+        write!(writer, "{},", self.ping_count)?;
+        write!(writer, "{},", self.disconnect_count)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,275 @@
+use super::byte_reader::ByteReader;
+
+/// Port of MQTT without TLS
+pub const MQTT_PORT: u16 = 1883;
+
+/// Largest part of the packet bodies buffered. Longer bodies, like big
+/// publish messages, are only read up to it
+const MAX_BODY_PREFIX: usize = 4096;
+
+pub const CONNECT: u8 = 1;
+pub const CONNACK: u8 = 2;
+pub const PUBLISH: u8 = 3;
+pub const SUBSCRIBE: u8 = 8;
+pub const UNSUBSCRIBE: u8 = 10;
+pub const PINGREQ: u8 = 12;
+pub const DISCONNECT: u8 = 14;
+
+/// Protocol level of MQTT 5
+const LEVEL_5: u8 = 5;
+
+/// Variable byte integer, on 1 to 4 bytes
+fn variable_int(reader: &mut ByteReader) -> Option<usize> {
+    let mut value = 0;
+    for i in 0..4 {
+        let byte = reader.u8()?;
+        value |= usize::from(byte & 0x7F) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Skip the properties of MQTT 5 packets
+fn skip_properties(reader: &mut ByteReader, level: u8) -> Option<()> {
+    if level >= LEVEL_5 {
+        let len = variable_int(reader)?;
+        reader.bytes(len)?;
+    }
+    Some(())
+}
+
+/// Fields of a CONNECT packet
+#[derive(Debug, Clone)]
+pub struct Connect {
+    pub level: u8,
+    pub has_username: bool,
+    pub has_password: bool,
+    pub keep_alive: u16,
+    pub client_id: Vec<u8>,
+}
+
+impl Connect {
+    pub fn parse(body: &[u8]) -> Option<Connect> {
+        let mut reader = ByteReader::new(body);
+        let name = reader.prefixed_bytes(2)?;
+        if name != b"MQTT" && name != b"MQIsdp" {
+            return None;
+        }
+        let level = reader.u8()?;
+        let flags = reader.u8()?;
+        let keep_alive = reader.u16()?;
+        skip_properties(&mut reader, level)?;
+        let client_id = reader.prefixed_bytes(2)?.to_vec();
+
+        Some(Connect {
+            level,
+            has_username: flags & 0x80 != 0,
+            has_password: flags & 0x40 != 0,
+            keep_alive,
+            client_id,
+        })
+    }
+}
+
+/// Return or reason code of a CONNACK packet
+pub fn connack_code(body: &[u8]) -> Option<u8> {
+    body.get(1).copied()
+}
+
+/// Check if the CONNACK code of the given protocol level refuses the
+/// credentials or the authorization of the client
+pub fn is_auth_failure(level: u8, code: u8) -> bool {
+    if level >= LEVEL_5 {
+        matches!(code, 0x86 | 0x87 | 0x8C)
+    } else {
+        matches!(code, 4 | 5)
+    }
+}
+
+/// Topic of a PUBLISH packet with the length of its application message,
+/// given the prefix and full length of the body
+pub fn publish_topic(body: &[u8], body_len: usize, qos: u8, level: u8) -> Option<(&[u8], usize)> {
+    let mut reader = ByteReader::new(body);
+    let topic = reader.prefixed_bytes(2)?;
+    if qos != 0 {
+        reader.u16()?;
+    }
+    skip_properties(&mut reader, level)?;
+    let header_len = reader.position();
+    Some((topic, body_len - header_len))
+}
+
+/// Topic filters of a SUBSCRIBE packet
+pub fn subscribe_topics(body: &[u8], level: u8) -> Option<Vec<&[u8]>> {
+    let mut reader = ByteReader::new(body);
+    reader.u16()?;
+    skip_properties(&mut reader, level)?;
+    let mut topics = Vec::new();
+    while !reader.is_empty() {
+        topics.push(reader.prefixed_bytes(2)?);
+        reader.u8()?;
+    }
+    Some(topics)
+}
+
+/// Fixed header of the packet being read
+#[derive(Debug, Clone, Copy)]
+struct FixedHeader {
+    packet_type: u8,
+    flags: u8,
+    body_len: usize,
+}
+
+/// Reader of the MQTT packets of a direction of a TCP stream
+#[derive(Debug, Clone, Default)]
+pub struct MqttStreamReader {
+    buffer: Vec<u8>,
+    /// Header of the packet whose body is being read
+    header: Option<FixedHeader>,
+    /// Body bytes left to skip after the buffered prefix
+    skip: usize,
+    /// Whether the stream is not MQTT, a packet was malformed or bytes were
+    /// missed, so no more packets can be read
+    done: bool,
+}
+
+impl MqttStreamReader {
+    /// Include the next bytes of the stream and call `consume` with the type,
+    /// flags, body prefix and body length of each packet they complete.
+    /// Reading stops when `consume` returns false
+    pub fn include<F: FnMut(u8, u8, &[u8], usize) -> bool>(
+        &mut self,
+        gap: bool,
+        data: &[u8],
+        mut consume: F,
+    ) {
+        if gap {
+            self.finish();
+        }
+        if self.done {
+            return;
+        }
+
+        let mut data = data;
+        while !self.done {
+            if let Some(header) = self.header {
+                let needed = header.body_len.min(MAX_BODY_PREFIX);
+                if self.buffer.len() == needed {
+                    if !consume(
+                        header.packet_type,
+                        header.flags,
+                        &self.buffer,
+                        header.body_len,
+                    ) {
+                        self.finish();
+                        break;
+                    }
+                    self.skip = header.body_len - needed;
+                    self.header = None;
+                    self.buffer.clear();
+                    continue;
+                }
+            }
+            if data.is_empty() {
+                break;
+            }
+
+            if self.skip != 0 {
+                let len = self.skip.min(data.len());
+                self.skip -= len;
+                data = &data[len..];
+            } else if let Some(header) = self.header {
+                let needed = header.body_len.min(MAX_BODY_PREFIX);
+                let len = (needed - self.buffer.len()).min(data.len());
+                self.buffer.extend_from_slice(&data[..len]);
+                data = &data[len..];
+            } else {
+                self.buffer.push(data[0]);
+                data = &data[1..];
+                self.read_header();
+            }
+        }
+    }
+
+    /// Read the fixed header from the buffer once its last byte is there
+    fn read_header(&mut self) {
+        // The remaining length takes up to 4 bytes, each one with a
+        // continuation bit
+        let last = self.buffer[self.buffer.len() - 1];
+        if self.buffer.len() == 1 || last & 0x80 != 0 {
+            if self.buffer.len() == 5 {
+                self.finish();
+            }
+            return;
+        }
+
+        let Some(body_len) = variable_int(&mut ByteReader::new(&self.buffer[1..])) else {
+            self.finish();
+            return;
+        };
+        let header = FixedHeader {
+            packet_type: self.buffer[0] >> 4,
+            flags: self.buffer[0] & 0x0F,
+            body_len,
+        };
+
+        if header.packet_type == 0 {
+            self.finish();
+            return;
+        }
+
+        self.buffer.clear();
+        self.header = Some(header);
+    }
+
+    fn finish(&mut self) {
+        self.done = true;
+        self.buffer = Vec::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connect() {
+        let body = b"\x00\x04MQTT\x04\xC2\x00\x3C\x00\x03abc\x00\x01u\x00\x01p";
+        let connect = Connect::parse(body).unwrap();
+        assert_eq!(connect.level, 4);
+        assert!(connect.has_username && connect.has_password);
+        assert_eq!(connect.keep_alive, 60);
+        assert_eq!(connect.client_id, b"abc");
+
+        assert!(Connect::parse(b"\x00\x04HTTP\x04").is_none());
+        assert!(is_auth_failure(4, 5));
+        assert!(!is_auth_failure(5, 5));
+    }
+
+    #[test]
+    fn test_stream_reader() {
+        let mut v = MqttStreamReader::default();
+        let mut packets = Vec::new();
+        let mut stream = vec![0x10, 0x0F];
+        stream.extend_from_slice(b"\x00\x04MQTT\x04\x02\x00\x3C\x00\x03abc");
+        stream.extend_from_slice(&[0x32, 0x07, 0x00, 0x01, b't', 0x00, 0x01, b'x', b'y']);
+        stream.extend_from_slice(&[0xC0, 0x00]);
+
+        for byte in &stream {
+            v.include(false, &[*byte], |t, f, _, len| {
+                packets.push((t, f, len));
+                true
+            });
+        }
+        assert_eq!(
+            packets,
+            vec![(CONNECT, 0, 15), (PUBLISH, 2, 7), (PINGREQ, 0, 0)]
+        );
+
+        let mut v = MqttStreamReader::default();
+        v.include(false, &[0x30, 0x00, 0x10], |_, _, _, _| false);
+        assert!(v.done);
+    }
+}
@@ -0,0 +1,110 @@
+use super::{
+    os_signatures::{OsLabel, SynFingerprint},
+    FlowStat, FlowTimes, OsSignatures, StatSettings,
+};
+use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
+use std::{
+    io::{BufWriter, Error, Write},
+    rc::Rc,
+};
+
+/// Operating systems of the client and server of TCP flows, guessed from
+/// their first SYN and SYN/ACK with the signatures given on the settings.
+/// Written as `unknown` when there is no match
+#[derive(Debug, Clone)]
+pub struct OsFingerprint {
+    signatures: Rc<OsSignatures>,
+    client_seen: bool,
+    server_seen: bool,
+    client: Option<Rc<OsLabel>>,
+    server: Option<Rc<OsLabel>>,
+}
+
+impl OsFingerprint {
+    fn include_packet(&mut self, sliced_packet: &etherparse::SlicedPacket) {
+        let Some(etherparse::TransportSlice::Tcp(tcp_slice)) = &sliced_packet.transport else {
+            return;
+        };
+        if !tcp_slice.syn() {
+            return;
+        }
+
+        let is_response = tcp_slice.ack();
+        let seen = if is_response {
+            &mut self.server_seen
+        } else {
+            &mut self.client_seen
+        };
+        if *seen {
+            return;
+        }
+        *seen = true;
+
+        let label = SynFingerprint::from_packet(sliced_packet, tcp_slice)
+            .and_then(|fingerprint| self.signatures.find(&fingerprint, is_response));
+        if is_response {
+            self.server = label;
+        } else {
+            self.client = label;
+        }
+    }
+
+    fn write_label_csv_value<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        label: Option<&Rc<OsLabel>>,
+    ) -> Result<(), Error> {
+        match label {
+            Some(label) => write!(writer, "{},{},", label.name(), label.flavor()),
+            None => write!(writer, "unknown,,"),
+        }
+    }
+}
+
+impl FlowStat for OsFingerprint {
+    fn from_packet(
+        settings: &StatSettings,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        let mut os_fingerprint = OsFingerprint {
+            signatures: settings.shared_os_signatures(),
+            client_seen: false,
+            server_seen: false,
+            client: None,
+            server: None,
+        };
+
+        os_fingerprint.include_packet(sliced_packet);
+        os_fingerprint
+    }
+    fn include(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        self.include_packet(sliced_packet);
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        write!(writer, "client_os,client_os_flavor,")?;
+        write!(writer, "server_os,server_os_flavor,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        Self::write_label_csv_value(writer, self.client.as_ref())?;
+        Self::write_label_csv_value(writer, self.server.as_ref())?;
+        Ok(())
+    }
+}
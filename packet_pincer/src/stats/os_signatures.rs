@@ -0,0 +1,504 @@
+use super::tcp_option_parse::{
+    TcpOptionIter, MAXIMUM_SEGMENT_SIZE, NO_OPERATION, SACK, SACK_PERMITTED, TIMESTAMPS,
+    WINDOW_SCALE,
+};
+use std::{error::Error, fs, path::PathBuf, rc::Rc};
+
+/// Signatures of the common operating systems, taken from p0f
+const BUILTIN_SIGNATURES: &str = include_str!("../../assets/os_signatures.fp");
+
+/// Maximum number of hops between the host and the capture point
+const MAX_DISTANCE: u8 = 35;
+
+/// Operating system a signature belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct OsLabel {
+    /// Whether the signature is generic, only used if nothing else matches
+    generic: bool,
+    name: String,
+    flavor: String,
+}
+
+impl OsLabel {
+    /// Name of the operating system family
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Versions of the operating system
+    pub fn flavor(&self) -> &str {
+        &self.flavor
+    }
+}
+
+/// Window size expected by a signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WindowSize {
+    Any,
+    Value(u16),
+    /// Multiple of the MSS
+    Mss(u32),
+    /// Multiple of the MTU
+    Mtu(u32),
+    /// Any multiple of the value
+    Modulo(u16),
+}
+
+/// Fields of a SYN or SYN/ACK packet compared against the signatures
+#[derive(Debug, Clone)]
+pub(crate) struct SynFingerprint {
+    version: u8,
+    ttl: u8,
+    ip_options_len: usize,
+    maximum_segment_size: Option<u16>,
+    window_size: u16,
+    window_scale: Option<u8>,
+    /// Option names in order, separated by commas
+    layout: String,
+    /// Sorted names of the unusual header values
+    quirks: Vec<&'static str>,
+    has_payload: bool,
+}
+
+impl SynFingerprint {
+    pub fn from_packet(
+        sliced_packet: &etherparse::SlicedPacket,
+        tcp_slice: &etherparse::TcpSlice,
+    ) -> Option<SynFingerprint> {
+        let mut quirks = Vec::new();
+        let (version, ttl, ip_options_len) = match &sliced_packet.net {
+            Some(etherparse::NetSlice::Ipv4(v)) => {
+                let header = v.header();
+                let identification = header.identification();
+                if header.dont_fragment() {
+                    quirks.push("df");
+                    if identification != 0 {
+                        quirks.push("id+");
+                    }
+                } else if identification == 0 {
+                    quirks.push("id-");
+                }
+                if header.slice()[1] & 0b11 != 0 {
+                    quirks.push("ecn");
+                }
+                if header.slice()[6] & 0x80 != 0 {
+                    quirks.push("0+");
+                }
+                (4, header.ttl(), header.options().len())
+            }
+            Some(etherparse::NetSlice::Ipv6(v)) => {
+                let header = v.header();
+                let slice = header.slice();
+                if header.traffic_class() & 0b11 != 0 {
+                    quirks.push("ecn");
+                }
+                if slice[1] & 0x0F != 0 || slice[2] != 0 || slice[3] != 0 {
+                    quirks.push("flow");
+                }
+                (6, header.hop_limit(), 0)
+            }
+            _ => return None,
+        };
+
+        if (tcp_slice.ece() || tcp_slice.cwr() || tcp_slice.ns()) && !quirks.contains(&"ecn") {
+            quirks.push("ecn");
+        }
+        if tcp_slice.sequence_number() == 0 {
+            quirks.push("seq-");
+        }
+        if tcp_slice.ack() && tcp_slice.acknowledgment_number() == 0 {
+            quirks.push("ack-");
+        }
+        if !tcp_slice.ack() && tcp_slice.acknowledgment_number() != 0 {
+            quirks.push("ack+");
+        }
+        if tcp_slice.urg() {
+            quirks.push("urgf+");
+        } else if tcp_slice.urgent_pointer() != 0 {
+            quirks.push("uptr+");
+        }
+        if tcp_slice.psh() {
+            quirks.push("pushf+");
+        }
+
+        let options = tcp_slice.options();
+        let mut fingerprint = SynFingerprint {
+            version,
+            ttl,
+            ip_options_len,
+            maximum_segment_size: None,
+            window_size: tcp_slice.window_size(),
+            window_scale: None,
+            layout: String::new(),
+            quirks,
+            has_payload: !tcp_slice.payload().is_empty(),
+        };
+        let mut names = Vec::new();
+        let mut consumed = 0;
+
+        let mut option_iter = TcpOptionIter::new(options);
+        for (kind, data) in option_iter.by_ref() {
+            match kind {
+                NO_OPERATION => {
+                    names.push("nop".to_string());
+                    consumed += 1;
+                    continue;
+                }
+                MAXIMUM_SEGMENT_SIZE if data.len() == 2 => {
+                    fingerprint.maximum_segment_size = Some(u16::from_be_bytes([data[0], data[1]]));
+                    names.push("mss".to_string());
+                }
+                WINDOW_SCALE if data.len() == 1 => {
+                    fingerprint.window_scale = Some(data[0]);
+                    if 14 < data[0] {
+                        fingerprint.quirks.push("exws");
+                    }
+                    names.push("ws".to_string());
+                }
+                SACK_PERMITTED => names.push("sok".to_string()),
+                SACK => names.push("sack".to_string()),
+                TIMESTAMPS if data.len() == 8 => {
+                    if data[..4] == [0; 4] {
+                        fingerprint.quirks.push("ts1-");
+                    }
+                    if data[4..] != [0; 4] && !tcp_slice.ack() {
+                        fingerprint.quirks.push("ts2+");
+                    }
+                    names.push("ts".to_string());
+                }
+                _ => names.push(format!("?{}", kind)),
+            }
+            consumed += 2 + data.len();
+        }
+        if let Some(padding) = option_iter.end_padding() {
+            names.push(format!("eol+{}", padding.len()));
+            if padding.iter().any(|b| *b != 0) {
+                fingerprint.quirks.push("opt+");
+            }
+            consumed += 1 + padding.len();
+        }
+        if consumed < options.len() {
+            fingerprint.quirks.push("bad");
+        }
+
+        fingerprint.layout = names.join(",");
+        fingerprint.quirks.sort_unstable();
+        Some(fingerprint)
+    }
+}
+
+/// A p0f signature of the SYN or SYN/ACK packets of an operating system
+#[derive(Debug, Clone)]
+struct OsSignature {
+    label: Rc<OsLabel>,
+    version: Option<u8>,
+    initial_ttl: u8,
+    ip_options_len: usize,
+    maximum_segment_size: Option<u16>,
+    window_size: WindowSize,
+    window_scale: Option<u8>,
+    layout: String,
+    /// Sorted names of the unusual header values
+    quirks: Vec<String>,
+    has_payload: Option<bool>,
+}
+
+fn parse_any<T: std::str::FromStr>(value: &str) -> Result<Option<T>, String> {
+    match value {
+        "*" => Ok(None),
+        _ => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("invalid value '{}'", value)),
+    }
+}
+
+impl OsSignature {
+    /// Parse the value of a `sig` line, with the format
+    /// `ver:ittl:olen:mss:wsize,scale:olayout:quirks:pclass`
+    fn parse(label: Rc<OsLabel>, value: &str) -> Result<OsSignature, String> {
+        let fields: Vec<&str> = value.split(':').map(str::trim).collect();
+        let [version, initial_ttl, ip_options_len, maximum_segment_size, window, layout, quirks, payload] =
+            fields[..]
+        else {
+            return Err("expected 8 fields separated by ':'".to_string());
+        };
+
+        let initial_ttl = initial_ttl.trim_end_matches(|c: char| !c.is_ascii_digit());
+        let (window_size, window_scale) = window
+            .split_once(',')
+            .ok_or_else(|| "expected the window size and scale separated by ','".to_string())?;
+        let window_size = if window_size == "*" {
+            WindowSize::Any
+        } else if let Some(multiplier) = window_size.strip_prefix("mss*") {
+            WindowSize::Mss(multiplier.parse().map_err(|_| "invalid window size")?)
+        } else if let Some(multiplier) = window_size.strip_prefix("mtu*") {
+            WindowSize::Mtu(multiplier.parse().map_err(|_| "invalid window size")?)
+        } else if let Some(divisor) = window_size.strip_prefix('%') {
+            WindowSize::Modulo(divisor.parse().map_err(|_| "invalid window size")?)
+        } else {
+            WindowSize::Value(window_size.parse().map_err(|_| "invalid window size")?)
+        };
+        let mut quirks: Vec<String> = quirks
+            .split(',')
+            .filter(|quirk| !quirk.is_empty())
+            .map(str::to_string)
+            .collect();
+        quirks.sort_unstable();
+
+        Ok(OsSignature {
+            label,
+            version: parse_any(version)?,
+            initial_ttl: initial_ttl
+                .parse()
+                .map_err(|_| format!("invalid initial TTL '{}'", initial_ttl))?,
+            ip_options_len: ip_options_len
+                .parse()
+                .map_err(|_| format!("invalid options length '{}'", ip_options_len))?,
+            maximum_segment_size: parse_any(maximum_segment_size)?,
+            window_size,
+            window_scale: parse_any(window_scale)?,
+            layout: layout.to_string(),
+            quirks,
+            has_payload: match payload {
+                "0" => Some(false),
+                "+" => Some(true),
+                "*" => None,
+                _ => return Err(format!("invalid payload class '{}'", payload)),
+            },
+        })
+    }
+
+    fn matches(&self, fingerprint: &SynFingerprint) -> bool {
+        let window_matches = match (self.window_size, fingerprint.maximum_segment_size) {
+            (WindowSize::Any, _) => true,
+            (WindowSize::Value(v), _) => fingerprint.window_size == v,
+            (WindowSize::Modulo(v), _) => fingerprint.window_size.is_multiple_of(v),
+            (WindowSize::Mss(n), Some(mss)) => {
+                u32::from(fingerprint.window_size) == u32::from(mss) * n
+            }
+            (WindowSize::Mtu(n), Some(mss)) => {
+                let header_len = if fingerprint.version == 4 { 40 } else { 60 };
+                u32::from(fingerprint.window_size) == (u32::from(mss) + header_len) * n
+            }
+            (_, None) => false,
+        };
+
+        self.version.is_none_or(|v| v == fingerprint.version)
+            && fingerprint.ttl <= self.initial_ttl
+            && self.initial_ttl - fingerprint.ttl <= MAX_DISTANCE
+            && self.ip_options_len == fingerprint.ip_options_len
+            && self
+                .maximum_segment_size
+                .is_none_or(|mss| Some(mss) == fingerprint.maximum_segment_size)
+            && window_matches
+            && self
+                .window_scale
+                .is_none_or(|scale| scale == fingerprint.window_scale.unwrap_or(0))
+            && self.layout == fingerprint.layout
+            && self
+                .quirks
+                .iter()
+                .map(String::as_str)
+                .eq(fingerprint.quirks.iter().copied())
+            && self
+                .has_payload
+                .is_none_or(|p| p == fingerprint.has_payload)
+    }
+}
+
+/// Database of p0f signatures of the SYN and SYN/ACK packets sent by each
+/// operating system
+#[derive(Debug, Clone, Default)]
+pub struct OsSignatures {
+    request: Vec<OsSignature>,
+    response: Vec<OsSignature>,
+}
+
+impl OsSignatures {
+    /// Create an empty database, which matches no packet
+    pub fn new() -> OsSignatures {
+        OsSignatures::default()
+    }
+
+    /// Create a database with the bundled signatures of
+    /// `assets/os_signatures.fp`
+    pub fn builtin() -> OsSignatures {
+        OsSignatures::parse(BUILTIN_SIGNATURES).expect("Built-in signatures are valid")
+    }
+
+    /// Try loading the signatures of a file in the p0f v3 format. Only the
+    /// `tcp:request` and `tcp:response` sections are used
+    pub fn from_file(file: PathBuf) -> Result<OsSignatures, Box<dyn Error>> {
+        let content = fs::read_to_string(file)?;
+        OsSignatures::parse(&content)
+    }
+
+    /// Try parsing signatures in the p0f v3 format
+    pub fn parse(content: &str) -> Result<OsSignatures, Box<dyn Error>> {
+        let mut signatures = OsSignatures::new();
+        let mut section = None;
+        let mut label = None;
+
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = match name {
+                    "tcp:request" => Some(false),
+                    "tcp:response" => Some(true),
+                    _ => None,
+                };
+                label = None;
+                continue;
+            }
+            let Some(is_response) = section else {
+                continue;
+            };
+
+            let error =
+                |message: &str| format!("Invalid OS signature on line {}: {}", i + 1, message);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected 'key = value'"))?;
+            let value = value.trim();
+            match key.trim() {
+                "label" => {
+                    let fields: Vec<&str> = value.splitn(4, ':').collect();
+                    let [kind, _class, name, flavor] = fields[..] else {
+                        return Err(error("expected 'type:class:name:flavor'").into());
+                    };
+                    if value.contains([',', '"', '\n']) {
+                        return Err(error("labels cannot contain commas or quotes").into());
+                    }
+                    label = Some(Rc::new(OsLabel {
+                        generic: kind == "g",
+                        name: name.to_string(),
+                        flavor: flavor.to_string(),
+                    }));
+                }
+                "sig" => {
+                    let label = label
+                        .clone()
+                        .ok_or_else(|| error("signature without label"))?;
+                    let signature = OsSignature::parse(label, value).map_err(|e| error(&e))?;
+                    if is_response {
+                        signatures.response.push(signature);
+                    } else {
+                        signatures.request.push(signature);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(signatures)
+    }
+
+    /// Find the operating system of a SYN, or of a SYN/ACK if `is_response`.
+    /// Generic signatures are only used if no specific one matches
+    pub(crate) fn find(
+        &self,
+        fingerprint: &SynFingerprint,
+        is_response: bool,
+    ) -> Option<Rc<OsLabel>> {
+        let signatures = if is_response {
+            &self.response
+        } else {
+            &self.request
+        };
+        let mut matching = signatures.iter().filter(|s| s.matches(fingerprint));
+
+        let first = matching.next()?;
+        if first.label.generic {
+            if let Some(specific) = matching.find(|s| !s.label.generic) {
+                return Some(Rc::clone(&specific.label));
+            }
+        }
+        Some(Rc::clone(&first.label))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNATURES: &str = "
+        [tcp:request]
+        ; Comments and unknown keys are ignored
+        classes = win,unix,other
+
+        label = g:unix:Linux:
+        sig   = *:64:0:*:*,*:mss,sok,ts,nop,ws:df,id+:0
+
+        label = s:unix:Linux:3.11 and newer
+        sig   = *:64:0:*:mss*20,10:mss,sok,ts,nop,ws:df,id+:0
+
+        label = s:!:NMap:SYN scan
+        sig   = *:64-:0:1460:1024,0:mss::0
+
+        [tcp:response]
+        label = s:win:Windows:7 or 8
+        sig   = 4:128:0:*:8192,8:mss,nop,ws,sok,ts:df,id+:0
+    ";
+
+    fn fingerprint(
+        ttl: u8,
+        window_size: u16,
+        layout: &str,
+        quirks: Vec<&'static str>,
+    ) -> SynFingerprint {
+        SynFingerprint {
+            version: 4,
+            ttl,
+            ip_options_len: 0,
+            maximum_segment_size: Some(1460),
+            window_size,
+            window_scale: Some(10),
+            layout: layout.to_string(),
+            quirks,
+            has_payload: false,
+        }
+    }
+
+    #[test]
+    fn test_signature_matching() {
+        let v = OsSignatures::parse(SIGNATURES).unwrap();
+
+        let linux = fingerprint(52, 29200, "mss,sok,ts,nop,ws", vec!["df", "id+"]);
+        let label = v.find(&linux, false).unwrap();
+        assert_eq!((label.name(), label.flavor()), ("Linux", "3.11 and newer"));
+
+        let linux = fingerprint(52, 65535, "mss,sok,ts,nop,ws", vec!["df", "id+"]);
+        assert_eq!(v.find(&linux, false).unwrap().flavor(), "");
+        assert!(v.find(&linux, true).is_none());
+
+        let mut nmap = fingerprint(41, 1024, "mss", vec![]);
+        nmap.window_scale = None;
+        assert_eq!(v.find(&nmap, false).unwrap().name(), "NMap");
+
+        // Too far from the initial TTL
+        nmap.ttl = 20;
+        assert!(v.find(&nmap, false).is_none());
+    }
+
+    #[test]
+    fn test_invalid_signatures() {
+        assert!(OsSignatures::parse("[tcp:request]\nsig = *:64:0:*:*,*:mss::0").is_err());
+        assert!(
+            OsSignatures::parse("[tcp:request]\nlabel = s:unix:Linux:x\nsig = *:64:0").is_err()
+        );
+        assert!(OsSignatures::parse("[mtu]\nlabel = Ethernet\nsig = 1500").is_ok());
+    }
+
+    #[test]
+    fn test_example_signatures() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/os_signatures.fp");
+        let v = OsSignatures::from_file(path.into()).unwrap();
+
+        assert!(!v.request.is_empty());
+        assert!(!v.response.is_empty());
+        assert_eq!(OsSignatures::builtin().request.len(), v.request.len());
+    }
+}
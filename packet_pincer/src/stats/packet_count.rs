@@ -1,18 +1,17 @@
-use std::{
-    io::{BufWriter, Error, Write},
-    net::IpAddr,
-};
+use std::io::{BufWriter, Error, Write};
 
 use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
 
-use super::{FlowStat, FlowTimes};
+use super::{FlowStat, FlowTimes, StatSettings};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PacketCount {
     forward_count: u32,
     backward_count: u32,
 }
 
+/// Get the number of packets received to obtain the given packet, which is
+/// more than one if the packet was reassembled from fragments
 pub fn extract_packet_count(reasembly_information: Option<&FragmentReasemblyInformation>) -> u32 {
     match reasembly_information {
         Some(reasembly_information) => reasembly_information.total_fragments_received_count,
@@ -22,6 +21,7 @@ pub fn extract_packet_count(reasembly_information: Option<&FragmentReasemblyInfo
 
 impl FlowStat for PacketCount {
     fn from_packet(
+        _settings: &StatSettings,
         _identifier: &TransportFlowIdentifier,
         _flow_times: &FlowTimes,
         _packet_header: &pcap::PacketHeader,
@@ -43,15 +43,9 @@ impl FlowStat for PacketCount {
     ) {
         let count = extract_packet_count(reasembly_information);
 
-        let src_ip = match &sliced_packet.net {
-            Some(header) => match header {
-                etherparse::NetSlice::Ipv4(v) => IpAddr::V4(v.header().source_addr()),
-                etherparse::NetSlice::Ipv6(v) => IpAddr::V6(v.header().source_addr()),
-            },
-            None => panic!("Unexpected sliced packet without net layer"),
-        };
+        let is_forward = identifier.is_forward(sliced_packet);
 
-        if src_ip == identifier.source_ip {
+        if is_forward {
             self.forward_count += count;
         } else {
             self.backward_count += count;
@@ -59,6 +53,7 @@ impl FlowStat for PacketCount {
     }
     fn write_csv_header<T: ?Sized + std::io::Write>(
         writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
     ) -> Result<(), Error> {
         write!(writer, "bidirectional_packet_count,")?;
         write!(writer, "forward_packet_count,")?;
@@ -95,3 +90,22 @@ impl FlowStat for PacketCount {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::test_flow::{TestFlow, ACK, SYN};
+
+    #[test]
+    fn test_packets_per_second() {
+        let mut v = TestFlow::<PacketCount>::new(StatSettings::default(), 40000, 80);
+        v.tcp(true, 0, SYN, 100, 0, b"")
+            .tcp(false, 250_000, SYN | ACK, 500, 101, b"")
+            .tcp(true, 500_000, ACK, 101, 501, b"");
+
+        assert_eq!(v.value("bidirectional_packet_count"), "3");
+        assert_eq!(v.value("bidirectional_packet_second"), "6.000000000");
+        assert_eq!(v.value("forward_packet_second"), "4.000000000");
+        assert_eq!(v.value("backward_packet_second"), "2.000000000");
+    }
+}
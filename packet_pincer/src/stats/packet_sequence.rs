@@ -0,0 +1,177 @@
+use super::{extract_byte_count, FlowStat, FlowTimes, StatSettings};
+use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
+use chrono::{DateTime, Utc};
+use std::io::{BufWriter, Error, Write};
+
+/// The sequence of the first packets of the flow. The sizes are signed with
+/// the direction of the packet (positive on forward and negative on backward)
+#[derive(Debug, Clone)]
+pub struct PacketSequence {
+    /// Maximum number of packets stored in the sequence
+    length: usize,
+    last_time: DateTime<Utc>,
+    signed_bytes: Vec<i64>,
+    interarrival_microseconds: Vec<u64>,
+    tcp_flags: Vec<u8>,
+}
+
+/// Get the TCP flags of the packet in the same order as they are found in the
+/// header, or 0 if it is not a TCP packet
+fn extract_tcp_flags(sliced_packet: &etherparse::SlicedPacket) -> u8 {
+    match &sliced_packet.transport {
+        Some(etherparse::TransportSlice::Tcp(s)) => [
+            s.cwr(),
+            s.ece(),
+            s.urg(),
+            s.ack(),
+            s.psh(),
+            s.rst(),
+            s.syn(),
+            s.fin(),
+        ]
+        .into_iter()
+        .fold(0, |flags, flag| (flags << 1) | u8::from(flag)),
+        _ => 0,
+    }
+}
+
+impl FlowStat for PacketSequence {
+    fn from_packet(
+        settings: &StatSettings,
+        _identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        let byte_count = extract_byte_count(packet_header, reasembly_information);
+
+        let length = settings.sequence_length();
+        let mut signed_bytes = Vec::with_capacity(length);
+        let mut interarrival_microseconds = Vec::with_capacity(length);
+        let mut tcp_flags = Vec::with_capacity(length);
+
+        if length != 0 {
+            signed_bytes.push(i64::try_from(byte_count).unwrap());
+            interarrival_microseconds.push(0);
+            tcp_flags.push(extract_tcp_flags(sliced_packet));
+        }
+
+        PacketSequence {
+            length,
+            last_time: flow_times.last_packet_time,
+            signed_bytes,
+            interarrival_microseconds,
+            tcp_flags,
+        }
+    }
+    fn include(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        if self.length <= self.signed_bytes.len() {
+            return;
+        }
+
+        let is_forward = identifier.is_forward(sliced_packet);
+        let byte_count =
+            i64::try_from(extract_byte_count(packet_header, reasembly_information)).unwrap();
+        let time = flow_times.last_packet_time;
+        let increment: u64 = (time - self.last_time)
+            .num_microseconds()
+            .expect("IAT increments microseconds should fit in a i64")
+            .try_into()
+            .expect("IAT increments microseconds should convert to u64");
+
+        if is_forward {
+            self.signed_bytes.push(byte_count);
+        } else {
+            self.signed_bytes.push(-byte_count);
+        }
+        self.interarrival_microseconds.push(increment);
+        self.tcp_flags.push(extract_tcp_flags(sliced_packet));
+        self.last_time = time;
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        settings: &StatSettings,
+    ) -> Result<(), Error> {
+        let length = settings.sequence_length();
+        for i in 0..length {
+            write!(writer, "sequence_signed_packet_bytes_{},", i)?;
+        }
+        for i in 0..length {
+            write!(writer, "sequence_inter_arrival_time_{},", i)?;
+        }
+        for i in 0..length {
+            write!(writer, "sequence_tcp_flags_{},", i)?;
+        }
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        for i in 0..self.length {
+            write!(writer, "{},", self.signed_bytes.get(i).unwrap_or(&0))?;
+        }
+        for i in 0..self.length {
+            match self.interarrival_microseconds.get(i) {
+                Some(iat) => write!(writer, "{:.9},", (*iat as f64) / 1_000_000.0)?,
+                None => write!(writer, "{},", 0)?,
+            }
+        }
+        for i in 0..self.length {
+            write!(writer, "{},", self.tcp_flags.get(i).unwrap_or(&0))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::test_flow::{TestFlow, ACK, PSH, SYN};
+
+    #[test]
+    fn test_sequence() {
+        let mut v = TestFlow::<PacketSequence>::new(
+            StatSettings::default().with_sequence_length(4),
+            40000,
+            80,
+        );
+        v.tcp(true, 0, SYN, 100, 0, b"")
+            .tcp(false, 1500, SYN | ACK, 500, 101, b"")
+            .tcp(true, 2000, PSH | ACK, 101, 501, b"hello");
+
+        let columns = v.columns();
+        assert_eq!(columns.len(), 12);
+        let values: Vec<&str> = columns.iter().map(|(_, value)| value.as_str()).collect();
+        // Ethernet, IPv4 and TCP headers of 54 bytes
+        assert_eq!(values[..4], ["54", "-54", "59", "0"]);
+        assert_eq!(
+            values[4..8],
+            ["0.000000000", "0.001500000", "0.000500000", "0"]
+        );
+        assert_eq!(values[8..], ["2", "18", "24", "0"]);
+    }
+
+    #[test]
+    fn test_header_length() {
+        let header = |length| {
+            let mut writer = BufWriter::new(Vec::new());
+            let settings = StatSettings::default().with_sequence_length(length);
+            PacketSequence::write_csv_header(&mut writer, &settings).unwrap();
+            String::from_utf8(writer.into_inner().unwrap()).unwrap()
+        };
+
+        assert_eq!(header(0), "");
+        assert_eq!(header(20).matches("sequence_tcp_flags_").count(), 20);
+        assert!(header(2).ends_with("sequence_tcp_flags_0,sequence_tcp_flags_1,"));
+    }
+}
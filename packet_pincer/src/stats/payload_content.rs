@@ -0,0 +1,285 @@
+use super::{FlowStat, FlowTimes, StatSettings};
+use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
+use std::io::{BufWriter, Error, Write};
+
+/// Accomulated distribution of the values of a sequence of bytes
+#[derive(Debug, Clone)]
+pub struct ByteDistribution {
+    histogram: [u64; 256],
+    count: u64,
+    printable_count: u64,
+}
+
+impl ByteDistribution {
+    /// Number of most frequent bytes reported
+    pub const TOP_BYTES: usize = 3;
+
+    pub fn new() -> ByteDistribution {
+        ByteDistribution {
+            histogram: [0; 256],
+            count: 0,
+            printable_count: 0,
+        }
+    }
+
+    pub fn include(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.histogram[usize::from(*byte)] += 1;
+            if byte.is_ascii_graphic() || byte.is_ascii_whitespace() {
+                self.printable_count += 1;
+            }
+        }
+        self.count += bytes.len() as u64;
+    }
+
+    /// Shannon entropy in bits per byte, between 0 and 8
+    pub fn entropy(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+
+        let total = self.count as f64;
+        self.histogram
+            .iter()
+            .filter(|count| **count != 0)
+            .map(|count| {
+                let probability = *count as f64 / total;
+                -probability * probability.log2()
+            })
+            .sum()
+    }
+
+    /// Ratio of the bytes that are printable ASCII characters
+    pub fn printable_ratio(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.printable_count as f64 / self.count as f64
+        }
+    }
+
+    /// Pearson's chi-square statistic of the histogram against an uniform
+    /// distribution of the byte values
+    pub fn chi_square(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+
+        let expected = self.count as f64 / 256.0;
+        self.histogram
+            .iter()
+            .map(|count| (*count as f64 - expected).powi(2) / expected)
+            .sum()
+    }
+
+    /// The most frequent byte values with the ratio of bytes they account for,
+    /// from most to least frequent. Ties are resolved with the lowest value
+    pub fn top_bytes(&self) -> Vec<(u8, f64)> {
+        let mut values: Vec<u8> = (0..=u8::MAX).collect();
+        values.sort_by_key(|value| std::cmp::Reverse(self.histogram[usize::from(*value)]));
+
+        values
+            .into_iter()
+            .take(Self::TOP_BYTES)
+            .filter(|value| self.histogram[usize::from(*value)] != 0)
+            .map(|value| {
+                (
+                    value,
+                    self.histogram[usize::from(value)] as f64 / self.count as f64,
+                )
+            })
+            .collect()
+    }
+}
+
+/// Distribution of the contents of the transport payloads on each direction
+#[derive(Debug, Clone)]
+pub struct PayloadContent {
+    forward_bytes: ByteDistribution,
+    backward_bytes: ByteDistribution,
+    forward_packet_count: u32,
+    backward_packet_count: u32,
+    forward_empty_payload_count: u32,
+    backward_empty_payload_count: u32,
+}
+
+fn extract_payload<'a>(sliced_packet: &etherparse::SlicedPacket<'a>) -> Option<&'a [u8]> {
+    match &sliced_packet.transport {
+        Some(etherparse::TransportSlice::Icmpv4(s)) => Some(s.payload()),
+        Some(etherparse::TransportSlice::Icmpv6(s)) => Some(s.payload()),
+        Some(etherparse::TransportSlice::Udp(s)) => Some(s.payload()),
+        Some(etherparse::TransportSlice::Tcp(s)) => Some(s.payload()),
+        None => None,
+    }
+}
+
+impl PayloadContent {
+    fn include_payload(&mut self, is_forward: bool, payload: &[u8]) {
+        let (bytes, packet_count, empty_payload_count) = if is_forward {
+            (
+                &mut self.forward_bytes,
+                &mut self.forward_packet_count,
+                &mut self.forward_empty_payload_count,
+            )
+        } else {
+            (
+                &mut self.backward_bytes,
+                &mut self.backward_packet_count,
+                &mut self.backward_empty_payload_count,
+            )
+        };
+
+        bytes.include(payload);
+        *packet_count += 1;
+        if payload.is_empty() {
+            *empty_payload_count += 1;
+        }
+    }
+
+    fn write_direction_csv_value<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        bytes: &ByteDistribution,
+        packet_count: u32,
+        empty_payload_count: u32,
+    ) -> Result<(), Error> {
+        write!(writer, "{:.9},", bytes.entropy())?;
+        write!(writer, "{:.9},", bytes.printable_ratio())?;
+        write!(writer, "{:.9},", bytes.chi_square())?;
+
+        let top_bytes = bytes.top_bytes();
+        for i in 0..ByteDistribution::TOP_BYTES {
+            match top_bytes.get(i) {
+                Some((value, ratio)) => write!(writer, "{},{:.9},", value, ratio)?,
+                None => write!(writer, "{},{},", 0, 0)?,
+            }
+        }
+
+        if packet_count == 0 {
+            write!(writer, "{},", 0)?;
+        } else {
+            write!(
+                writer,
+                "{:.9},",
+                f64::from(empty_payload_count) / f64::from(packet_count)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FlowStat for PayloadContent {
+    fn from_packet(
+        _settings: &StatSettings,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        let mut payload_content = PayloadContent {
+            forward_bytes: ByteDistribution::new(),
+            backward_bytes: ByteDistribution::new(),
+            forward_packet_count: 0,
+            backward_packet_count: 0,
+            forward_empty_payload_count: 0,
+            backward_empty_payload_count: 0,
+        };
+
+        if let Some(payload) = extract_payload(sliced_packet) {
+            payload_content.include_payload(true, payload);
+        }
+
+        payload_content
+    }
+    fn include(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        let is_forward = identifier.is_forward(sliced_packet);
+
+        if let Some(payload) = extract_payload(sliced_packet) {
+            self.include_payload(is_forward, payload);
+        }
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        for direction in ["forward", "backward"] {
+            write!(writer, "{}_payload_entropy,", direction)?;
+            write!(writer, "{}_payload_printable_ratio,", direction)?;
+            write!(writer, "{}_payload_chi_square,", direction)?;
+            for i in 1..=ByteDistribution::TOP_BYTES {
+                write!(writer, "{}_payload_top_byte_{},", direction, i)?;
+                write!(writer, "{}_payload_top_byte_{}_ratio,", direction, i)?;
+            }
+            write!(writer, "{}_zero_length_payload_ratio,", direction)?;
+        }
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        Self::write_direction_csv_value(
+            writer,
+            &self.forward_bytes,
+            self.forward_packet_count,
+            self.forward_empty_payload_count,
+        )?;
+        Self::write_direction_csv_value(
+            writer,
+            &self.backward_bytes,
+            self.backward_packet_count,
+            self.backward_empty_payload_count,
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_distribution() {
+        let mut v = ByteDistribution::new();
+
+        assert_eq!(v.entropy(), 0.0);
+        assert_eq!(v.printable_ratio(), 0.0);
+        assert_eq!(v.chi_square(), 0.0);
+        assert!(v.top_bytes().is_empty());
+
+        v.include(b"aaaa");
+
+        assert_eq!(v.entropy(), 0.0);
+        assert_eq!(v.printable_ratio(), 1.0);
+        assert_eq!(v.chi_square(), 4.0 * 255.0);
+        assert_eq!(v.top_bytes(), vec![(b'a', 1.0)]);
+
+        v.include(&[0, 0, 1, 2]);
+
+        assert_eq!(v.entropy(), 1.75);
+        assert_eq!(v.printable_ratio(), 0.5);
+        assert_eq!(v.top_bytes(), vec![(b'a', 0.5), (0, 0.25), (1, 0.125)]);
+    }
+
+    #[test]
+    fn test_byte_distribution_uniform() {
+        let mut v = ByteDistribution::new();
+        let bytes: Vec<u8> = (0..=u8::MAX).collect();
+
+        v.include(&bytes);
+        v.include(&bytes);
+
+        assert_eq!(v.entropy(), 8.0);
+        assert_eq!(v.chi_square(), 0.0);
+        assert_eq!(v.top_bytes().len(), ByteDistribution::TOP_BYTES);
+    }
+}
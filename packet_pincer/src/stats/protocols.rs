@@ -1,8 +1,8 @@
-use super::{FlowStat, FlowTimes};
+use super::{FlowStat, FlowTimes, StatSettings};
 use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
 use std::io::{BufWriter, Error, Write};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Protocols {
     has_tcp: bool,
     has_udp: bool,
@@ -42,6 +42,7 @@ fn evaluate(sliced_packet: &etherparse::SlicedPacket) -> PacketEval {
 
 impl FlowStat for Protocols {
     fn from_packet(
+        _settings: &StatSettings,
         _identifier: &TransportFlowIdentifier,
         _flow_times: &FlowTimes,
         _packet_header: &pcap::PacketHeader,
@@ -70,6 +71,7 @@ impl FlowStat for Protocols {
     }
     fn write_csv_header<T: ?Sized + std::io::Write>(
         writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
     ) -> Result<(), Error> {
         write!(writer, "has_tcp,")?;
         write!(writer, "has_udp,")?;
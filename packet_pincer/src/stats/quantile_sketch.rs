@@ -0,0 +1,196 @@
+/// Quantile sketch with bounded memory based on DDSketch (Masson, Rim and Lee,
+/// VLDB 2019). Each positive value `x` is counted on the bucket
+/// `i = ceil(log_gamma(x))`, with `gamma = (1 + a) / (1 - a)`, so that any
+/// quantile is estimated with a relative error of at most `a`. When there are
+/// more than `MAX_BUCKETS` buckets, the lowest ones are collapsed together,
+/// which only loses accuracy on the lowest quantiles
+#[derive(Debug, Clone)]
+pub struct QuantileSketch {
+    /// Count of each bucket, starting at `first_index`
+    buckets: Vec<u64>,
+    first_index: i32,
+    zero_count: u64,
+    count: u64,
+}
+
+impl QuantileSketch {
+    /// Relative accuracy of the estimates
+    const RELATIVE_ACCURACY: f64 = 0.01;
+    /// Maximum number of buckets kept, enough to cover values from 1 to more
+    /// than 10^17 without collapsing
+    const MAX_BUCKETS: usize = 2048;
+
+    pub fn new() -> QuantileSketch {
+        QuantileSketch {
+            buckets: Vec::new(),
+            first_index: 0,
+            zero_count: 0,
+            count: 0,
+        }
+    }
+
+    fn gamma() -> f64 {
+        (1.0 + Self::RELATIVE_ACCURACY) / (1.0 - Self::RELATIVE_ACCURACY)
+    }
+
+    fn bucket_index(value: u64) -> i32 {
+        ((value as f64).ln() / Self::gamma().ln()).ceil() as i32
+    }
+
+    fn bucket_value(index: i32) -> f64 {
+        let gamma = Self::gamma();
+        2.0 * gamma.powi(index) / (gamma + 1.0)
+    }
+
+    pub fn include(&mut self, value: u64) {
+        self.count += 1;
+        if value == 0 {
+            self.zero_count += 1;
+            return;
+        }
+
+        let index = Self::bucket_index(value);
+        if self.buckets.is_empty() {
+            self.first_index = index;
+            self.buckets.push(0);
+        } else if index < self.first_index {
+            let missing = usize::try_from(self.first_index - index).unwrap();
+            self.buckets.splice(0..0, std::iter::repeat_n(0, missing));
+            self.first_index = index;
+        } else {
+            let position = usize::try_from(index - self.first_index).unwrap();
+            if self.buckets.len() <= position {
+                self.buckets.resize(position + 1, 0);
+            }
+        }
+
+        let position = usize::try_from(index - self.first_index).unwrap();
+        self.buckets[position] += 1;
+        self.collapse();
+    }
+
+    /// Combine the values of another sketch into this one
+    pub fn merge(&mut self, other: &QuantileSketch) {
+        self.zero_count += other.zero_count;
+        self.count += other.count;
+        if other.buckets.is_empty() {
+            return;
+        }
+        if self.buckets.is_empty() {
+            self.buckets = other.buckets.clone();
+            self.first_index = other.first_index;
+            return;
+        }
+
+        let first_index = std::cmp::min(self.first_index, other.first_index);
+        let last_index = std::cmp::max(
+            self.first_index + i32::try_from(self.buckets.len()).unwrap(),
+            other.first_index + i32::try_from(other.buckets.len()).unwrap(),
+        );
+        let mut buckets = vec![0; usize::try_from(last_index - first_index).unwrap()];
+        for sketch in [&*self, other] {
+            let offset = usize::try_from(sketch.first_index - first_index).unwrap();
+            for (position, bucket_count) in sketch.buckets.iter().enumerate() {
+                buckets[offset + position] += bucket_count;
+            }
+        }
+
+        self.buckets = buckets;
+        self.first_index = first_index;
+        self.collapse();
+    }
+
+    /// Collapse the lowest buckets until there are no more than `MAX_BUCKETS`
+    fn collapse(&mut self) {
+        if self.buckets.len() <= Self::MAX_BUCKETS {
+            return;
+        }
+
+        let excess = self.buckets.len() - Self::MAX_BUCKETS;
+        let collapsed: u64 = self.buckets.drain(..excess).sum();
+        self.buckets[0] += collapsed;
+        self.first_index += i32::try_from(excess).unwrap();
+    }
+
+    /// Estimate the value at the given quantile, between 0 and 1, or `None`
+    /// if no value was included
+    pub fn quantile(&self, quantile: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let rank = (quantile.clamp(0.0, 1.0) * (self.count - 1) as f64).floor() as u64;
+        if rank < self.zero_count {
+            return Some(0.0);
+        }
+
+        let mut accumulated = self.zero_count;
+        for (position, bucket_count) in self.buckets.iter().enumerate() {
+            accumulated += bucket_count;
+            if rank < accumulated {
+                let index = self.first_index + i32::try_from(position).unwrap();
+                return Some(Self::bucket_value(index));
+            }
+        }
+
+        unreachable!("The rank is always lower than the count")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantile_accuracy() {
+        let mut v = QuantileSketch::new();
+
+        assert_eq!(v.quantile(0.5), None);
+
+        for value in 0..=1000 {
+            v.include(value);
+        }
+
+        for (quantile, expected) in [(0.25, 250.0), (0.5, 500.0), (0.95, 950.0), (1.0, 1000.0)] {
+            let estimate = v.quantile(quantile).unwrap();
+            assert!((estimate - expected).abs() <= expected * QuantileSketch::RELATIVE_ACCURACY);
+        }
+        assert_eq!(v.quantile(0.0), Some(0.0));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut sequential = QuantileSketch::new();
+        let mut first = QuantileSketch::new();
+        let mut second = QuantileSketch::new();
+
+        for value in 0..=1000 {
+            sequential.include(value * 7);
+            if value % 3 == 0 {
+                first.include(value * 7);
+            } else {
+                second.include(value * 7);
+            }
+        }
+        first.merge(&second);
+
+        for quantile in [0.0, 0.25, 0.5, 0.95, 1.0] {
+            assert_eq!(first.quantile(quantile), sequential.quantile(quantile));
+        }
+    }
+
+    #[test]
+    fn test_bounded_buckets() {
+        let mut v = QuantileSketch::new();
+
+        for exponent in 0..64 {
+            v.include(1 << exponent);
+            v.include(u64::MAX >> exponent);
+        }
+
+        assert!(v.buckets.len() <= QuantileSketch::MAX_BUCKETS);
+        let estimate = v.quantile(1.0).unwrap();
+        let expected = u64::MAX as f64;
+        assert!((estimate - expected).abs() <= expected * QuantileSketch::RELATIVE_ACCURACY);
+    }
+}
@@ -0,0 +1,143 @@
+use super::{reflection_message::Service, FlowStat, FlowTimes, StatSettings};
+use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
+use std::io::{BufWriter, Error, Write};
+
+/// Requests and responses of UDP flows on the ports of services abused for
+/// reflection and amplification attacks: the service, the command of the
+/// first request and response, like NTP monlist, SNMP GetBulk or Memcached
+/// stats, and the amplification factor, which is the ratio between the
+/// response and request payload bytes
+#[derive(Debug, Clone)]
+pub struct Reflection {
+    service: Option<Service>,
+    /// Whether the requests are sent on the forward direction
+    client_is_forward: bool,
+
+    request_count: u32,
+    response_count: u32,
+    request_bytes: u64,
+    response_bytes: u64,
+    request_command: Option<String>,
+    response_command: Option<String>,
+}
+
+impl Reflection {
+    fn include_packet(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        sliced_packet: &etherparse::SlicedPacket,
+    ) {
+        let Some(service) = self.service else {
+            return;
+        };
+        let Some(etherparse::TransportSlice::Udp(udp_slice)) = &sliced_packet.transport else {
+            return;
+        };
+
+        let payload = udp_slice.payload();
+        if identifier.is_forward(sliced_packet) == self.client_is_forward {
+            self.request_count += 1;
+            self.request_bytes += payload.len() as u64;
+            if self.request_command.is_none() {
+                self.request_command = service.command(payload);
+            }
+        } else {
+            self.response_count += 1;
+            self.response_bytes += payload.len() as u64;
+            if self.response_command.is_none() {
+                self.response_command = service.command(payload);
+            }
+        }
+    }
+}
+
+impl FlowStat for Reflection {
+    fn from_packet(
+        _settings: &StatSettings,
+        identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        // The services are only abused over UDP
+        let is_udp = matches!(
+            sliced_packet.transport,
+            Some(etherparse::TransportSlice::Udp(_))
+        );
+        let service = Service::from_port(identifier.dest_port())
+            .or_else(|| Service::from_port(identifier.source_port()))
+            .filter(|_| is_udp);
+        let mut reflection = Reflection {
+            service,
+            client_is_forward: service
+                .is_some_and(|service| identifier.dest_port() == service.port()),
+            request_count: 0,
+            response_count: 0,
+            request_bytes: 0,
+            response_bytes: 0,
+            request_command: None,
+            response_command: None,
+        };
+
+        reflection.include_packet(identifier, sliced_packet);
+        reflection
+    }
+    fn include(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        self.include_packet(identifier, sliced_packet);
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        write!(writer, "reflection_service,")?;
+        write!(
+            writer,
+            "reflection_request_count,reflection_response_count,"
+        )?;
+        write!(
+            writer,
+            "reflection_request_bytes,reflection_response_bytes,"
+        )?;
+        write!(writer, "reflection_amplification_factor,")?;
+        write!(writer, "reflection_request_command,")?;
+        write!(writer, "reflection_response_command,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        match self.service {
+            Some(service) => write!(writer, "{},", service.name())?,
+            None => write!(writer, ",")?,
+        }
+        write!(writer, "{},{},", self.request_count, self.response_count)?;
+        write!(writer, "{},{},", self.request_bytes, self.response_bytes)?;
+        let amplification_factor = if self.request_bytes == 0 {
+            0.0
+        } else {
+            self.response_bytes as f64 / self.request_bytes as f64
+        };
+        write!(writer, "{:.9},", amplification_factor)?;
+        write!(
+            writer,
+            "{},",
+            self.request_command.as_deref().unwrap_or_default()
+        )?;
+        write!(
+            writer,
+            "{},",
+            self.response_command.as_deref().unwrap_or_default()
+        )?;
+        Ok(())
+    }
+}
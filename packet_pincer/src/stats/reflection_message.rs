@@ -0,0 +1,294 @@
+/// UDP services abused for reflection and amplification attacks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Service {
+    CharGen,
+    Ntp,
+    NetBios,
+    Snmp,
+    Ldap,
+    Mssql,
+    Ssdp,
+    Memcached,
+}
+
+/// Services with the port they are served on
+const SERVICE_PORTS: [(Service, u16); 8] = [
+    (Service::CharGen, 19),
+    (Service::Ntp, 123),
+    (Service::NetBios, 137),
+    (Service::Snmp, 161),
+    (Service::Ldap, 389),
+    (Service::Mssql, 1434),
+    (Service::Ssdp, 1900),
+    (Service::Memcached, 11211),
+];
+
+/// Largest command read from the text protocols
+const MAX_COMMAND_LEN: usize = 16;
+
+/// Names of the LDAP protocol operations, by their application tag number
+const LDAP_OPERATIONS: [(u8, &str); 9] = [
+    (0, "bind_request"),
+    (1, "bind_response"),
+    (2, "unbind_request"),
+    (3, "search_request"),
+    (4, "search_result_entry"),
+    (5, "search_result_done"),
+    (19, "search_result_reference"),
+    (23, "extended_request"),
+    (24, "extended_response"),
+];
+
+/// Names of the SNMP PDUs, in the order of their context tag numbers
+const SNMP_PDUS: [&str; 9] = [
+    "get", "get_next", "response", "set", "trap", "get_bulk", "inform", "trap_v2", "report",
+];
+
+impl Service {
+    /// Service served on the given port
+    pub fn from_port(port: u16) -> Option<Service> {
+        SERVICE_PORTS
+            .iter()
+            .find(|(_, service_port)| *service_port == port)
+            .map(|(service, _)| *service)
+    }
+
+    pub fn port(self) -> u16 {
+        SERVICE_PORTS
+            .iter()
+            .find(|(service, _)| *service == self)
+            .map_or(0, |(_, port)| *port)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Service::CharGen => "chargen",
+            Service::Ntp => "ntp",
+            Service::NetBios => "netbios",
+            Service::Snmp => "snmp",
+            Service::Ldap => "ldap",
+            Service::Mssql => "mssql",
+            Service::Ssdp => "ssdp",
+            Service::Memcached => "memcached",
+        }
+    }
+
+    /// Command or operation of a datagram of the service, if it can be read.
+    /// CharGen datagrams carry none
+    pub fn command(self, payload: &[u8]) -> Option<String> {
+        match self {
+            Service::CharGen => None,
+            Service::Ntp => ntp_command(payload).map(str::to_string),
+            Service::NetBios => netbios_command(payload).map(str::to_string),
+            Service::Snmp => snmp_command(payload).map(str::to_string),
+            Service::Ldap => ldap_command(payload).map(str::to_string),
+            Service::Mssql => mssql_command(payload).map(str::to_string),
+            Service::Ssdp => text_command(payload),
+            // Datagrams start with a frame header of 8 bytes
+            Service::Memcached => text_command(payload.get(8..)?),
+        }
+    }
+}
+
+/// NTP mode, with the request code of the private mode, used by monlist
+fn ntp_command(payload: &[u8]) -> Option<&'static str> {
+    let first = *payload.first()?;
+    let version = (first >> 3) & 0x07;
+    if !(1..=4).contains(&version) {
+        return None;
+    }
+    Some(match first & 0x07 {
+        1 => "symmetric_active",
+        2 => "symmetric_passive",
+        3 => "client",
+        4 => "server",
+        5 => "broadcast",
+        6 => match payload.get(1)? & 0x1F {
+            1 => "control_readstat",
+            2 => "control_readvar",
+            _ => "control",
+        },
+        7 => match payload.get(3)? {
+            20 | 42 => "monlist",
+            _ => "private",
+        },
+        _ => return None,
+    })
+}
+
+/// Operation of a NetBIOS name service message, telling the node status
+/// queries apart from the name queries
+fn netbios_command(payload: &[u8]) -> Option<&'static str> {
+    let header = payload.get(..12)?;
+    let is_response = header[2] & 0x80 != 0;
+    Some(match ((header[2] >> 3) & 0x0F, is_response) {
+        (0, false) => {
+            // Question names are encoded on 34 bytes
+            match payload.get(46..48)? {
+                [0x00, 0x21] => "node_status",
+                _ => "name_query",
+            }
+        }
+        (0, true) => "query_response",
+        (5, _) => "registration",
+        (6, _) => "release",
+        (7, _) => "wack",
+        (8 | 9, _) => "refresh",
+        _ => return None,
+    })
+}
+
+/// Request type of a SQL Server Resolution Protocol message
+fn mssql_command(payload: &[u8]) -> Option<&'static str> {
+    Some(match payload.first()? {
+        0x02 => "broadcast_ex",
+        0x03 => "unicast_ex",
+        0x04 => "unicast_instance",
+        0x05 => "server_response",
+        0x0F => "unicast_dac",
+        _ => return None,
+    })
+}
+
+/// First word of a text protocol message, in lowercase, like the method of
+/// SSDP requests or the command of Memcached requests
+fn text_command(payload: &[u8]) -> Option<String> {
+    let word = payload
+        .split(|byte| byte.is_ascii_whitespace())
+        .next()
+        .filter(|word| !word.is_empty() && word.len() <= MAX_COMMAND_LEN)?;
+    if !word
+        .iter()
+        .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'/' | b'.' | b'_'))
+    {
+        return None;
+    }
+    // Responses of SSDP start with the version
+    if word.starts_with(b"HTTP/") {
+        return Some("response".to_string());
+    }
+    Some(
+        String::from_utf8_lossy(word)
+            .to_ascii_lowercase()
+            .replace('-', "_"),
+    )
+}
+
+/// Cursor over BER encoded values
+struct BerReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> BerReader<'a> {
+    /// Read the tag and the contents of the next value
+    fn value(&mut self) -> Option<(u8, &'a [u8])> {
+        let tag = *self.data.first()?;
+        let first_len = *self.data.get(1)?;
+        let (len, header_len) = if first_len & 0x80 == 0 {
+            (usize::from(first_len), 2)
+        } else {
+            let len_bytes = usize::from(first_len & 0x7F);
+            if !(1..=4).contains(&len_bytes) {
+                return None;
+            }
+            let len = self
+                .data
+                .get(2..2 + len_bytes)?
+                .iter()
+                .fold(0, |len, byte| (len << 8) | usize::from(*byte));
+            (len, 2 + len_bytes)
+        };
+        // Values may be cut by the end of the datagram
+        let end = (header_len + len).min(self.data.len());
+        let contents = &self.data[header_len..end];
+        self.data = &self.data[end..];
+        Some((tag, contents))
+    }
+}
+
+const BER_SEQUENCE: u8 = 0x30;
+const BER_INTEGER: u8 = 0x02;
+const BER_OCTET_STRING: u8 = 0x04;
+
+/// PDU of a SNMPv1 or SNMPv2c message. The PDUs of SNMPv3 may be encrypted,
+/// so only the version is told
+fn snmp_command(payload: &[u8]) -> Option<&'static str> {
+    let (BER_SEQUENCE, message) = (BerReader { data: payload }).value()? else {
+        return None;
+    };
+    let mut reader = BerReader { data: message };
+    let (BER_INTEGER, version) = reader.value()? else {
+        return None;
+    };
+    match version {
+        [0 | 1] => {}
+        [3] => return Some("v3"),
+        _ => return None,
+    }
+    let (BER_OCTET_STRING, _community) = reader.value()? else {
+        return None;
+    };
+    let (tag, _) = reader.value()?;
+    tag.checked_sub(0xA0)
+        .and_then(|index| SNMP_PDUS.get(usize::from(index)))
+        .copied()
+}
+
+/// Protocol operation of a connectionless LDAP message
+fn ldap_command(payload: &[u8]) -> Option<&'static str> {
+    let (BER_SEQUENCE, message) = (BerReader { data: payload }).value()? else {
+        return None;
+    };
+    let mut reader = BerReader { data: message };
+    let (BER_INTEGER, _message_id) = reader.value()? else {
+        return None;
+    };
+    // Operations have an application class tag
+    let (tag, _) = reader.value()?;
+    if tag & 0xC0 != 0x40 {
+        return None;
+    }
+    let operation = LDAP_OPERATIONS
+        .iter()
+        .find(|(number, _)| *number == tag & 0x1F)
+        .map_or("other", |(_, name)| name);
+    Some(operation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command() {
+        assert_eq!(Service::from_port(123), Some(Service::Ntp));
+        assert_eq!(Service::Memcached.port(), 11211);
+
+        let monlist = [0x17, 0x00, 0x03, 0x2A, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(Service::Ntp.command(&monlist).as_deref(), Some("monlist"));
+
+        let get_bulk = [
+            0x30, 0x19, 0x02, 0x01, 0x01, 0x04, 0x06, b'p', b'u', b'b', b'l', b'i', b'c', 0xA5,
+            0x0C, 0x02, 0x01, 0x01, 0x02, 0x01, 0x00, 0x02, 0x02, 0x07, 0xD0, 0x30, 0x00,
+        ];
+        assert_eq!(
+            Service::Snmp.command(&get_bulk).as_deref(),
+            Some("get_bulk")
+        );
+
+        let search = [
+            0x30, 0x84, 0x00, 0x00, 0x00, 0x09, 0x02, 0x01, 0x01, 0x63, 0x84, 0x00, 0x00, 0x00,
+            0x00,
+        ];
+        assert_eq!(
+            Service::Ldap.command(&search).as_deref(),
+            Some("search_request")
+        );
+
+        let stats = b"\x00\x01\x00\x00\x00\x01\x00\x00stats\r\n";
+        assert_eq!(Service::Memcached.command(stats).as_deref(), Some("stats"));
+        let search = b"M-SEARCH * HTTP/1.1\r\nST: ssdp:all\r\n\r\n";
+        assert_eq!(Service::Ssdp.command(search).as_deref(), Some("m_search"));
+        assert_eq!(Service::Ssdp.command(b"\x16\x03").as_deref(), None);
+    }
+}
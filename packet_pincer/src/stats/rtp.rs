@@ -0,0 +1,364 @@
+use super::{
+    media_sessions::{MediaSession, MediaSessions},
+    rtp_message::{static_clock_rate, RtpHeader},
+    FlowStat, FlowTimes, StatSettings,
+};
+use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
+use chrono::{DateTime, Utc};
+use std::{
+    io::{BufWriter, Error, Write},
+    net::IpAddr,
+    rc::Rc,
+};
+
+/// Lowest port used for media, to skip the UDP services
+const MIN_MEDIA_PORT: u16 = 1024;
+
+/// Largest jump of the sequence number between consecutive packets of a
+/// stream
+const MAX_DROPOUT: i16 = 3000;
+
+/// Clock rate used for the dynamic payload types that were not announced
+const DEFAULT_CLOCK_RATE: u32 = 8000;
+
+/// RTP stream of a single synchronization source
+#[derive(Debug, Clone)]
+struct RtpStream {
+    ssrc: u32,
+    payload_type: u8,
+    clock_rate: u32,
+    first_time: DateTime<Utc>,
+    first_timestamp: u32,
+    first_sequence_number: i64,
+    /// Highest sequence number, extended with the wraps
+    highest_sequence_number: i64,
+    last_sequence_number: u16,
+    received: u64,
+    /// Interarrival jitter of RFC 3550, in seconds
+    jitter: f64,
+    last_transit: f64,
+}
+
+impl RtpStream {
+    fn new(header: &RtpHeader, time: DateTime<Utc>, clock_rate: u32) -> RtpStream {
+        RtpStream {
+            ssrc: header.ssrc,
+            payload_type: header.payload_type,
+            clock_rate,
+            first_time: time,
+            first_timestamp: header.timestamp,
+            first_sequence_number: i64::from(header.sequence_number),
+            highest_sequence_number: i64::from(header.sequence_number),
+            last_sequence_number: header.sequence_number,
+            received: 1,
+            jitter: 0.0,
+            last_transit: 0.0,
+        }
+    }
+
+    /// Include a packet of the stream, returning whether it follows the
+    /// previous one
+    fn include(&mut self, header: &RtpHeader, time: DateTime<Utc>) -> bool {
+        self.received += 1;
+        let delta = header
+            .sequence_number
+            .wrapping_sub(self.last_sequence_number) as i16;
+        self.last_sequence_number = header.sequence_number;
+        let highest_delta = header.sequence_number.wrapping_sub(
+            // Only the lowest 16 bits are compared
+            self.highest_sequence_number as u16,
+        ) as i16;
+        if highest_delta > 0 {
+            self.highest_sequence_number += i64::from(highest_delta);
+        }
+
+        let arrival = (time - self.first_time).num_microseconds().unwrap_or(0) as f64 / 1_000_000.0;
+        let timestamp = header.timestamp.wrapping_sub(self.first_timestamp) as i32;
+        let transit = arrival - f64::from(timestamp) / f64::from(self.clock_rate);
+        let difference = (transit - self.last_transit).abs();
+        self.last_transit = transit;
+        self.jitter += (difference - self.jitter) / 16.0;
+
+        (1..=MAX_DROPOUT).contains(&delta)
+    }
+
+    fn expected(&self) -> u64 {
+        (self.highest_sequence_number - self.first_sequence_number + 1) as u64
+    }
+}
+
+/// RTP streams of a direction of the flow
+#[derive(Debug, Clone)]
+struct RtpDirection {
+    stream: Option<RtpStream>,
+    /// Whether two consecutive packets of a stream were seen, so the
+    /// direction is RTP
+    confirmed: bool,
+    ssrc_changes: u32,
+    /// Expected and received packets of the previous streams
+    expected: u64,
+    received: u64,
+}
+
+impl RtpDirection {
+    fn new() -> RtpDirection {
+        RtpDirection {
+            stream: None,
+            confirmed: false,
+            ssrc_changes: 0,
+            expected: 0,
+            received: 0,
+        }
+    }
+
+    fn include(&mut self, header: &RtpHeader, time: DateTime<Utc>, clock_rate: impl Fn(u8) -> u32) {
+        match &mut self.stream {
+            Some(stream) if stream.ssrc == header.ssrc => {
+                if stream.include(header, time) {
+                    self.confirmed = true;
+                }
+            }
+            stream => {
+                if let Some(previous) = stream {
+                    self.ssrc_changes += 1;
+                    self.expected += previous.expected();
+                    self.received += previous.received;
+                }
+                *stream = Some(RtpStream::new(
+                    header,
+                    time,
+                    clock_rate(header.payload_type),
+                ));
+            }
+        }
+    }
+
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+    ) -> Result<(), Error> {
+        let Some(stream) = self.stream.as_ref().filter(|_| self.confirmed) else {
+            return write!(writer, "0,,0,0,{:.9},{:.9},", 0.0, 0.0);
+        };
+        let expected = self.expected + stream.expected();
+        let received = self.received + stream.received;
+        let lost = expected.saturating_sub(received);
+        write!(writer, "{},", received)?;
+        write!(writer, "{},", stream.payload_type)?;
+        write!(writer, "{},", self.ssrc_changes)?;
+        write!(writer, "{},", lost)?;
+        write!(writer, "{:.9},", lost as f64 / expected as f64)?;
+        write!(writer, "{:.9},", stream.jitter)?;
+        Ok(())
+    }
+}
+
+/// RTP media of UDP flows between unprivileged ports: packets, payload type,
+/// SSRC changes, lost packets from the sequence numbers and the interarrival
+/// jitter of RFC 3550 of the last stream of each direction. The flow is
+/// linked to the Call-ID of the SIP dialog that announced any of its
+/// endpoints with SDP before its first RTP packet
+#[derive(Debug, Clone)]
+pub struct Rtp {
+    is_candidate: bool,
+    endpoints: [(IpAddr, u16); 2],
+    /// Sessions announced on the SIP flows, until the first RTP packet
+    media_sessions: Option<Rc<MediaSessions>>,
+    /// Session of the flow, found on the first RTP packet as the endpoints
+    /// may be announced again by later dialogs
+    session: Option<MediaSession>,
+    forward: RtpDirection,
+    backward: RtpDirection,
+}
+
+impl Rtp {
+    /// Session that announced any of the endpoints of the flow
+    fn find_media_session(
+        media_sessions: &MediaSessions,
+        endpoints: &[(IpAddr, u16)],
+    ) -> Option<MediaSession> {
+        endpoints
+            .iter()
+            .find_map(|(address, port)| media_sessions.get(*address, *port))
+    }
+
+    fn include_packet(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        sliced_packet: &etherparse::SlicedPacket,
+    ) {
+        if !self.is_candidate {
+            return;
+        }
+        let Some(etherparse::TransportSlice::Udp(udp_slice)) = &sliced_packet.transport else {
+            return;
+        };
+        let Some(header) = RtpHeader::parse(udp_slice.payload()) else {
+            return;
+        };
+        self.include_header(
+            identifier.is_forward(sliced_packet),
+            &header,
+            flow_times.last_packet_time,
+        );
+    }
+
+    fn include_header(&mut self, is_forward: bool, header: &RtpHeader, time: DateTime<Utc>) {
+        if let Some(media_sessions) = self.media_sessions.take() {
+            self.session = Self::find_media_session(&media_sessions, &self.endpoints);
+        }
+
+        // Dynamic payload types take the clock rate announced with SDP
+        let session = &self.session;
+        let clock_rate = |payload_type| {
+            static_clock_rate(payload_type)
+                .or_else(|| {
+                    session
+                        .as_ref()?
+                        .clock_rates
+                        .iter()
+                        .find(|(known, _)| *known == payload_type)
+                        .map(|(_, clock_rate)| *clock_rate)
+                })
+                .unwrap_or(DEFAULT_CLOCK_RATE)
+        };
+        let direction = if is_forward {
+            &mut self.forward
+        } else {
+            &mut self.backward
+        };
+        direction.include(header, time, clock_rate);
+    }
+}
+
+impl FlowStat for Rtp {
+    fn from_packet(
+        settings: &StatSettings,
+        identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        let is_udp = matches!(
+            sliced_packet.transport,
+            Some(etherparse::TransportSlice::Udp(_))
+        );
+        let mut rtp = Rtp {
+            is_candidate: is_udp
+                && identifier.source_port() >= MIN_MEDIA_PORT
+                && identifier.dest_port() >= MIN_MEDIA_PORT,
+            endpoints: [
+                (identifier.source_ip(), identifier.source_port()),
+                (identifier.dest_ip(), identifier.dest_port()),
+            ],
+            media_sessions: Some(settings.shared_media_sessions()),
+            session: None,
+            forward: RtpDirection::new(),
+            backward: RtpDirection::new(),
+        };
+
+        rtp.include_packet(identifier, flow_times, sliced_packet);
+        rtp
+    }
+    fn include(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        self.include_packet(identifier, flow_times, sliced_packet);
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        write!(writer, "rtp_media,")?;
+        for direction in ["fwd", "bwd"] {
+            write!(
+                writer,
+                "rtp_{}_packets,rtp_{}_payload_type,",
+                direction, direction
+            )?;
+            write!(writer, "rtp_{}_ssrc_changes,", direction)?;
+            write!(
+                writer,
+                "rtp_{}_lost,rtp_{}_loss_ratio,",
+                direction, direction
+            )?;
+            write!(writer, "rtp_{}_jitter,", direction)?;
+        }
+        write!(writer, "rtp_sip_call_id,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        let is_rtp = self.forward.confirmed || self.backward.confirmed;
+        write!(writer, "{},", u8::from(is_rtp))?;
+        self.forward.write_csv_value(writer)?;
+        self.backward.write_csv_value(writer)?;
+        match self.session.as_ref().filter(|_| is_rtp) {
+            Some(session) => write!(writer, "{},", session.call_id)?,
+            None => write!(writer, ",")?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_session_on_port_reuse() {
+        let address = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2));
+        let media_sessions = Rc::new(MediaSessions::default());
+        let session = |call_id: &str, clock_rate| MediaSession {
+            call_id: call_id.into(),
+            clock_rates: vec![(96, clock_rate)],
+        };
+        media_sessions.insert(address, 40000, session("first", 16000));
+
+        let mut v = Rtp {
+            is_candidate: true,
+            endpoints: [
+                (address, 40000),
+                (IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)), 50000),
+            ],
+            media_sessions: Some(Rc::clone(&media_sessions)),
+            session: None,
+            forward: RtpDirection::new(),
+            backward: RtpDirection::new(),
+        };
+        let time = DateTime::from_timestamp(0, 0).unwrap();
+        for sequence_number in 1..=2 {
+            let header = RtpHeader {
+                payload_type: 96,
+                sequence_number,
+                timestamp: 160 * u32::from(sequence_number),
+                ssrc: 1,
+            };
+            v.include_header(true, &header, time);
+        }
+        // A later call announcing the same endpoint
+        media_sessions.insert(address, 40000, session("second", 48000));
+
+        assert_eq!(v.forward.stream.as_ref().unwrap().clock_rate, 16000);
+        let mut writer = BufWriter::new(Vec::new());
+        let flow_times = FlowTimes {
+            first_packet_time: time,
+            last_packet_time: time,
+        };
+        v.write_csv_value(&mut writer, &flow_times).unwrap();
+        let line = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert!(line.starts_with("1,2,96,"));
+        assert!(line.ends_with(",first,"));
+    }
+}
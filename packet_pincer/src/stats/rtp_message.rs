@@ -0,0 +1,85 @@
+/// Fixed header of a RTP packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtpHeader {
+    pub payload_type: u8,
+    pub sequence_number: u16,
+    pub timestamp: u32,
+    pub ssrc: u32,
+}
+
+impl RtpHeader {
+    pub fn parse(payload: &[u8]) -> Option<RtpHeader> {
+        let header = payload.get(..12)?;
+        if header[0] >> 6 != 2 {
+            return None;
+        }
+        // These values are the packet types of RTCP with the marker bit
+        let payload_type = header[1] & 0x7F;
+        if (72..=76).contains(&payload_type) {
+            return None;
+        }
+
+        let mut header_len = 12 + 4 * usize::from(header[0] & 0x0F);
+        if header[0] & 0x10 != 0 {
+            let extension = payload.get(header_len..header_len + 4)?;
+            header_len += 4 + 4 * usize::from(u16::from_be_bytes([extension[2], extension[3]]));
+        }
+        let padding_len = if header[0] & 0x20 != 0 {
+            usize::from(*payload.last()?)
+        } else {
+            0
+        };
+        if header_len + padding_len > payload.len() {
+            return None;
+        }
+
+        Some(RtpHeader {
+            payload_type,
+            sequence_number: u16::from_be_bytes([header[2], header[3]]),
+            timestamp: u32::from_be_bytes([header[4], header[5], header[6], header[7]]),
+            ssrc: u32::from_be_bytes([header[8], header[9], header[10], header[11]]),
+        })
+    }
+}
+
+/// Clock rate of the static payload types of the RTP audio and video profile
+pub fn static_clock_rate(payload_type: u8) -> Option<u32> {
+    match payload_type {
+        6 => Some(16000),
+        10 | 11 => Some(44100),
+        14 => Some(90000),
+        16 => Some(11025),
+        17 => Some(22050),
+        0..=18 => Some(8000),
+        25..=34 => Some(90000),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_header() {
+        let packet = [
+            0x80, 0x08, 0x12, 0x34, 0x00, 0x00, 0x00, 0xA0, 0xDE, 0xAD, 0xBE, 0xEF, 0xD5, 0xD5,
+        ];
+        assert_eq!(
+            RtpHeader::parse(&packet),
+            Some(RtpHeader {
+                payload_type: 8,
+                sequence_number: 0x1234,
+                timestamp: 160,
+                ssrc: 0xDEADBEEF,
+            })
+        );
+
+        // RTCP sender report
+        assert!(RtpHeader::parse(&[0x80, 0xC8, 0, 6, 0, 0, 0, 1, 0, 0, 0, 0]).is_none());
+        // Contributing sources beyond the packet
+        assert!(RtpHeader::parse(&[0x8F, 0x00, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1]).is_none());
+        assert_eq!(static_clock_rate(0), Some(8000));
+        assert_eq!(static_clock_rate(96), None);
+    }
+}
@@ -1,3 +1,5 @@
+use super::quantile_sketch::QuantileSketch;
+
 /// Running stat implementation based on Knuth TAOCP vol 2, 3rd edition, page
 /// 232. Where we have the recurrences for 2 <= k <= n:
 ///
@@ -5,8 +7,11 @@
 /// - S_{1} = 0,     S_{k} = S_{k-1} + ( x_{k} - M_{k-1} ) * ( x_{k} - M_{k} )
 ///
 /// Where M_{k} is the mean and the variance is equal to S_{k} / (k - 1) at the
-/// step k
-#[derive(Debug)]
+/// step k. The third and fourth central moments are accomulated with the
+/// one-pass updates of Pébay (2008) and Terriberry (2007), which also allow
+/// merging two partial running stats. Quantiles can be estimated with a
+/// bounded memory sketch if enabled on creation
+#[derive(Debug, Clone)]
 pub struct RunningStat {
     count: u64,
     sum: u64,
@@ -15,9 +20,15 @@ pub struct RunningStat {
     mean: f64,
     /// Variance without dividing by total_count-1
     sq_diff: f64,
+    /// Sum of the cubed differences to the mean
+    cube_diff: f64,
+    /// Sum of the differences to the mean to the fourth power
+    fourth_diff: f64,
+    quantiles: Option<QuantileSketch>,
 }
 
 impl RunningStat {
+    /// Create a running stat without values
     pub fn new() -> RunningStat {
         RunningStat {
             count: 0,
@@ -26,40 +37,128 @@ impl RunningStat {
             max: u64::MIN,
             mean: 0.0,
             sq_diff: 0.0,
+            cube_diff: 0.0,
+            fourth_diff: 0.0,
+            quantiles: None,
         }
     }
 
+    /// Create a running stat that also estimates quantiles
+    pub fn with_quantiles() -> RunningStat {
+        RunningStat {
+            quantiles: Some(QuantileSketch::new()),
+            ..RunningStat::new()
+        }
+    }
+
+    /// Include a value on the running stat
     pub fn include(&mut self, value: u64) {
         // Accoumate integer values
         self.count += 1;
         self.sum += value;
         self.min = std::cmp::min(self.min, value);
         self.max = std::cmp::max(self.max, value);
+        if let Some(quantiles) = &mut self.quantiles {
+            quantiles.include(value);
+        }
 
         // List reccurence values
         let value = value as f64;
         let total = self.count as f64;
         let previous_mean = self.mean;
         let previous_sq_diff = self.sq_diff;
+        let previous_cube_diff = self.cube_diff;
 
         // Apply recurrence
         let next_mean = previous_mean + (value - previous_mean) / total;
         let next_sq_diff = previous_sq_diff + (value - previous_mean) * (value - next_mean);
 
+        // Apply higher moments recurrence, which depend on the previous lower
+        // moments
+        let delta = value - previous_mean;
+        let delta_n = delta / total;
+        let term = delta * delta_n * (total - 1.0);
+        self.fourth_diff += term * delta_n * delta_n * (total * total - 3.0 * total + 3.0)
+            + 6.0 * delta_n * delta_n * previous_sq_diff
+            - 4.0 * delta_n * previous_cube_diff;
+        self.cube_diff += term * delta_n * (total - 2.0) - 3.0 * delta_n * previous_sq_diff;
+
         // Store values
         self.mean = next_mean;
         self.sq_diff = next_sq_diff;
     }
 
-    #[allow(dead_code)]
+    /// Combine the values of another running stat into this one, as if all
+    /// of them had been included on this one. Quantiles are only kept if both
+    /// running stats estimate them. Used to aggregate the stats computed on
+    /// separate threads or partitions of the traffic
+    pub fn merge(&mut self, other: &RunningStat) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            let quantiles = self.quantiles.take();
+            *self = other.clone();
+            if quantiles.is_none() {
+                self.quantiles = None;
+            }
+            return;
+        }
+
+        let count_a = self.count as f64;
+        let count_b = other.count as f64;
+        let total = count_a + count_b;
+        let delta = other.mean - self.mean;
+        let delta_2 = delta * delta;
+
+        let mean = self.mean + delta * count_b / total;
+        let sq_diff = self.sq_diff + other.sq_diff + delta_2 * count_a * count_b / total;
+        let cube_diff = self.cube_diff
+            + other.cube_diff
+            + delta_2 * delta * count_a * count_b * (count_a - count_b) / (total * total)
+            + 3.0 * delta * (count_a * other.sq_diff - count_b * self.sq_diff) / total;
+        let fourth_diff = self.fourth_diff
+            + other.fourth_diff
+            + delta_2
+                * delta_2
+                * count_a
+                * count_b
+                * (count_a * count_a - count_a * count_b + count_b * count_b)
+                / (total * total * total)
+            + 6.0
+                * delta_2
+                * (count_a * count_a * other.sq_diff + count_b * count_b * self.sq_diff)
+                / (total * total)
+            + 4.0 * delta * (count_a * other.cube_diff - count_b * self.cube_diff) / total;
+
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = std::cmp::min(self.min, other.min);
+        self.max = std::cmp::max(self.max, other.max);
+        self.mean = mean;
+        self.sq_diff = sq_diff;
+        self.cube_diff = cube_diff;
+        self.fourth_diff = fourth_diff;
+        self.quantiles = match (self.quantiles.take(), &other.quantiles) {
+            (Some(mut quantiles), Some(other_quantiles)) => {
+                quantiles.merge(other_quantiles);
+                Some(quantiles)
+            }
+            _ => None,
+        };
+    }
+
+    /// Number of values included
     pub fn current_count(&self) -> u64 {
         self.count
     }
 
+    /// Sum of the values
     pub fn current_sum(&self) -> u64 {
         self.sum
     }
 
+    /// Minimum of the values, or `None` if there are no values
     pub fn current_min(&self) -> Option<u64> {
         if self.count == 0 {
             None
@@ -68,6 +167,7 @@ impl RunningStat {
         }
     }
 
+    /// Maximum of the values, or `None` if there are no values
     pub fn current_max(&self) -> Option<u64> {
         if self.count == 0 {
             None
@@ -76,10 +176,12 @@ impl RunningStat {
         }
     }
 
+    /// Mean of the values, or 0 if there are no values
     pub fn current_mean(&self) -> f64 {
         self.mean
     }
 
+    /// Sample variance of the values
     pub fn current_variance(&self) -> f64 {
         if self.count == 1 {
             0.0
@@ -88,9 +190,44 @@ impl RunningStat {
         }
     }
 
+    /// Sample standard deviation of the values
     pub fn current_standard_deviation(&self) -> f64 {
         f64::sqrt(self.current_variance())
     }
+
+    /// Population skewness of the values, or 0 if they are all equal
+    pub fn current_skewness(&self) -> f64 {
+        if self.sq_diff <= 0.0 {
+            0.0
+        } else {
+            (self.count as f64).sqrt() * self.cube_diff / self.sq_diff.powf(1.5)
+        }
+    }
+
+    /// Population excess kurtosis of the values, or 0 if they are all equal
+    pub fn current_kurtosis(&self) -> f64 {
+        if self.sq_diff <= 0.0 {
+            0.0
+        } else {
+            (self.count as f64) * self.fourth_diff / (self.sq_diff * self.sq_diff) - 3.0
+        }
+    }
+
+    /// Estimate the value at the given percentile, between 0 and 100. Returns
+    /// 0 if there are no values and `None` if quantiles are not enabled
+    pub fn current_percentile(&self, percentile: u8) -> Option<f64> {
+        let quantiles = self.quantiles.as_ref()?;
+        match quantiles.quantile(f64::from(percentile) / 100.0) {
+            None => Some(0.0),
+            Some(estimate) => Some(estimate.clamp(self.min as f64, self.max as f64)),
+        }
+    }
+}
+
+impl Default for RunningStat {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
@@ -146,4 +283,78 @@ mod tests {
         assert_eq!(v.current_variance(), 70.0 / 4.0);
         assert_eq!(v.current_standard_deviation(), f64::sqrt(70.0 / 4.0));
     }
+
+    /// Compute the population skewness and excess kurtosis of the values
+    /// directly from their definition
+    fn direct_higher_moments(values: &[u64]) -> (f64, f64) {
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<u64>() as f64 / n;
+        let moment = |power: i32| -> f64 {
+            values
+                .iter()
+                .map(|value| (*value as f64 - mean).powi(power))
+                .sum::<f64>()
+                / n
+        };
+        (
+            moment(3) / moment(2).powf(1.5),
+            moment(4) / (moment(2) * moment(2)) - 3.0,
+        )
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_higher_moments() {
+        let values = [2, 8, 0, 4, 1, 9, 9, 0, 120, 3];
+        let mut v = RunningStat::new();
+
+        assert_eq!(v.current_skewness(), 0.0);
+        assert_eq!(v.current_kurtosis(), 0.0);
+
+        for value in values {
+            v.include(value);
+        }
+
+        let (skewness, kurtosis) = direct_higher_moments(&values);
+        assert_close(v.current_skewness(), skewness);
+        assert_close(v.current_kurtosis(), kurtosis);
+    }
+
+    #[test]
+    fn test_merge() {
+        let values = [2, 8, 0, 4, 1, 9, 9, 0, 120, 3];
+        let mut sequential = RunningStat::new();
+        let mut first = RunningStat::new();
+        let mut second = RunningStat::new();
+
+        for value in values {
+            sequential.include(value);
+        }
+        for value in &values[..3] {
+            first.include(*value);
+        }
+        for value in &values[3..] {
+            second.include(*value);
+        }
+        first.merge(&second);
+
+        assert_eq!(first.current_count(), sequential.current_count());
+        assert_eq!(first.current_sum(), sequential.current_sum());
+        assert_eq!(first.current_min(), sequential.current_min());
+        assert_eq!(first.current_max(), sequential.current_max());
+        assert_close(first.current_mean(), sequential.current_mean());
+        assert_close(first.current_variance(), sequential.current_variance());
+        assert_close(first.current_skewness(), sequential.current_skewness());
+        assert_close(first.current_kurtosis(), sequential.current_kurtosis());
+
+        // Merging with an empty running stat changes nothing
+        let mut empty = RunningStat::new();
+        empty.merge(&first);
+        first.merge(&RunningStat::new());
+        assert_eq!(empty.current_count(), first.current_count());
+        assert_close(empty.current_kurtosis(), first.current_kurtosis());
+    }
 }
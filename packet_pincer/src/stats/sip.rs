@@ -0,0 +1,195 @@
+use super::{
+    csv_text::escape_text,
+    media_sessions::{MediaSession, MediaSessions},
+    sip_message::{frame_len, sdp_media, SipMessage, StartLine, METHODS, SIP_PORT},
+    stream_frames::{Directional, FrameReader},
+    FlowStat, FlowTimes, StatSettings,
+};
+use crate::{
+    packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier,
+    stream_reassembly::StreamChunk,
+};
+use std::{
+    io::{BufWriter, Error, Write},
+    rc::Rc,
+};
+
+/// Largest number of different Call-IDs kept
+const MAX_CALL_IDS: usize = 64;
+
+/// SIP messages of the flows on the SIP port, over UDP or TCP: methods,
+/// response status classes and Call-IDs. The media endpoints announced with
+/// SDP are shared so the RTP flows can be linked to the dialog
+#[derive(Debug, Clone)]
+pub struct Sip {
+    readers: Directional<FrameReader>,
+    is_sip: bool,
+    media_sessions: Rc<MediaSessions>,
+
+    request_count: u32,
+    response_count: u32,
+    method_counts: [u32; METHODS.len()],
+    other_method_count: u32,
+    status_class_counts: [u32; 6],
+    last_status: u16,
+    call_ids: Vec<Rc<str>>,
+    sdp_media_count: u32,
+}
+
+impl Sip {
+    fn include_message(&mut self, message: &[u8]) {
+        let Some(message) = SipMessage::parse(message) else {
+            return;
+        };
+
+        match message.start_line {
+            StartLine::Request { method } => {
+                self.request_count += 1;
+                match METHODS.iter().position(|name| name.as_bytes() == method) {
+                    Some(i) => self.method_counts[i] += 1,
+                    None => self.other_method_count += 1,
+                }
+            }
+            StartLine::Response { status } => {
+                self.response_count += 1;
+                if (100..700).contains(&status) {
+                    self.status_class_counts[usize::from(status / 100 - 1)] += 1;
+                }
+                self.last_status = status;
+            }
+        }
+
+        let Some(call_id) = message.header("call-id") else {
+            return;
+        };
+        let call_id: Rc<str> = escape_text(call_id).into();
+        if self.call_ids.len() < MAX_CALL_IDS && !self.call_ids.contains(&call_id) {
+            self.call_ids.push(Rc::clone(&call_id));
+        }
+
+        if message.has_sdp() {
+            for media in sdp_media(message.body) {
+                self.sdp_media_count += 1;
+                self.media_sessions.insert(
+                    media.address,
+                    media.port,
+                    MediaSession {
+                        call_id: Rc::clone(&call_id),
+                        clock_rates: media.clock_rates,
+                    },
+                );
+            }
+        }
+    }
+
+    fn include_packet(&mut self, sliced_packet: &etherparse::SlicedPacket) {
+        if !self.is_sip {
+            return;
+        }
+        if let Some(etherparse::TransportSlice::Udp(udp_slice)) = &sliced_packet.transport {
+            self.include_message(udp_slice.payload());
+        }
+    }
+}
+
+impl FlowStat for Sip {
+    fn from_packet(
+        settings: &StatSettings,
+        identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        let mut sip = Sip {
+            readers: Directional::default(),
+            is_sip: identifier.source_port() == SIP_PORT || identifier.dest_port() == SIP_PORT,
+            media_sessions: settings.shared_media_sessions(),
+            request_count: 0,
+            response_count: 0,
+            method_counts: [0; METHODS.len()],
+            other_method_count: 0,
+            status_class_counts: [0; 6],
+            last_status: 0,
+            call_ids: Vec::new(),
+            sdp_media_count: 0,
+        };
+
+        sip.include_packet(sliced_packet);
+        sip
+    }
+    fn include(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        self.include_packet(sliced_packet);
+    }
+    fn uses_streams(_settings: &StatSettings) -> bool {
+        true
+    }
+    fn include_stream(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        chunk: &StreamChunk,
+    ) {
+        if !self.is_sip {
+            return;
+        }
+
+        Directional::with(
+            self,
+            |sip| &mut sip.readers,
+            chunk.is_forward,
+            |sip, reader| {
+                reader.include(chunk.gap != 0, chunk.data, frame_len, |message| {
+                    sip.include_message(message);
+                    true
+                })
+            },
+        );
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        write!(writer, "sip_request_count,sip_response_count,")?;
+        for method in METHODS {
+            write!(writer, "sip_method_{}_count,", method.to_ascii_lowercase())?;
+        }
+        write!(writer, "sip_method_other_count,")?;
+        for class in 1..=6 {
+            write!(writer, "sip_status_{}xx_count,", class)?;
+        }
+        write!(writer, "sip_last_status,")?;
+        write!(writer, "sip_call_id,sip_call_id_count,sip_sdp_media_count,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        write!(writer, "{},{},", self.request_count, self.response_count)?;
+        for count in self.method_counts {
+            write!(writer, "{},", count)?;
+        }
+        write!(writer, "{},", self.other_method_count)?;
+        for count in self.status_class_counts {
+            write!(writer, "{},", count)?;
+        }
+        write!(writer, "{},", self.last_status)?;
+        write!(
+            writer,
+            "{},",
+            self.call_ids.first().map_or("", |call_id| call_id)
+        )?;
+        write!(writer, "{},", self.call_ids.len())?;
+        write!(writer, "{},", self.sdp_media_count)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,249 @@
+use super::stream_frames::FrameLength;
+use std::net::IpAddr;
+
+/// Port of SIP without TLS
+pub const SIP_PORT: u16 = 5060;
+
+/// Largest message head buffered, with the start line and the headers
+const MAX_HEAD_LEN: usize = 8192;
+
+/// Largest body of a message
+const MAX_BODY_LEN: usize = 65536;
+
+/// Request methods with a dedicated count
+pub const METHODS: [&str; 6] = ["INVITE", "ACK", "BYE", "CANCEL", "REGISTER", "OPTIONS"];
+
+/// Start line of a SIP message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartLine<'a> {
+    Request { method: &'a [u8] },
+    Response { status: u16 },
+}
+
+/// The parts of a SIP message used by the stats
+#[derive(Debug, Clone)]
+pub struct SipMessage<'a> {
+    pub start_line: StartLine<'a>,
+    /// Headers with their names in lowercase, with the compact forms expanded
+    headers: Vec<(String, &'a [u8])>,
+    pub body: &'a [u8],
+}
+
+/// Position of the end of the head, after the blank line
+fn head_end(bytes: &[u8]) -> Option<usize> {
+    bytes
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|position| position + 4)
+}
+
+/// Expand the compact form of a header name
+fn full_header_name(name: &str) -> &str {
+    match name {
+        "i" => "call-id",
+        "l" => "content-length",
+        "c" => "content-type",
+        "f" => "from",
+        "t" => "to",
+        "m" => "contact",
+        "v" => "via",
+        _ => name,
+    }
+}
+
+impl<'a> SipMessage<'a> {
+    pub fn parse(message: &'a [u8]) -> Option<SipMessage<'a>> {
+        let end = head_end(message).unwrap_or(message.len());
+        let mut lines = message[..end]
+            .split(|byte| *byte == b'\n')
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+        let first_line = lines.next()?;
+
+        let start_line = if let Some(rest) = first_line.strip_prefix(b"SIP/2.0 ") {
+            let status = std::str::from_utf8(rest.get(..3)?).ok()?.parse().ok()?;
+            StartLine::Response { status }
+        } else {
+            let mut parts = first_line.split(|byte| *byte == b' ');
+            let method = parts.next()?;
+            parts.next()?;
+            if parts.next()? != b"SIP/2.0"
+                || method.is_empty()
+                || !method.iter().all(u8::is_ascii_uppercase)
+            {
+                return None;
+            }
+            StartLine::Request { method }
+        };
+
+        let headers = lines
+            .filter(|line| !line.is_empty())
+            .filter_map(|line| {
+                let separator = line.iter().position(|byte| *byte == b':')?;
+                let name = std::str::from_utf8(&line[..separator]).ok()?;
+                let name = name.trim().to_ascii_lowercase();
+                Some((
+                    full_header_name(&name).to_string(),
+                    line[separator + 1..].trim_ascii(),
+                ))
+            })
+            .collect();
+
+        Some(SipMessage {
+            start_line,
+            headers,
+            body: &message[end..],
+        })
+    }
+
+    /// Value of the first header with the given lowercase name
+    pub fn header(&self, name: &str) -> Option<&'a [u8]> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| *value)
+    }
+
+    /// Check if the body is a SDP session description
+    pub fn has_sdp(&self) -> bool {
+        self.header("content-type")
+            .is_some_and(|value| value.to_ascii_lowercase().starts_with(b"application/sdp"))
+    }
+}
+
+/// Length of the SIP message at the start of the bytes of a TCP stream,
+/// from its Content-Length header
+pub fn frame_len(bytes: &[u8]) -> FrameLength {
+    let Some(end) = head_end(bytes) else {
+        return if bytes.len() > MAX_HEAD_LEN {
+            FrameLength::Invalid
+        } else {
+            FrameLength::Incomplete
+        };
+    };
+    let Some(message) = SipMessage::parse(&bytes[..end]) else {
+        return FrameLength::Invalid;
+    };
+    let body_len = message
+        .header("content-length")
+        .and_then(|value| std::str::from_utf8(value).ok()?.parse::<usize>().ok())
+        .unwrap_or(0);
+    if body_len > MAX_BODY_LEN {
+        return FrameLength::Invalid;
+    }
+    FrameLength::Length(end + body_len)
+}
+
+/// Media stream announced by a SDP session description
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SdpMedia {
+    /// Address and port where the media is received
+    pub address: IpAddr,
+    pub port: u16,
+    /// Clock rates of the payload types, from the rtpmap attributes
+    pub clock_rates: Vec<(u8, u32)>,
+}
+
+/// Media description of a SDP session description, which may take the
+/// connection address of the session
+struct MediaDescription {
+    port: u16,
+    address: Option<IpAddr>,
+    clock_rates: Vec<(u8, u32)>,
+}
+
+/// Media streams of a SDP session description with a connection address
+/// and an enabled port
+pub fn sdp_media(body: &[u8]) -> Vec<SdpMedia> {
+    let mut session_address = None;
+    let mut media: Vec<MediaDescription> = Vec::new();
+
+    let lines = body
+        .split(|byte| *byte == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter_map(|line| std::str::from_utf8(line).ok());
+    for line in lines {
+        if let Some(connection) = line.strip_prefix("c=") {
+            // Connection data is "IN IP4 <address>", maybe with a TTL
+            let address = connection
+                .split(' ')
+                .nth(2)
+                .and_then(|address| address.split('/').next()?.parse().ok());
+            match media.last_mut() {
+                Some(description) => description.address = address,
+                None => session_address = address,
+            }
+        } else if let Some(description) = line.strip_prefix("m=") {
+            let port = description
+                .split(' ')
+                .nth(1)
+                .and_then(|port| port.split('/').next()?.parse().ok())
+                .unwrap_or(0);
+            media.push(MediaDescription {
+                port,
+                address: None,
+                clock_rates: Vec::new(),
+            });
+        } else if let Some(rtpmap) = line.strip_prefix("a=rtpmap:") {
+            // The map is "<payload type> <encoding>/<clock rate>"
+            let mut parts = rtpmap.split([' ', '/']);
+            let payload_type = parts.next().and_then(|value| value.parse().ok());
+            let clock_rate = parts.nth(1).and_then(|value| value.parse().ok());
+            if let (Some(description), Some(payload_type), Some(clock_rate)) =
+                (media.last_mut(), payload_type, clock_rate)
+            {
+                description.clock_rates.push((payload_type, clock_rate));
+            }
+        }
+    }
+
+    media
+        .into_iter()
+        .filter(|description| description.port != 0)
+        .filter_map(|description| {
+            Some(SdpMedia {
+                address: description.address.or(session_address)?,
+                port: description.port,
+                clock_rates: description.clock_rates,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_message() {
+        let body = b"v=0\r\no=- 1 1 IN IP4 10.0.0.1\r\nc=IN IP4 10.0.0.1\r\nt=0 0\r\n\
+            m=audio 49170 RTP/AVP 0 96\r\na=rtpmap:96 opus/48000/2\r\n\
+            m=video 0 RTP/AVP 31\r\n";
+        let mut message = format!(
+            "INVITE sip:bob@example.com SIP/2.0\r\ni: a84b4c76e66710\r\n\
+            Content-Type: application/sdp\r\nl: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        message.extend_from_slice(body);
+        message.extend_from_slice(b"SIP/2.0 100 Trying\r\n");
+
+        assert_eq!(frame_len(&message), FrameLength::Length(message.len() - 20));
+        let v = SipMessage::parse(&message[..message.len() - 20]).unwrap();
+        assert_eq!(v.start_line, StartLine::Request { method: b"INVITE" });
+        assert_eq!(v.header("call-id"), Some(&b"a84b4c76e66710"[..]));
+        assert!(v.has_sdp());
+
+        assert_eq!(
+            sdp_media(v.body),
+            vec![SdpMedia {
+                address: "10.0.0.1".parse().unwrap(),
+                port: 49170,
+                clock_rates: vec![(96, 48000)],
+            }]
+        );
+
+        let v = SipMessage::parse(b"SIP/2.0 486 Busy Here\r\n\r\n").unwrap();
+        assert_eq!(v.start_line, StartLine::Response { status: 486 });
+        assert!(SipMessage::parse(b"GET / HTTP/1.1\r\n\r\n").is_none());
+    }
+}
@@ -0,0 +1,305 @@
+use super::{
+    csv_text::escape_text,
+    running_stat::RunningStat,
+    ssh_message::{KexInit, SshEvent, SshStreamReader, KEXINIT, SSH_PORT},
+    stream_frames::Directional,
+    FlowStat, FlowTimes, StatSettings,
+};
+use crate::{
+    packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier,
+    stream_reassembly::StreamChunk,
+};
+use std::io::{BufWriter, Error, Write};
+
+/// Largest encrypted chunk sent by the server that is taken as a reply to an
+/// authentication request, like a failure listing the methods that can
+/// continue. Larger ones follow a successful authentication. Chunks holding
+/// several packets make the heuristic miss some replies
+const MAX_AUTH_REPLY_LEN: usize = 128;
+
+/// Side of a SSH connection, read from a direction of the stream
+#[derive(Debug, Clone)]
+struct SshSide {
+    identification: Option<Vec<u8>>,
+    kex_init: Option<KexInit>,
+    /// Sizes of the chunks of the stream after NEWKEYS. The lengths of the
+    /// packets are encrypted too, so a chunk may hold several packets or part
+    /// of one
+    encrypted: RunningStat,
+}
+
+/// SSH identification strings, KEXINIT algorithms with the HASSH
+/// fingerprints and the sizes of the encrypted chunks of TCP flows, read
+/// from the reassembled streams. The encrypted stream can not be split in
+/// packets, so it is counted in the chunks given by the reassembly. The
+/// outcome of the authentication is a heuristic: small encrypted replies of
+/// the server are taken as failures, including the one to the usual `none`
+/// probe, and a larger one after a client request as a success, which turns
+/// the small reply sent just before it into the success reply
+#[derive(Debug, Clone)]
+pub struct Ssh {
+    readers: Directional<SshStreamReader>,
+    client: SshSide,
+    server: SshSide,
+    /// Whether the client sends on the forward direction
+    client_is_forward: bool,
+
+    /// Small encrypted chunks of the server before the authentication
+    /// succeeded, including the acceptance of the authentication service
+    auth_reply_count: u32,
+    /// Whether the last small chunk of the server was sent after the last
+    /// chunk of the client
+    auth_reply_is_last: bool,
+    auth_success: bool,
+}
+
+impl SshSide {
+    fn new() -> SshSide {
+        SshSide {
+            identification: None,
+            kex_init: None,
+            encrypted: RunningStat::new(),
+        }
+    }
+}
+
+impl Ssh {
+    fn include_event(&mut self, is_client: bool, event: SshEvent) {
+        let side = if is_client {
+            &mut self.client
+        } else {
+            &mut self.server
+        };
+        match event {
+            SshEvent::Identification(line) => side.identification = Some(line.to_vec()),
+            SshEvent::Message(payload) => {
+                if payload[0] == KEXINIT && side.kex_init.is_none() {
+                    side.kex_init = KexInit::parse(payload);
+                }
+            }
+            SshEvent::Encrypted(len) => {
+                side.encrypted.include(len as u64);
+                if self.auth_success {
+                    return;
+                }
+                if is_client {
+                    self.auth_reply_is_last = false;
+                } else if len <= MAX_AUTH_REPLY_LEN {
+                    self.auth_reply_count += 1;
+                    self.auth_reply_is_last = true;
+                } else if self.client.encrypted.current_count() != 0 {
+                    self.auth_success = true;
+                    // The reply to the last request was the success
+                    if self.auth_reply_is_last {
+                        self.auth_reply_count -= 1;
+                    }
+                }
+            }
+        }
+    }
+
+    fn write_side<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        side: &SshSide,
+        is_client: bool,
+    ) -> Result<(), Error> {
+        write!(
+            writer,
+            "{},",
+            escape_text(side.identification.as_deref().unwrap_or_default())
+        )?;
+        match &side.kex_init {
+            Some(kex_init) => {
+                for count in kex_init.algorithm_counts(is_client) {
+                    write!(writer, "{},", count)?;
+                }
+                write!(writer, "{},", kex_init.hassh(is_client))?;
+            }
+            None => write!(writer, "0,0,0,0,0,,")?,
+        }
+        write!(writer, "{},", side.encrypted.current_count())?;
+        write!(writer, "{},", side.encrypted.current_sum())?;
+        write!(writer, "{:.9},", side.encrypted.current_mean())?;
+        Ok(())
+    }
+}
+
+impl FlowStat for Ssh {
+    fn from_packet(
+        _settings: &StatSettings,
+        identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        _sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        Ssh {
+            readers: Directional::default(),
+            client: SshSide::new(),
+            server: SshSide::new(),
+            // Flows are started by the client unless they were already
+            // established when the capture started
+            client_is_forward: identifier.source_port() != SSH_PORT
+                || identifier.dest_port() == SSH_PORT,
+            auth_reply_count: 0,
+            auth_reply_is_last: false,
+            auth_success: false,
+        }
+    }
+    fn include(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        _sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+    }
+    fn uses_streams(_settings: &StatSettings) -> bool {
+        true
+    }
+    fn include_stream(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        chunk: &StreamChunk,
+    ) {
+        let is_client = chunk.is_forward == self.client_is_forward;
+
+        Directional::with(
+            self,
+            |ssh| &mut ssh.readers,
+            chunk.is_forward,
+            |ssh, reader| {
+                reader.include(chunk.gap != 0, chunk.data, |event| {
+                    ssh.include_event(is_client, event)
+                })
+            },
+        );
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        for side in ["client", "server"] {
+            write!(writer, "ssh_{}_version,", side)?;
+            write!(
+                writer,
+                "ssh_{}_kex_count,ssh_{}_host_key_count,",
+                side, side
+            )?;
+            write!(writer, "ssh_{}_cipher_count,ssh_{}_mac_count,", side, side)?;
+            write!(writer, "ssh_{}_compression_count,", side)?;
+            write!(writer, "ssh_{}_hassh,", side)?;
+            write!(writer, "ssh_{}_encrypted_chunks,", side)?;
+            write!(writer, "ssh_{}_encrypted_bytes,", side)?;
+            write!(writer, "ssh_{}_encrypted_size_mean,", side)?;
+        }
+        write!(writer, "ssh_auth_failure_count,ssh_auth_success,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        Self::write_side(writer, &self.client, true)?;
+        Self::write_side(writer, &self.server, false)?;
+        // The first reply accepts the authentication service
+        write!(writer, "{},", self.auth_reply_count.saturating_sub(1))?;
+        write!(writer, "{},", u8::from(self.auth_success))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::test_flow::{TestFlow, ACK, PSH, SYN};
+
+    /// NEWKEYS packet with its length and padding
+    const NEWKEYS_PACKET: [u8; 12] = [0, 0, 0, 8, 6, 21, 0, 0, 0, 0, 0, 0];
+
+    /// SSH session with the given sizes of the encrypted chunks of the client
+    /// and the server, each one sent on its own segment
+    fn session(chunks: &[(bool, usize)]) -> TestFlow<Ssh> {
+        let mut v = TestFlow::<Ssh>::new(StatSettings::default(), 40000, SSH_PORT);
+        v.tcp(true, 0, SYN, 100, 0, b"")
+            .tcp(false, 1000, SYN | ACK, 500, 101, b"")
+            .tcp(true, 2000, ACK, 101, 501, b"");
+
+        let (mut client_seq, mut server_seq) = (101, 501);
+        let mut micros = 3000;
+        let mut send = |v: &mut TestFlow<Ssh>, is_client: bool, payload: &[u8]| {
+            let len = payload.len() as u32;
+            if is_client {
+                v.tcp(true, micros, PSH | ACK, client_seq, server_seq, payload);
+                client_seq += len;
+            } else {
+                v.tcp(false, micros, PSH | ACK, server_seq, client_seq, payload);
+                server_seq += len;
+            }
+            micros += 1000;
+        };
+        for is_client in [true, false] {
+            let mut payload = b"SSH-2.0-OpenSSH_9.6\r\n".to_vec();
+            payload.extend_from_slice(&NEWKEYS_PACKET);
+            send(&mut v, is_client, &payload);
+        }
+        for (is_client, len) in chunks {
+            send(&mut v, *is_client, &vec![0x5A; *len]);
+        }
+        v
+    }
+
+    #[test]
+    fn test_auth_failure() {
+        // Service request and acceptance, the none probe and two rejected
+        // passwords
+        let v = session(&[
+            (true, 44),
+            (false, 44),
+            (true, 60),
+            (false, 60),
+            (true, 80),
+            (false, 60),
+            (true, 80),
+            (false, 60),
+        ]);
+
+        assert_eq!(v.value("ssh_client_encrypted_chunks"), "4");
+        assert_eq!(v.value("ssh_server_encrypted_bytes"), "224");
+        assert_eq!(v.value("ssh_auth_failure_count"), "3");
+        assert_eq!(v.value("ssh_auth_success"), "0");
+    }
+
+    #[test]
+    fn test_auth_success() {
+        // Service request and acceptance, the none probe and an accepted
+        // password, whose small success reply is followed by the host keys
+        let v = session(&[
+            (true, 44),
+            (false, 44),
+            (true, 60),
+            (false, 60),
+            (true, 80),
+            (false, 28),
+            (false, 500),
+        ]);
+
+        assert_eq!(v.value("ssh_auth_failure_count"), "1");
+        assert_eq!(v.value("ssh_auth_success"), "1");
+
+        // Without the none probe there are no failures
+        let v = session(&[
+            (true, 44),
+            (false, 44),
+            (true, 80),
+            (false, 28),
+            (false, 500),
+        ]);
+
+        assert_eq!(v.value("ssh_auth_failure_count"), "0");
+        assert_eq!(v.value("ssh_auth_success"), "1");
+    }
+}
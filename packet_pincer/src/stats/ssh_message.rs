@@ -0,0 +1,289 @@
+use super::digest::{md5, to_hex};
+
+/// Port of SSH
+pub const SSH_PORT: u16 = 22;
+
+/// Largest identification string or line before it, with its line ending
+const MAX_LINE_LEN: usize = 255;
+
+/// Largest number of lines the server can send before its identification
+/// string
+const MAX_LINES_BEFORE_IDENTIFICATION: usize = 8;
+
+/// Largest packet length that implementations must accept
+const MAX_PACKET_LEN: usize = 35000;
+
+pub const KEXINIT: u8 = 20;
+pub const NEWKEYS: u8 = 21;
+
+/// Positions of the name-lists of a KEXINIT message
+const KEX: usize = 0;
+const HOST_KEY: usize = 1;
+const CIPHER_CLIENT_TO_SERVER: usize = 2;
+const MAC_CLIENT_TO_SERVER: usize = 4;
+const COMPRESSION_CLIENT_TO_SERVER: usize = 6;
+const NAME_LIST_COUNT: usize = 10;
+
+/// Algorithms offered by a KEXINIT message
+#[derive(Debug, Clone)]
+pub struct KexInit {
+    name_lists: Vec<Vec<u8>>,
+}
+
+impl KexInit {
+    pub fn parse(payload: &[u8]) -> Option<KexInit> {
+        if *payload.first()? != KEXINIT {
+            return None;
+        }
+        // Skip the message type and the cookie
+        let mut rest = payload.get(17..)?;
+        let mut name_lists = Vec::with_capacity(NAME_LIST_COUNT);
+        for _ in 0..NAME_LIST_COUNT {
+            let len = rest.get(..4)?;
+            let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+            name_lists.push(rest.get(4..4 + len)?.to_vec());
+            rest = &rest[4 + len..];
+        }
+        Some(KexInit { name_lists })
+    }
+
+    /// Name-list of the given position, using the one of the direction of
+    /// the sender for those that have one per direction
+    fn name_list(&self, position: usize, is_client: bool) -> &[u8] {
+        if position >= CIPHER_CLIENT_TO_SERVER && !is_client {
+            &self.name_lists[position + 1]
+        } else {
+            &self.name_lists[position]
+        }
+    }
+
+    /// Number of key exchange, host key, cipher, MAC and compression
+    /// algorithms offered by the client or server
+    pub fn algorithm_counts(&self, is_client: bool) -> [usize; 5] {
+        [
+            KEX,
+            HOST_KEY,
+            CIPHER_CLIENT_TO_SERVER,
+            MAC_CLIENT_TO_SERVER,
+            COMPRESSION_CLIENT_TO_SERVER,
+        ]
+        .map(|position| {
+            let name_list = self.name_list(position, is_client);
+            if name_list.is_empty() {
+                0
+            } else {
+                name_list.split(|byte| *byte == b',').count()
+            }
+        })
+    }
+
+    /// HASSH fingerprint of the client or HASSHServer fingerprint of the
+    /// server
+    pub fn hassh_string(&self, is_client: bool) -> String {
+        [
+            KEX,
+            CIPHER_CLIENT_TO_SERVER,
+            MAC_CLIENT_TO_SERVER,
+            COMPRESSION_CLIENT_TO_SERVER,
+        ]
+        .map(|position| String::from_utf8_lossy(self.name_list(position, is_client)))
+        .join(";")
+    }
+
+    pub fn hassh(&self, is_client: bool) -> String {
+        to_hex(&md5(self.hassh_string(is_client).as_bytes()))
+    }
+}
+
+/// Part of a direction of a SSH stream read by a `SshStreamReader`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SshEvent<'a> {
+    /// Identification string, without its line ending
+    Identification(&'a [u8]),
+    /// Payload of a packet sent before NEWKEYS
+    Message(&'a [u8]),
+    /// Number of encrypted bytes of a chunk of the stream after NEWKEYS. The
+    /// packet lengths are encrypted, so the chunk is not split in packets
+    Encrypted(usize),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Identification,
+    Packets,
+    Encrypted,
+}
+
+/// Reader of a direction of a SSH stream, up to the NEWKEYS message, from
+/// which the packets are encrypted
+#[derive(Debug, Clone, Default)]
+pub struct SshStreamReader {
+    buffer: Vec<u8>,
+    state: State,
+    skipped_lines: usize,
+    /// Whether the stream is not SSH, a packet was malformed or bytes were
+    /// missed before the encryption, so nothing more can be read
+    done: bool,
+}
+
+impl SshStreamReader {
+    /// Include the next bytes of the stream and call `consume` with each
+    /// part of the stream they complete
+    pub fn include<F: FnMut(SshEvent)>(&mut self, gap: bool, data: &[u8], mut consume: F) {
+        // Sizes of the encrypted chunks can still be told after a gap
+        if gap && self.state != State::Encrypted {
+            self.finish();
+        }
+        if self.done {
+            return;
+        }
+        if self.state == State::Encrypted {
+            if !data.is_empty() {
+                consume(SshEvent::Encrypted(data.len()));
+            }
+            return;
+        }
+
+        self.buffer.extend_from_slice(data);
+        let mut start = 0;
+        while !self.done && self.state != State::Encrypted {
+            let rest = &self.buffer[start..];
+            match self.state {
+                State::Identification => {
+                    let Some(line_end) = rest.iter().position(|byte| *byte == b'\n') else {
+                        if rest.len() > MAX_LINE_LEN {
+                            self.finish();
+                        }
+                        break;
+                    };
+                    let line = &rest[..line_end];
+                    let line = line.strip_suffix(b"\r").unwrap_or(line);
+                    start += line_end + 1;
+
+                    if line.starts_with(b"SSH-") {
+                        consume(SshEvent::Identification(line));
+                        self.state = State::Packets;
+                    } else {
+                        self.skipped_lines += 1;
+                        if self.skipped_lines > MAX_LINES_BEFORE_IDENTIFICATION {
+                            self.finish();
+                        }
+                    }
+                }
+                State::Packets => {
+                    let Some(header) = rest.get(..5) else {
+                        break;
+                    };
+                    let packet_len =
+                        u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+                    let padding_len = usize::from(header[4]);
+                    // Payloads have at least the message type
+                    if packet_len > MAX_PACKET_LEN || padding_len + 2 > packet_len {
+                        self.finish();
+                        break;
+                    }
+                    let Some(packet) = rest.get(..4 + packet_len) else {
+                        break;
+                    };
+                    let payload = &packet[5..4 + packet_len - padding_len];
+                    start += 4 + packet_len;
+
+                    consume(SshEvent::Message(payload));
+                    if payload[0] == NEWKEYS {
+                        self.state = State::Encrypted;
+                        if start != self.buffer.len() {
+                            consume(SshEvent::Encrypted(self.buffer.len() - start));
+                        }
+                    }
+                }
+                State::Encrypted => {}
+            }
+        }
+
+        if self.state == State::Encrypted {
+            self.buffer = Vec::new();
+        } else if !self.done {
+            self.buffer.drain(..start);
+        }
+    }
+
+    fn finish(&mut self) {
+        self.done = true;
+        self.buffer = Vec::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(payload: &[u8]) -> Vec<u8> {
+        let padding_len = 8 - (5 + payload.len()) % 8 + 4;
+        let packet_len = (1 + payload.len() + padding_len) as u32;
+        let mut packet = packet_len.to_be_bytes().to_vec();
+        packet.push(padding_len as u8);
+        packet.extend_from_slice(payload);
+        packet.resize(packet.len() + padding_len, 0);
+        packet
+    }
+
+    #[test]
+    fn test_stream_reader() {
+        let mut payload = vec![KEXINIT];
+        payload.resize(17, 0xAA);
+        for name_list in [
+            &b"curve25519-sha256,diffie-hellman-group14-sha256"[..],
+            b"ssh-ed25519",
+            b"aes128-ctr",
+            b"aes128-ctr",
+            b"hmac-sha2-256",
+            b"hmac-sha2-256",
+            b"none",
+            b"none",
+            b"",
+            b"",
+        ] {
+            payload.extend_from_slice(&(name_list.len() as u32).to_be_bytes());
+            payload.extend_from_slice(name_list);
+        }
+        payload.extend_from_slice(&[0, 0, 0, 0, 0]);
+
+        let mut stream = b"SSH-2.0-OpenSSH_9.6\r\n".to_vec();
+        stream.extend_from_slice(&packet(&payload));
+        stream.extend_from_slice(&packet(&[NEWKEYS]));
+        stream.extend_from_slice(&[0x5A; 52]);
+
+        let mut v = SshStreamReader::default();
+        let mut events = Vec::new();
+        for part in stream.chunks(7) {
+            v.include(false, part, |event| {
+                events.push(match event {
+                    SshEvent::Identification(line) => line.len(),
+                    SshEvent::Message(payload) => usize::from(payload[0]),
+                    SshEvent::Encrypted(len) => 1000 + len,
+                })
+            });
+        }
+        v.include(true, &[0x5A; 10], |event| {
+            assert_eq!(event, SshEvent::Encrypted(10))
+        });
+        assert_eq!(
+            events[..3],
+            [19, usize::from(KEXINIT), usize::from(NEWKEYS)]
+        );
+        let encrypted_len: usize = events[3..].iter().map(|len| len - 1000).sum();
+        assert_eq!(encrypted_len, 52);
+
+        let kex_init = KexInit::parse(&payload).unwrap();
+        assert_eq!(kex_init.algorithm_counts(true), [2, 1, 1, 1, 1]);
+        assert_eq!(
+            kex_init.hassh_string(false),
+            "curve25519-sha256,diffie-hellman-group14-sha256;aes128-ctr;hmac-sha2-256;none"
+        );
+
+        let mut v = SshStreamReader::default();
+        v.include(false, &[0x16; 300], |_| panic!());
+        assert!(v.done);
+    }
+}
@@ -0,0 +1,488 @@
+use super::{log_scaled_bin_edges, media_sessions::MediaSessions, AppSignatures, OsSignatures};
+use chrono::TimeDelta;
+use std::{fmt::Display, rc::Rc, str::FromStr};
+
+/// Named group of features that can be selected to be computed on each flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FeatureGroup {
+    /// Transport protocols seen on the flow
+    Protocols,
+    /// Packet counts and rates
+    PacketCount,
+    /// Packet sizes and byte rates
+    ByteCount,
+    /// Inter arrival times between packets
+    Interarrival,
+    /// TCP flag counts
+    TcpFlags,
+    /// Transport header and payload sizes
+    Transport,
+    /// Active and idle periods
+    Activity,
+    /// Sizes, inter arrival times and TCP flags of the first packets
+    PacketSequence,
+    /// Distribution of the payload bytes
+    PayloadContent,
+    /// The features of CICFlowMeter, selected by its output profile
+    CicFlowMeter,
+    /// Histograms of the packet sizes and inter arrival times
+    Histograms,
+    /// Bulk transfers and subflows
+    BulkTransfer,
+    /// RTTs, retransmissions and window events of the TCP sequence space
+    TcpAnalysis,
+    /// Handshake latencies and connection state of TCP flows
+    TcpConnection,
+    /// TCP options of the handshake and counts of options
+    TcpOptions,
+    /// TTL, IP ID, DF flag, DSCP, ECN and flow label of the IP headers
+    IpHeader,
+    /// Operating systems of the client and server
+    OsFingerprint,
+    /// Queries, responses, names and records of DNS messages
+    Dns,
+    /// Hellos, certificates and fingerprints of TLS handshakes
+    Tls,
+    /// Requests and responses of HTTP/1.x
+    Http,
+    /// Connections, publications and subscriptions of MQTT
+    Mqtt,
+    /// Methods, responses, observe usage and tokens of CoAP
+    Coap,
+    /// Function codes, exceptions, units and quantities of Modbus/TCP
+    Modbus,
+    /// Function codes and object groups of DNP3
+    Dnp3,
+    /// Services, commands and amplification factor of UDP reflection
+    /// protocols like NTP, SNMP or Memcached
+    Reflection,
+    /// Identification strings, key exchange algorithms, HASSH fingerprints
+    /// and the encrypted sizes of SSH
+    Ssh,
+    /// Methods, response classes and Call-IDs of SIP
+    Sip,
+    /// Payload types, loss, jitter and SSRC changes of RTP, linked to the
+    /// SIP dialog that negotiated the media
+    Rtp,
+    /// Application protocol identified from the first payloads
+    AppProtocol,
+}
+
+impl FeatureGroup {
+    /// All the available feature groups, in the order they are written
+    pub const ALL: [FeatureGroup; 29] = [
+        FeatureGroup::Protocols,
+        FeatureGroup::PacketCount,
+        FeatureGroup::ByteCount,
+        FeatureGroup::Interarrival,
+        FeatureGroup::TcpFlags,
+        FeatureGroup::Transport,
+        FeatureGroup::Activity,
+        FeatureGroup::PacketSequence,
+        FeatureGroup::PayloadContent,
+        FeatureGroup::CicFlowMeter,
+        FeatureGroup::Histograms,
+        FeatureGroup::BulkTransfer,
+        FeatureGroup::TcpAnalysis,
+        FeatureGroup::TcpConnection,
+        FeatureGroup::TcpOptions,
+        FeatureGroup::IpHeader,
+        FeatureGroup::OsFingerprint,
+        FeatureGroup::Dns,
+        FeatureGroup::Tls,
+        FeatureGroup::Http,
+        FeatureGroup::Mqtt,
+        FeatureGroup::Coap,
+        FeatureGroup::Modbus,
+        FeatureGroup::Dnp3,
+        FeatureGroup::Reflection,
+        FeatureGroup::Ssh,
+        FeatureGroup::Sip,
+        FeatureGroup::Rtp,
+        FeatureGroup::AppProtocol,
+    ];
+
+    /// The feature groups computed when there is no explicit selection
+    pub const DEFAULT: [FeatureGroup; 7] = [
+        FeatureGroup::Protocols,
+        FeatureGroup::PacketCount,
+        FeatureGroup::ByteCount,
+        FeatureGroup::Interarrival,
+        FeatureGroup::TcpFlags,
+        FeatureGroup::Transport,
+        FeatureGroup::Activity,
+    ];
+
+    /// The name used to select the feature group
+    pub fn name(&self) -> &'static str {
+        match self {
+            FeatureGroup::Protocols => "protocols",
+            FeatureGroup::PacketCount => "packet_count",
+            FeatureGroup::ByteCount => "byte_count",
+            FeatureGroup::Interarrival => "interarrival",
+            FeatureGroup::TcpFlags => "tcp_flags",
+            FeatureGroup::Transport => "transport",
+            FeatureGroup::Activity => "activity",
+            FeatureGroup::PacketSequence => "packet_sequence",
+            FeatureGroup::PayloadContent => "payload_content",
+            FeatureGroup::CicFlowMeter => "cicflowmeter",
+            FeatureGroup::Histograms => "histograms",
+            FeatureGroup::BulkTransfer => "bulk_transfer",
+            FeatureGroup::TcpAnalysis => "tcp_analysis",
+            FeatureGroup::TcpConnection => "tcp_connection",
+            FeatureGroup::TcpOptions => "tcp_options",
+            FeatureGroup::IpHeader => "ip_header",
+            FeatureGroup::OsFingerprint => "os_fingerprint",
+            FeatureGroup::Dns => "dns",
+            FeatureGroup::Tls => "tls",
+            FeatureGroup::Http => "http",
+            FeatureGroup::Mqtt => "mqtt",
+            FeatureGroup::Coap => "coap",
+            FeatureGroup::Modbus => "modbus",
+            FeatureGroup::Dnp3 => "dnp3",
+            FeatureGroup::Reflection => "reflection",
+            FeatureGroup::Ssh => "ssh",
+            FeatureGroup::Sip => "sip",
+            FeatureGroup::Rtp => "rtp",
+            FeatureGroup::AppProtocol => "app_protocol",
+        }
+    }
+}
+
+impl Display for FeatureGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for FeatureGroup {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FeatureGroup::ALL
+            .into_iter()
+            .find(|group| group.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = FeatureGroup::ALL.iter().map(|g| g.name()).collect();
+                format!(
+                    "unknown feature group '{}', expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Column set and semantics of the records written for each flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputProfile {
+    /// The columns of the selected feature groups
+    Native,
+    /// The columns written by CICFlowMeter, as found on the CIC-IDS and
+    /// CIC-DDoS datasets
+    CicFlowMeter,
+}
+
+impl OutputProfile {
+    /// All the available output profiles
+    pub const ALL: [OutputProfile; 2] = [OutputProfile::Native, OutputProfile::CicFlowMeter];
+
+    /// The name used to select the output profile
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputProfile::Native => "native",
+            OutputProfile::CicFlowMeter => "cicflowmeter",
+        }
+    }
+}
+
+impl Display for OutputProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for OutputProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OutputProfile::ALL
+            .into_iter()
+            .find(|profile| profile.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = OutputProfile::ALL.iter().map(|p| p.name()).collect();
+                format!(
+                    "unknown output profile '{}', expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Settings of the statistics computed on each flow
+#[derive(Debug, Clone)]
+pub struct StatSettings {
+    feature_groups: Vec<FeatureGroup>,
+    output_profile: OutputProfile,
+    /// Number of first packets of the packet sequences
+    sequence_length: usize,
+    /// Sorted percentiles estimated by the stats that support them
+    percentiles: Rc<[u8]>,
+    /// Whether the skewness and kurtosis are written by the stats that
+    /// support them
+    higher_moments: bool,
+    /// Sorted edges of the bins of the packet sizes histograms
+    packet_bytes_bin_edges: Rc<[u64]>,
+    /// Sorted edges of the bins of the inter arrival times histograms, in
+    /// microseconds
+    inter_arrival_bin_edges: Rc<[u64]>,
+    /// Minimum number of packets with payload on a bulk
+    bulk_min_packets: u32,
+    /// Maximum gap between the packets of a bulk
+    bulk_timeout: TimeDelta,
+    /// Maximum gap between the packets of a subflow
+    subflow_timeout: TimeDelta,
+    /// Signatures used to guess the operating systems
+    os_signatures: Rc<OsSignatures>,
+    /// Maximum bytes received out of order buffered on each TCP flow
+    stream_buffer_limit: usize,
+    /// Media endpoints announced by the SIP flows, read by the RTP flows
+    media_sessions: Rc<MediaSessions>,
+    /// Signatures used to identify the application protocols
+    app_signatures: Rc<AppSignatures>,
+}
+
+impl StatSettings {
+    /// Create the settings to compute the given feature groups. If empty, the
+    /// default feature groups are computed
+    pub fn new(feature_groups: &[FeatureGroup]) -> StatSettings {
+        let feature_groups = if feature_groups.is_empty() {
+            FeatureGroup::DEFAULT.to_vec()
+        } else {
+            feature_groups.to_vec()
+        };
+
+        StatSettings {
+            feature_groups,
+            output_profile: OutputProfile::Native,
+            sequence_length: 20,
+            percentiles: Rc::new([]),
+            higher_moments: false,
+            packet_bytes_bin_edges: log_scaled_bin_edges(64, 1024, 5).into(),
+            inter_arrival_bin_edges: log_scaled_bin_edges(100, 1_000_000, 5).into(),
+            bulk_min_packets: 4,
+            bulk_timeout: TimeDelta::seconds(1),
+            subflow_timeout: TimeDelta::seconds(1),
+            os_signatures: Rc::new(OsSignatures::builtin()),
+            stream_buffer_limit: 65536,
+            media_sessions: Rc::new(MediaSessions::default()),
+            app_signatures: Rc::new(AppSignatures::builtin()),
+        }
+    }
+
+    /// Set the output profile of the records. Profiles other than the native
+    /// one replace the selected feature groups with the ones they need
+    pub fn with_output_profile(mut self, output_profile: OutputProfile) -> StatSettings {
+        if output_profile == OutputProfile::CicFlowMeter {
+            self.feature_groups = vec![FeatureGroup::CicFlowMeter];
+        }
+        self.output_profile = output_profile;
+        self
+    }
+
+    /// Check if the given feature group should be computed
+    pub fn is_selected(&self, feature_group: FeatureGroup) -> bool {
+        self.feature_groups.contains(&feature_group)
+    }
+
+    /// Get the output profile of the records
+    pub fn output_profile(&self) -> OutputProfile {
+        self.output_profile
+    }
+
+    /// Set the number of first packets of each flow written by the packet
+    /// sequence features. By default, 20 packets
+    pub fn with_sequence_length(mut self, sequence_length: usize) -> StatSettings {
+        self.sequence_length = sequence_length;
+        self
+    }
+
+    /// Get the number of first packets of the packet sequences
+    pub fn sequence_length(&self) -> usize {
+        self.sequence_length
+    }
+
+    /// Set the percentiles, between 0 and 100, estimated on the packet sizes
+    /// and inter arrival times. If empty, no percentiles are estimated
+    pub fn with_percentiles(mut self, mut percentiles: Vec<u8>) -> StatSettings {
+        percentiles.retain(|percentile| *percentile <= 100);
+        percentiles.sort_unstable();
+        percentiles.dedup();
+
+        self.percentiles = percentiles.into();
+        self
+    }
+
+    /// Get the percentiles estimated on the packet sizes and inter arrival
+    /// times
+    pub fn percentiles(&self) -> &[u8] {
+        &self.percentiles
+    }
+
+    /// Set whether the skewness and kurtosis of the packet sizes and inter
+    /// arrival times are written
+    pub fn with_higher_moments(mut self, higher_moments: bool) -> StatSettings {
+        self.higher_moments = higher_moments;
+        self
+    }
+
+    /// Check if the skewness and kurtosis of the packet sizes and inter
+    /// arrival times are written
+    pub fn higher_moments(&self) -> bool {
+        self.higher_moments
+    }
+
+    /// Set the edges of the bins of the packet sizes histograms, in bytes.
+    /// The first bin starts at 0 and the last one has no upper limit. By
+    /// default, the edges are 64, 128, 256, 512 and 1024
+    pub fn with_packet_bytes_bin_edges(mut self, mut edges: Vec<u64>) -> StatSettings {
+        edges.sort_unstable();
+        edges.dedup();
+
+        self.packet_bytes_bin_edges = edges.into();
+        self
+    }
+
+    /// Get the edges of the bins of the packet sizes histograms
+    pub fn packet_bytes_bin_edges(&self) -> &[u64] {
+        &self.packet_bytes_bin_edges
+    }
+
+    /// Set the edges of the bins of the inter arrival times histograms, in
+    /// microseconds. The first bin starts at 0 and the last one has no upper
+    /// limit. By default, the edges are 100, 1000, 10000, 100000 and 1000000
+    pub fn with_inter_arrival_bin_edges(mut self, mut edges: Vec<u64>) -> StatSettings {
+        edges.sort_unstable();
+        edges.dedup();
+
+        self.inter_arrival_bin_edges = edges.into();
+        self
+    }
+
+    /// Get the edges of the bins of the inter arrival times histograms
+    pub fn inter_arrival_bin_edges(&self) -> &[u64] {
+        &self.inter_arrival_bin_edges
+    }
+
+    /// Set the thresholds of the bulk transfers. A bulk is a sequence of at
+    /// least `min_packets` packets with payload on the same direction, without
+    /// gaps longer than `timeout` between them. A `min_packets` of 0 is the
+    /// same as 1, where every packet with payload is part of a bulk. By
+    /// default, 4 packets and 1 second
+    pub fn with_bulk_thresholds(mut self, min_packets: u32, timeout: TimeDelta) -> StatSettings {
+        self.bulk_min_packets = min_packets;
+        self.bulk_timeout = timeout;
+        self
+    }
+
+    /// Get the minimum number of packets with payload on a bulk
+    pub fn bulk_min_packets(&self) -> u32 {
+        self.bulk_min_packets
+    }
+
+    /// Get the maximum gap between the packets of a bulk
+    pub fn bulk_timeout(&self) -> TimeDelta {
+        self.bulk_timeout
+    }
+
+    /// Set the maximum gap between the packets of a subflow. By default, 1
+    /// second
+    pub fn with_subflow_timeout(mut self, timeout: TimeDelta) -> StatSettings {
+        self.subflow_timeout = timeout;
+        self
+    }
+
+    /// Get the maximum gap between the packets of a subflow
+    pub fn subflow_timeout(&self) -> TimeDelta {
+        self.subflow_timeout
+    }
+
+    /// Set the signatures used to guess the operating systems of the flow
+    /// endpoints. By default, the bundled p0f signatures are used
+    pub fn with_os_signatures(mut self, os_signatures: OsSignatures) -> StatSettings {
+        self.os_signatures = Rc::new(os_signatures);
+        self
+    }
+
+    /// Get the signatures used to guess the operating systems
+    pub fn os_signatures(&self) -> &OsSignatures {
+        &self.os_signatures
+    }
+
+    /// Set the maximum bytes received out of order buffered on each TCP flow
+    /// while reassembling its streams. When exceeded, the missing bytes are
+    /// skipped. By default, 65536 bytes
+    pub fn with_stream_buffer_limit(mut self, stream_buffer_limit: usize) -> StatSettings {
+        self.stream_buffer_limit = stream_buffer_limit;
+        self
+    }
+
+    /// Get the maximum bytes received out of order buffered on each TCP flow
+    pub fn stream_buffer_limit(&self) -> usize {
+        self.stream_buffer_limit
+    }
+
+    /// Set the signatures used to identify the application protocol of the
+    /// flows. By default, the built-in signatures are used
+    pub fn with_app_signatures(mut self, app_signatures: AppSignatures) -> StatSettings {
+        self.app_signatures = Rc::new(app_signatures);
+        self
+    }
+
+    /// Get the signatures used to identify the application protocols
+    pub fn app_signatures(&self) -> &AppSignatures {
+        &self.app_signatures
+    }
+
+    /// Get the percentiles estimated, shared to be stored on each flow
+    pub(crate) fn shared_percentiles(&self) -> Rc<[u8]> {
+        Rc::clone(&self.percentiles)
+    }
+
+    /// Get the edges of the packet sizes bins, shared to be stored on each
+    /// flow
+    pub(crate) fn shared_packet_bytes_bin_edges(&self) -> Rc<[u64]> {
+        Rc::clone(&self.packet_bytes_bin_edges)
+    }
+
+    /// Get the edges of the inter arrival times bins, shared to be stored on
+    /// each flow
+    pub(crate) fn shared_inter_arrival_bin_edges(&self) -> Rc<[u64]> {
+        Rc::clone(&self.inter_arrival_bin_edges)
+    }
+
+    /// Get the signatures of the operating systems, shared to be stored on
+    /// each flow
+    pub(crate) fn shared_os_signatures(&self) -> Rc<OsSignatures> {
+        Rc::clone(&self.os_signatures)
+    }
+
+    /// Get the media endpoints announced by the SIP flows, shared to be
+    /// stored on each flow
+    pub(crate) fn shared_media_sessions(&self) -> Rc<MediaSessions> {
+        Rc::clone(&self.media_sessions)
+    }
+
+    /// Get the signatures of the application protocols, shared to be stored
+    /// on each flow
+    pub(crate) fn shared_app_signatures(&self) -> Rc<AppSignatures> {
+        Rc::clone(&self.app_signatures)
+    }
+}
+
+impl Default for StatSettings {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
@@ -0,0 +1,147 @@
+/// Result of looking for a frame at the start of the buffered bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameLength {
+    /// More bytes are needed to know the length
+    Incomplete,
+    /// The bytes are not a valid frame of the protocol
+    Invalid,
+    /// Length of the frame, including its header
+    Length(usize),
+}
+
+/// Splitter of the length delimited frames of a direction of a TCP stream
+#[derive(Debug, Clone, Default)]
+pub struct FrameReader {
+    buffer: Vec<u8>,
+    /// Whether an invalid frame was found or bytes were missed, so the frame
+    /// boundaries are unknown
+    done: bool,
+}
+
+impl FrameReader {
+    /// Include the next bytes of the stream and call `consume` with each
+    /// frame they complete. `frame_len` finds the length of the frame at the
+    /// start of the given bytes. Reading stops on invalid frames or when
+    /// `consume` returns false
+    pub fn include<L, F>(&mut self, gap: bool, data: &[u8], frame_len: L, mut consume: F)
+    where
+        L: Fn(&[u8]) -> FrameLength,
+        F: FnMut(&[u8]) -> bool,
+    {
+        if gap {
+            self.finish();
+        }
+        if self.done {
+            return;
+        }
+
+        self.buffer.extend_from_slice(data);
+        let mut start = 0;
+        loop {
+            match frame_len(&self.buffer[start..]) {
+                FrameLength::Incomplete => break,
+                FrameLength::Invalid => {
+                    self.finish();
+                    return;
+                }
+                FrameLength::Length(len) => {
+                    let Some(frame) = self.buffer.get(start..start + len) else {
+                        break;
+                    };
+                    if !consume(frame) {
+                        self.finish();
+                        return;
+                    }
+                    start += len;
+                }
+            }
+        }
+        self.buffer.drain(..start);
+    }
+
+    fn finish(&mut self) {
+        self.done = true;
+        self.buffer = Vec::new();
+    }
+}
+
+/// Stream readers of the forward and backward directions of a flow
+#[derive(Debug, Clone, Default)]
+pub struct Directional<R> {
+    forward: R,
+    backward: R,
+}
+
+impl<R: Default> Directional<R> {
+    /// The reader of the direction
+    pub fn get(&self, is_forward: bool) -> &R {
+        if is_forward {
+            &self.forward
+        } else {
+            &self.backward
+        }
+    }
+
+    fn get_mut(&mut self, is_forward: bool) -> &mut R {
+        if is_forward {
+            &mut self.forward
+        } else {
+            &mut self.backward
+        }
+    }
+
+    /// Call `f` with the stat and the reader of the direction, which is taken
+    /// out of the stat while `f` runs so both can be borrowed mutably
+    pub fn with<S, T, F>(
+        stat: &mut S,
+        readers: fn(&mut S) -> &mut Self,
+        is_forward: bool,
+        f: F,
+    ) -> T
+    where
+        F: FnOnce(&mut S, &mut R) -> T,
+    {
+        let mut reader = std::mem::take(readers(stat).get_mut(is_forward));
+        let result = f(stat, &mut reader);
+        *readers(stat).get_mut(is_forward) = reader;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames prefixed with their length on a byte, with 0 being invalid
+    fn frame_len(bytes: &[u8]) -> FrameLength {
+        match bytes.first() {
+            None => FrameLength::Incomplete,
+            Some(0) => FrameLength::Invalid,
+            Some(len) => FrameLength::Length(1 + usize::from(*len)),
+        }
+    }
+
+    #[test]
+    fn test_frame_reader() {
+        let mut v = FrameReader::default();
+        let mut frames = Vec::new();
+
+        v.include(false, &[2, 1], frame_len, |f| {
+            frames.push(f.to_vec());
+            true
+        });
+        assert!(frames.is_empty());
+        v.include(false, &[2, 1, 3], frame_len, |f| {
+            frames.push(f.to_vec());
+            true
+        });
+        assert_eq!(frames, vec![vec![2, 1, 2], vec![1, 3]]);
+
+        v.include(false, &[0, 1, 1], frame_len, |_| true);
+        assert!(v.done);
+
+        let mut v = FrameReader::default();
+        v.include(true, &[1, 1], frame_len, |_| panic!());
+        assert!(v.done);
+    }
+}
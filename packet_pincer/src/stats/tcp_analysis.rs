@@ -0,0 +1,149 @@
+use super::{
+    tcp_sequence::{DirectionSequence, TcpSegment, TcpSequenceTracker},
+    FlowStat, FlowTimes, StatSettings,
+};
+use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
+use std::io::{BufWriter, Error, Write};
+
+/// Analysis of the TCP sequence space of each direction: handshake and
+/// segment RTTs, retransmissions, out of order segments, duplicate ACKs and
+/// window events. Non TCP flows have all the values at zero
+#[derive(Debug, Clone)]
+pub struct TcpAnalysis {
+    tracker: TcpSequenceTracker,
+}
+
+impl TcpAnalysis {
+    fn include_packet(
+        &mut self,
+        is_forward: bool,
+        flow_times: &FlowTimes,
+        sliced_packet: &etherparse::SlicedPacket,
+    ) {
+        if let Some(etherparse::TransportSlice::Tcp(tcp_slice)) = &sliced_packet.transport {
+            let segment =
+                TcpSegment::from_slice(is_forward, flow_times.last_packet_time, tcp_slice);
+            self.tracker.include(&segment);
+        }
+    }
+
+    fn write_direction_csv_value<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        direction: &DirectionSequence,
+    ) -> Result<(), Error> {
+        let rtt = direction.rtt();
+        write!(
+            writer,
+            "{:.9},{:.9},{:.9},",
+            rtt.current_min().unwrap_or(0) as f64 / 1_000_000.0,
+            rtt.current_mean() / 1_000_000.0,
+            rtt.current_max().unwrap_or(0) as f64 / 1_000_000.0
+        )?;
+        write!(writer, "{},", rtt.current_count())?;
+        write!(writer, "{},", direction.retransmission_count())?;
+        write!(writer, "{},", direction.out_of_order_count())?;
+        write!(writer, "{},", direction.duplicate_ack_count())?;
+        write!(writer, "{},", direction.zero_window_count())?;
+        write!(writer, "{},", direction.window_full_count())?;
+        Ok(())
+    }
+}
+
+impl FlowStat for TcpAnalysis {
+    fn from_packet(
+        _settings: &StatSettings,
+        _identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        let mut tcp_analysis = TcpAnalysis {
+            tracker: TcpSequenceTracker::new(),
+        };
+
+        tcp_analysis.include_packet(true, flow_times, sliced_packet);
+        tcp_analysis
+    }
+    fn include(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        let is_forward = identifier.is_forward(sliced_packet);
+
+        self.include_packet(is_forward, flow_times, sliced_packet);
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        write!(writer, "tcp_handshake_rtt,")?;
+        for direction in ["forward", "backward"] {
+            write!(writer, "{}_tcp_rtt_min,", direction)?;
+            write!(writer, "{}_tcp_rtt_mean,", direction)?;
+            write!(writer, "{}_tcp_rtt_max,", direction)?;
+            write!(writer, "{}_tcp_rtt_samples,", direction)?;
+            write!(writer, "{}_tcp_retransmission_count,", direction)?;
+            write!(writer, "{}_tcp_out_of_order_count,", direction)?;
+            write!(writer, "{}_tcp_duplicate_ack_count,", direction)?;
+            write!(writer, "{}_tcp_zero_window_count,", direction)?;
+            write!(writer, "{}_tcp_window_full_count,", direction)?;
+        }
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        let handshake_rtt = self
+            .tracker
+            .handshake_rtt()
+            .and_then(|rtt| rtt.num_microseconds())
+            .unwrap_or(0);
+        write!(writer, "{:.9},", handshake_rtt as f64 / 1_000_000.0)?;
+        Self::write_direction_csv_value(writer, self.tracker.forward())?;
+        Self::write_direction_csv_value(writer, self.tracker.backward())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::test_flow::{TestFlow, ACK, PSH, SYN};
+
+    #[test]
+    fn test_sequence_events() {
+        let mut v = TestFlow::<TcpAnalysis>::new(StatSettings::default(), 40000, 80);
+        v.tcp(true, 0, SYN, 100, 0, b"")
+            .tcp(false, 10_000, SYN | ACK, 500, 101, b"")
+            .tcp(true, 20_000, ACK, 101, 501, b"")
+            .tcp(true, 30_000, PSH | ACK, 101, 501, &[0; 100]);
+        // The segment of 201 arrives 1 millisecond after the one of 301,
+        // faster than the RTT, so it was reordered rather than lost
+        v.tcp(true, 31_000, PSH | ACK, 301, 501, &[0; 100]).tcp(
+            true,
+            32_000,
+            PSH | ACK,
+            201,
+            501,
+            &[0; 100],
+        );
+        v.window(0).tcp(false, 60_000, ACK, 501, 401, b"");
+        // The segment of 301 is sent again after it was acknowledged
+        v.window(u16::MAX)
+            .tcp(true, 200_000, PSH | ACK, 301, 501, &[0; 100]);
+
+        assert_eq!(v.value("tcp_handshake_rtt"), "0.020000000");
+        assert_eq!(v.value("forward_tcp_retransmission_count"), "1");
+        assert_eq!(v.value("forward_tcp_out_of_order_count"), "1");
+        assert_eq!(v.value("forward_tcp_zero_window_count"), "0");
+        assert_eq!(v.value("backward_tcp_retransmission_count"), "0");
+        assert_eq!(v.value("backward_tcp_zero_window_count"), "1");
+    }
+}
@@ -0,0 +1,250 @@
+use super::{
+    tcp_sequence::{TcpHandshake, TcpSegment},
+    FlowStat, FlowTimes, StatSettings,
+};
+use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
+use std::io::{BufWriter, Error, Write};
+
+/// Connection state of a flow, following the `conn_state` of Zeek. The
+/// originator is the forward direction of the flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Connection attempt seen, no reply
+    S0,
+    /// Connection established, not terminated
+    S1,
+    /// Normal establishment and termination
+    SF,
+    /// Connection attempt rejected
+    Rej,
+    /// Established, originator closed and responder did not
+    S2,
+    /// Established, responder closed and originator did not
+    S3,
+    /// Established, originator aborted with a RST
+    Rsto,
+    /// Established, responder aborted with a RST
+    Rstr,
+    /// Originator sent a SYN followed by a RST, without SYN/ACK
+    Rstos0,
+    /// Responder sent a SYN/ACK followed by a RST, without SYN
+    Rstrh,
+    /// Originator sent a SYN followed by a FIN, without SYN/ACK
+    Sh,
+    /// Responder sent a SYN/ACK followed by a FIN, without SYN
+    Shr,
+    /// No SYN seen, just midstream traffic
+    Oth,
+}
+
+impl ConnectionState {
+    /// The name used by Zeek
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConnectionState::S0 => "S0",
+            ConnectionState::S1 => "S1",
+            ConnectionState::SF => "SF",
+            ConnectionState::Rej => "REJ",
+            ConnectionState::S2 => "S2",
+            ConnectionState::S3 => "S3",
+            ConnectionState::Rsto => "RSTO",
+            ConnectionState::Rstr => "RSTR",
+            ConnectionState::Rstos0 => "RSTOS0",
+            ConnectionState::Rstrh => "RSTRH",
+            ConnectionState::Sh => "SH",
+            ConnectionState::Shr => "SHR",
+            ConnectionState::Oth => "OTH",
+        }
+    }
+}
+
+/// Handshake latencies and connection state of TCP flows. Flows of other
+/// protocols have no latencies and are either S0, if only the forward
+/// direction was seen, or SF
+#[derive(Debug, Clone)]
+pub struct TcpConnection {
+    is_tcp: bool,
+    backward_seen: bool,
+    handshake: TcpHandshake,
+
+    forward_syn: bool,
+    backward_syn_ack: bool,
+    forward_fin: bool,
+    backward_fin: bool,
+    /// Whether the first RST was sent forward, if any
+    first_rst_forward: Option<bool>,
+}
+
+impl TcpConnection {
+    fn new(is_tcp: bool) -> TcpConnection {
+        TcpConnection {
+            is_tcp,
+            backward_seen: false,
+            handshake: TcpHandshake::new(),
+            forward_syn: false,
+            backward_syn_ack: false,
+            forward_fin: false,
+            backward_fin: false,
+            first_rst_forward: None,
+        }
+    }
+
+    fn include_packet(
+        &mut self,
+        is_forward: bool,
+        flow_times: &FlowTimes,
+        sliced_packet: &etherparse::SlicedPacket,
+    ) {
+        if !is_forward {
+            self.backward_seen = true;
+        }
+        let Some(etherparse::TransportSlice::Tcp(tcp_slice)) = &sliced_packet.transport else {
+            return;
+        };
+        let segment = TcpSegment::from_slice(is_forward, flow_times.last_packet_time, tcp_slice);
+        self.handshake.include(&segment);
+
+        if tcp_slice.syn() && !tcp_slice.ack() && is_forward {
+            self.forward_syn = true;
+        } else if tcp_slice.syn() && tcp_slice.ack() && !is_forward {
+            self.backward_syn_ack = true;
+        }
+
+        if tcp_slice.fin() {
+            if is_forward {
+                self.forward_fin = true;
+            } else {
+                self.backward_fin = true;
+            }
+        }
+        if tcp_slice.rst() && self.first_rst_forward.is_none() {
+            self.first_rst_forward = Some(is_forward);
+        }
+    }
+
+    /// Classify the connection with the flags seen so far
+    pub fn connection_state(&self) -> ConnectionState {
+        if !self.is_tcp {
+            return if self.backward_seen {
+                ConnectionState::SF
+            } else {
+                ConnectionState::S0
+            };
+        }
+
+        let established = self.forward_syn && self.backward_syn_ack;
+        match (
+            self.first_rst_forward,
+            self.forward_syn,
+            self.backward_syn_ack,
+        ) {
+            (Some(true), true, true) => ConnectionState::Rsto,
+            (Some(false), true, true) => ConnectionState::Rstr,
+            (Some(true), true, false) => ConnectionState::Rstos0,
+            (Some(false), true, false) => ConnectionState::Rej,
+            (Some(false), false, true) => ConnectionState::Rstrh,
+            (Some(_), _, _) => ConnectionState::Oth,
+            (None, _, _) if established => match (self.forward_fin, self.backward_fin) {
+                (true, true) => ConnectionState::SF,
+                (true, false) => ConnectionState::S2,
+                (false, true) => ConnectionState::S3,
+                (false, false) => ConnectionState::S1,
+            },
+            (None, true, false) if self.forward_fin => ConnectionState::Sh,
+            (None, true, false) => ConnectionState::S0,
+            (None, false, true) if self.backward_fin => ConnectionState::Shr,
+            (None, _, _) => ConnectionState::Oth,
+        }
+    }
+}
+
+impl FlowStat for TcpConnection {
+    fn from_packet(
+        _settings: &StatSettings,
+        _identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        let is_tcp = matches!(
+            sliced_packet.transport,
+            Some(etherparse::TransportSlice::Tcp(_))
+        );
+        let mut tcp_connection = TcpConnection::new(is_tcp);
+
+        tcp_connection.include_packet(true, flow_times, sliced_packet);
+        tcp_connection
+    }
+    fn include(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        let is_forward = identifier.is_forward(sliced_packet);
+
+        self.include_packet(is_forward, flow_times, sliced_packet);
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        write!(writer, "tcp_syn_to_syn_ack,")?;
+        write!(writer, "tcp_syn_ack_to_ack,")?;
+        write!(writer, "conn_state,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        let latencies = [
+            self.handshake.syn_to_syn_ack(),
+            self.handshake.syn_ack_to_ack(),
+        ];
+        for latency in latencies {
+            let microseconds = latency.and_then(|l| l.num_microseconds()).unwrap_or(0);
+            write!(writer, "{:.9},", microseconds as f64 / 1_000_000.0)?;
+        }
+        write!(writer, "{},", self.connection_state().name())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_state() {
+        let mut v = TcpConnection::new(true);
+        assert_eq!(v.connection_state(), ConnectionState::Oth);
+
+        v.forward_syn = true;
+        assert_eq!(v.connection_state(), ConnectionState::S0);
+        v.forward_fin = true;
+        assert_eq!(v.connection_state(), ConnectionState::Sh);
+        v.forward_fin = false;
+        v.first_rst_forward = Some(false);
+        assert_eq!(v.connection_state(), ConnectionState::Rej);
+
+        v.first_rst_forward = None;
+        v.backward_syn_ack = true;
+        assert_eq!(v.connection_state(), ConnectionState::S1);
+        v.backward_fin = true;
+        assert_eq!(v.connection_state(), ConnectionState::S3);
+        v.forward_fin = true;
+        assert_eq!(v.connection_state(), ConnectionState::SF);
+        v.first_rst_forward = Some(true);
+        assert_eq!(v.connection_state(), ConnectionState::Rsto);
+
+        let mut v = TcpConnection::new(false);
+        assert_eq!(v.connection_state(), ConnectionState::S0);
+        v.backward_seen = true;
+        assert_eq!(v.connection_state(), ConnectionState::SF);
+    }
+}
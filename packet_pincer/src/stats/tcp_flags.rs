@@ -1,11 +1,8 @@
-use super::{FlowStat, FlowTimes};
+use super::{FlowStat, FlowTimes, StatSettings};
 use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
-use std::{
-    io::{BufWriter, Error, Write},
-    net::IpAddr,
-};
+use std::io::{BufWriter, Error, Write};
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct TcpFlags {
     bidirectional_tcp_cwr_flags_count: u32,
     bidirectional_tcp_ece_flags_count: u32,
@@ -23,6 +20,7 @@ pub struct TcpFlags {
 
 impl FlowStat for TcpFlags {
     fn from_packet(
+        _settings: &StatSettings,
         _identifier: &TransportFlowIdentifier,
         _flow_times: &FlowTimes,
         _packet_header: &pcap::PacketHeader,
@@ -57,13 +55,7 @@ impl FlowStat for TcpFlags {
         _reasembly_information: Option<&FragmentReasemblyInformation>,
     ) {
         if let Some(etherparse::TransportSlice::Tcp(transport_slice)) = &sliced_packet.transport {
-            let src_ip = match &sliced_packet.net {
-                Some(header) => match header {
-                    etherparse::NetSlice::Ipv4(v) => IpAddr::V4(v.header().source_addr()),
-                    etherparse::NetSlice::Ipv6(v) => IpAddr::V6(v.header().source_addr()),
-                },
-                None => panic!("Unexpected sliced packet without net layer"),
-            };
+            let is_forward = identifier.is_forward(sliced_packet);
 
             if transport_slice.cwr() {
                 self.bidirectional_tcp_cwr_flags_count += 1;
@@ -90,7 +82,7 @@ impl FlowStat for TcpFlags {
                 self.bidirectional_tcp_fin_flags_count += 1;
             }
 
-            if src_ip == identifier.source_ip {
+            if is_forward {
                 self.forward_tcp_psh_flags_count += 1;
                 self.forward_tcp_urg_flags_count += 1;
             } else {
@@ -102,6 +94,7 @@ impl FlowStat for TcpFlags {
 
     fn write_csv_header<T: ?Sized + std::io::Write>(
         writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
     ) -> Result<(), Error> {
         write!(writer, "bidirectional_tcp_cwr_flags_count,")?;
         write!(writer, "bidirectional_tcp_ece_flags_count,")?;
@@ -0,0 +1,119 @@
+/// Kind of the end of option list TCP option
+pub const END_OF_OPTIONS: u8 = 0;
+/// Kind of the no operation TCP option
+pub const NO_OPERATION: u8 = 1;
+/// Kind of the maximum segment size TCP option
+pub const MAXIMUM_SEGMENT_SIZE: u8 = 2;
+/// Kind of the window scale TCP option
+pub const WINDOW_SCALE: u8 = 3;
+/// Kind of the SACK permitted TCP option
+pub const SACK_PERMITTED: u8 = 4;
+/// Kind of the SACK TCP option
+pub const SACK: u8 = 5;
+/// Kind of the timestamps TCP option
+pub const TIMESTAMPS: u8 = 8;
+
+/// Iterator over the raw TCP options of a header, yielding the kind and the
+/// data of each option. Stops at the end of option list or at the first
+/// malformed option
+pub struct TcpOptionIter<'a> {
+    options: &'a [u8],
+    /// The padding after the end of option list, once it is reached
+    end_padding: Option<&'a [u8]>,
+}
+
+impl<'a> TcpOptionIter<'a> {
+    pub fn new(options: &'a [u8]) -> TcpOptionIter<'a> {
+        TcpOptionIter {
+            options,
+            end_padding: None,
+        }
+    }
+
+    /// The bytes that follow the end of option list, if the iteration
+    /// stopped at it
+    pub fn end_padding(&self) -> Option<&'a [u8]> {
+        self.end_padding
+    }
+}
+
+impl<'a> Iterator for TcpOptionIter<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let kind = *self.options.first()?;
+        match kind {
+            END_OF_OPTIONS => {
+                self.end_padding = Some(&self.options[1..]);
+                self.options = &[];
+                None
+            }
+            NO_OPERATION => {
+                self.options = &self.options[1..];
+                Some((kind, &[]))
+            }
+            _ => {
+                let length = usize::from(*self.options.get(1)?);
+                if length < 2 || self.options.len() < length {
+                    self.options = &[];
+                    return None;
+                }
+                let data = &self.options[2..length];
+                self.options = &self.options[length..];
+                Some((kind, data))
+            }
+        }
+    }
+}
+
+/// Short name of the option kind, as used by p0f
+pub fn option_name(kind: u8) -> String {
+    match kind {
+        END_OF_OPTIONS => "eol".to_string(),
+        NO_OPERATION => "nop".to_string(),
+        MAXIMUM_SEGMENT_SIZE => "mss".to_string(),
+        WINDOW_SCALE => "ws".to_string(),
+        SACK_PERMITTED => "sok".to_string(),
+        SACK => "sack".to_string(),
+        TIMESTAMPS => "ts".to_string(),
+        _ => format!("?{}", kind),
+    }
+}
+
+/// Get the window scale shift announced on the given raw TCP options, if any
+pub fn extract_window_scale(options: &[u8]) -> Option<u8> {
+    TcpOptionIter::new(options)
+        .find(|(kind, data)| *kind == WINDOW_SCALE && data.len() == 1)
+        .map(|(_, data)| std::cmp::min(data[0], 14))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_option_iteration() {
+        // MSS 1460, NOP, window scale 7, SACK permitted, end of list
+        let options = [2, 4, 5, 180, 1, 3, 3, 7, 4, 2, 0, 1];
+        let mut iter = TcpOptionIter::new(&options);
+        let parsed: Vec<(u8, &[u8])> = iter.by_ref().collect();
+
+        assert_eq!(
+            parsed,
+            vec![
+                (MAXIMUM_SEGMENT_SIZE, &[5, 180][..]),
+                (NO_OPERATION, &[][..]),
+                (WINDOW_SCALE, &[7][..]),
+                (SACK_PERMITTED, &[][..]),
+            ]
+        );
+        assert_eq!(iter.end_padding(), Some(&[1][..]));
+        assert_eq!(extract_window_scale(&options), Some(7));
+
+        // Truncated options stop the iteration
+        let mut iter = TcpOptionIter::new(&[1, 2, 4, 5]);
+        assert_eq!(iter.by_ref().count(), 1);
+        assert_eq!(iter.end_padding(), None);
+        assert_eq!(extract_window_scale(&[1, 1, 3, 3]), None);
+    }
+}
@@ -0,0 +1,186 @@
+use super::{
+    tcp_option_parse::{
+        option_name, TcpOptionIter, END_OF_OPTIONS, MAXIMUM_SEGMENT_SIZE, SACK, SACK_PERMITTED,
+        TIMESTAMPS, WINDOW_SCALE,
+    },
+    FlowStat, FlowTimes, StatSettings,
+};
+use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
+use std::io::{BufWriter, Error, Write};
+
+/// Options announced on the first SYN sent on a direction
+#[derive(Debug, Clone, Default)]
+struct SynOptions {
+    maximum_segment_size: Option<u16>,
+    window_scale: Option<u8>,
+    sack_permitted: bool,
+    timestamps: bool,
+    /// Option names in order, separated by `-`
+    layout: String,
+    options_len: usize,
+}
+
+impl SynOptions {
+    fn from_options(options: &[u8]) -> SynOptions {
+        let mut syn_options = SynOptions {
+            options_len: options.len(),
+            ..Default::default()
+        };
+        let mut names = Vec::new();
+
+        let mut option_iter = TcpOptionIter::new(options);
+        for (kind, data) in option_iter.by_ref() {
+            match kind {
+                MAXIMUM_SEGMENT_SIZE if data.len() == 2 => {
+                    syn_options.maximum_segment_size = Some(u16::from_be_bytes([data[0], data[1]]));
+                }
+                WINDOW_SCALE if data.len() == 1 => syn_options.window_scale = Some(data[0]),
+                SACK_PERMITTED => syn_options.sack_permitted = true,
+                TIMESTAMPS => syn_options.timestamps = true,
+                _ => {}
+            }
+            names.push(option_name(kind));
+        }
+        if option_iter.end_padding().is_some() {
+            names.push(option_name(END_OF_OPTIONS));
+        }
+        syn_options.layout = names.join("-");
+        syn_options
+    }
+}
+
+/// TCP options of each direction: the ones announced on its first SYN or
+/// SYN/ACK, and the counts of SACK and timestamp options across the flow
+#[derive(Debug, Clone, Default)]
+struct DirectionOptions {
+    syn: Option<SynOptions>,
+    sack_option_count: u32,
+    sack_block_count: u32,
+    timestamp_option_count: u32,
+}
+
+impl DirectionOptions {
+    fn include(&mut self, tcp_slice: &etherparse::TcpSlice) {
+        if tcp_slice.syn() && self.syn.is_none() {
+            self.syn = Some(SynOptions::from_options(tcp_slice.options()));
+        }
+
+        for (kind, data) in TcpOptionIter::new(tcp_slice.options()) {
+            match kind {
+                SACK => {
+                    self.sack_option_count += 1;
+                    self.sack_block_count += u32::try_from(data.len() / 8).unwrap();
+                }
+                TIMESTAMPS => self.timestamp_option_count += 1,
+                _ => {}
+            }
+        }
+    }
+
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+    ) -> Result<(), Error> {
+        let syn = self.syn.clone().unwrap_or_default();
+        write!(writer, "{},", syn.maximum_segment_size.unwrap_or(0))?;
+        write!(writer, "{},", syn.window_scale.map_or(-1, i16::from))?;
+        write!(writer, "{},", u8::from(syn.sack_permitted))?;
+        write!(writer, "{},", u8::from(syn.timestamps))?;
+        write!(writer, "{},", syn.layout)?;
+        write!(writer, "{},", syn.options_len)?;
+        write!(writer, "{},", self.sack_option_count)?;
+        write!(writer, "{},", self.sack_block_count)?;
+        write!(writer, "{},", self.timestamp_option_count)?;
+        Ok(())
+    }
+}
+
+/// TCP options announced on the handshake of each direction and counts of
+/// the options seen on the rest of the flow. A missing MSS is written as 0,
+/// a missing window scale as -1 and a missing layout as an empty string
+#[derive(Debug, Clone, Default)]
+pub struct TcpOptions {
+    forward: DirectionOptions,
+    backward: DirectionOptions,
+}
+
+impl FlowStat for TcpOptions {
+    fn from_packet(
+        _settings: &StatSettings,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        let mut tcp_options = TcpOptions::default();
+
+        if let Some(etherparse::TransportSlice::Tcp(tcp_slice)) = &sliced_packet.transport {
+            tcp_options.forward.include(tcp_slice);
+        }
+        tcp_options
+    }
+    fn include(
+        &mut self,
+        identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+        if let Some(etherparse::TransportSlice::Tcp(tcp_slice)) = &sliced_packet.transport {
+            if identifier.is_forward(sliced_packet) {
+                self.forward.include(tcp_slice);
+            } else {
+                self.backward.include(tcp_slice);
+            }
+        }
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        for direction in ["forward", "backward"] {
+            write!(writer, "{}_syn_mss,", direction)?;
+            write!(writer, "{}_syn_window_scale,", direction)?;
+            write!(writer, "{}_syn_sack_permitted,", direction)?;
+            write!(writer, "{}_syn_timestamps,", direction)?;
+            write!(writer, "{}_syn_option_layout,", direction)?;
+            write!(writer, "{}_syn_options_len,", direction)?;
+            write!(writer, "{}_tcp_sack_option_count,", direction)?;
+            write!(writer, "{}_tcp_sack_block_count,", direction)?;
+            write!(writer, "{}_tcp_timestamp_option_count,", direction)?;
+        }
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        self.forward.write_csv_value(writer)?;
+        self.backward.write_csv_value(writer)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syn_options() {
+        // Linux: MSS 1460, SACK permitted, timestamps, NOP, window scale 7
+        let options = [
+            2, 4, 5, 180, 4, 2, 8, 10, 0, 0, 0, 1, 0, 0, 0, 0, 1, 3, 3, 7,
+        ];
+        let v = SynOptions::from_options(&options);
+
+        assert_eq!(v.maximum_segment_size, Some(1460));
+        assert_eq!(v.window_scale, Some(7));
+        assert!(v.sack_permitted);
+        assert!(v.timestamps);
+        assert_eq!(v.layout, "mss-sok-ts-nop-ws");
+        assert_eq!(v.options_len, 20);
+    }
+}
@@ -0,0 +1,537 @@
+use super::{running_stat::RunningStat, tcp_option_parse::extract_window_scale};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::collections::VecDeque;
+
+/// Maximum number of sequence holes remembered per direction
+const MAX_HOLES: usize = 16;
+/// Maximum number of segments waiting for an ACK to sample their RTT
+const MAX_PENDING: usize = 32;
+
+/// Whether the sequence number `a` comes after `b`, modulo 2^32
+fn seq_after(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) > 0
+}
+
+/// The fields of a TCP segment used by the sequence analysis
+#[derive(Debug, Clone)]
+pub struct TcpSegment {
+    pub is_forward: bool,
+    pub time: DateTime<Utc>,
+    pub sequence_number: u32,
+    /// The acknowledgment number, if the ACK flag is set
+    pub acknowledgment_number: Option<u32>,
+    pub window_size: u16,
+    pub payload_len: u32,
+    pub syn: bool,
+    pub fin: bool,
+    pub rst: bool,
+    /// The window scale option, only meaningful on SYN segments
+    pub window_scale: Option<u8>,
+}
+
+impl TcpSegment {
+    pub fn from_slice(
+        is_forward: bool,
+        time: DateTime<Utc>,
+        tcp_slice: &etherparse::TcpSlice,
+    ) -> TcpSegment {
+        TcpSegment {
+            is_forward,
+            time,
+            sequence_number: tcp_slice.sequence_number(),
+            acknowledgment_number: tcp_slice.ack().then(|| tcp_slice.acknowledgment_number()),
+            window_size: tcp_slice.window_size(),
+            payload_len: tcp_slice.payload().len().try_into().unwrap(),
+            syn: tcp_slice.syn(),
+            fin: tcp_slice.fin(),
+            rst: tcp_slice.rst(),
+            window_scale: extract_window_scale(tcp_slice.options()),
+        }
+    }
+
+    /// Length of the segment on the sequence space. SYN and FIN consume one
+    /// sequence number each
+    fn sequence_len(&self) -> u32 {
+        self.payload_len + u32::from(self.syn) + u32::from(self.fin)
+    }
+}
+
+/// Range of sequence numbers not seen yet, below the next expected one
+#[derive(Debug, Clone)]
+struct Hole {
+    start: u32,
+    end: u32,
+    time: DateTime<Utc>,
+}
+
+/// Sequence space analysis of the segments sent on a single direction
+#[derive(Debug, Clone)]
+pub struct DirectionSequence {
+    /// Sequence number following the highest one seen, unknown until the
+    /// first segment with data when the capture starts mid-connection
+    next_seq: Option<u32>,
+    holes: Vec<Hole>,
+    /// End sequence number and time of the segments not acknowledged yet
+    pending: VecDeque<(u32, DateTime<Utc>)>,
+
+    syn_seen: bool,
+    window_scale: Option<u8>,
+    last_ack: Option<u32>,
+    last_window_size: Option<u16>,
+    /// Last advertised window in bytes, unknown if the scale is unknown
+    last_window_bytes: Option<u64>,
+
+    rtt: RunningStat,
+    retransmission_count: u64,
+    out_of_order_count: u64,
+    duplicate_ack_count: u64,
+    zero_window_count: u64,
+    window_full_count: u64,
+}
+
+impl DirectionSequence {
+    fn new() -> DirectionSequence {
+        DirectionSequence {
+            next_seq: None,
+            holes: Vec::new(),
+            pending: VecDeque::new(),
+            syn_seen: false,
+            window_scale: None,
+            last_ack: None,
+            last_window_size: None,
+            last_window_bytes: None,
+            rtt: RunningStat::new(),
+            retransmission_count: 0,
+            out_of_order_count: 0,
+            duplicate_ack_count: 0,
+            zero_window_count: 0,
+            window_full_count: 0,
+        }
+    }
+
+    /// RTT samples in microseconds, measured from the segments sent on this
+    /// direction to the ACKs that acknowledged them
+    pub fn rtt(&self) -> &RunningStat {
+        &self.rtt
+    }
+
+    pub fn retransmission_count(&self) -> u64 {
+        self.retransmission_count
+    }
+
+    pub fn out_of_order_count(&self) -> u64 {
+        self.out_of_order_count
+    }
+
+    pub fn duplicate_ack_count(&self) -> u64 {
+        self.duplicate_ack_count
+    }
+
+    pub fn zero_window_count(&self) -> u64 {
+        self.zero_window_count
+    }
+
+    /// Number of segments that filled the window advertised by the peer
+    pub fn window_full_count(&self) -> u64 {
+        self.window_full_count
+    }
+
+    /// Remove the given range from the holes, returning the creation time of
+    /// the hole it was in, if any
+    fn fill_hole(&mut self, start: u32, end: u32) -> Option<DateTime<Utc>> {
+        let position = self
+            .holes
+            .iter()
+            .position(|h| !seq_after(h.start, start) && seq_after(h.end, start))?;
+        let hole = self.holes.remove(position);
+
+        if seq_after(start, hole.start) {
+            self.holes.push(Hole {
+                start: hole.start,
+                end: start,
+                time: hole.time,
+            });
+        }
+        if seq_after(hole.end, end) {
+            self.holes.push(Hole {
+                start: end,
+                end: hole.end,
+                time: hole.time,
+            });
+        }
+        Some(hole.time)
+    }
+
+    /// Forget the pending RTT samples of a retransmitted range (Karn's
+    /// algorithm)
+    fn discard_pending(&mut self, start: u32, end: u32) {
+        self.pending.retain(|(pending_end, _)| {
+            !seq_after(*pending_end, start) || seq_after(*pending_end, end)
+        });
+    }
+
+    fn push_pending(&mut self, end: u32, time: DateTime<Utc>) {
+        if self.pending.len() < MAX_PENDING {
+            self.pending.push_back((end, time));
+        }
+    }
+
+    /// Take the RTT sample of the most recent segment covered by the ACK
+    fn acknowledge(&mut self, ack: u32, time: DateTime<Utc>) {
+        let mut sample = None;
+        while let Some((end, sent_time)) = self.pending.front() {
+            if seq_after(*end, ack) {
+                break;
+            }
+            sample = Some(*sent_time);
+            self.pending.pop_front();
+        }
+
+        if let Some(sent_time) = sample {
+            let rtt: u64 = (time - sent_time)
+                .num_microseconds()
+                .expect("RTT microseconds should fit in a i64")
+                .try_into()
+                .expect("RTT microseconds should convert to u64");
+            self.rtt.include(rtt);
+        }
+    }
+}
+
+/// Timing of the three way handshake of a TCP connection, started by the
+/// first SYN seen on either direction
+#[derive(Debug, Clone)]
+pub struct TcpHandshake {
+    /// Time of the first SYN and whether it was sent forward
+    syn: Option<(DateTime<Utc>, bool)>,
+    /// Time of the first SYN/ACK answering the SYN
+    syn_ack_time: Option<DateTime<Utc>>,
+    /// Sequence number acknowledging the SYN/ACK
+    syn_ack_end: Option<u32>,
+    syn_to_syn_ack: Option<TimeDelta>,
+    syn_ack_to_ack: Option<TimeDelta>,
+}
+
+impl TcpHandshake {
+    pub fn new() -> TcpHandshake {
+        TcpHandshake {
+            syn: None,
+            syn_ack_time: None,
+            syn_ack_end: None,
+            syn_to_syn_ack: None,
+            syn_ack_to_ack: None,
+        }
+    }
+
+    /// Time from the SYN to the SYN/ACK, if both were captured
+    pub fn syn_to_syn_ack(&self) -> Option<TimeDelta> {
+        self.syn_to_syn_ack
+    }
+
+    /// Time from the SYN/ACK to the ACK that acknowledged it, if the
+    /// handshake was captured
+    pub fn syn_ack_to_ack(&self) -> Option<TimeDelta> {
+        self.syn_ack_to_ack
+    }
+
+    /// Time from the SYN to the ACK of the SYN/ACK, if the handshake was
+    /// captured
+    pub fn rtt(&self) -> Option<TimeDelta> {
+        Some(self.syn_to_syn_ack? + self.syn_ack_to_ack?)
+    }
+
+    pub fn include(&mut self, segment: &TcpSegment) {
+        if segment.syn && segment.acknowledgment_number.is_none() {
+            if self.syn.is_none() {
+                self.syn = Some((segment.time, segment.is_forward));
+            }
+        } else if segment.syn {
+            if let Some((syn_time, syn_forward)) = self.syn {
+                if syn_forward != segment.is_forward {
+                    self.syn_ack_end = Some(segment.sequence_number.wrapping_add(1));
+                    if self.syn_ack_time.is_none() {
+                        self.syn_ack_time = Some(segment.time);
+                        self.syn_to_syn_ack = Some(segment.time - syn_time);
+                    }
+                }
+            }
+        } else if let (Some((_, syn_forward)), Some(syn_ack_time), Some(syn_ack_end), Some(ack)) = (
+            self.syn,
+            self.syn_ack_time,
+            self.syn_ack_end,
+            segment.acknowledgment_number,
+        ) {
+            if self.syn_ack_to_ack.is_none()
+                && syn_forward == segment.is_forward
+                && ack == syn_ack_end
+            {
+                self.syn_ack_to_ack = Some(segment.time - syn_ack_time);
+            }
+        }
+    }
+}
+
+/// Analysis of the sequence and acknowledgment numbers of both directions of
+/// a TCP connection, following the heuristics of Wireshark. Works on
+/// captures that start mid-connection, although the handshake RTT and the
+/// window full events need the handshake to be captured
+#[derive(Debug, Clone)]
+pub struct TcpSequenceTracker {
+    forward: DirectionSequence,
+    backward: DirectionSequence,
+    handshake: TcpHandshake,
+}
+
+impl TcpSequenceTracker {
+    pub fn new() -> TcpSequenceTracker {
+        TcpSequenceTracker {
+            forward: DirectionSequence::new(),
+            backward: DirectionSequence::new(),
+            handshake: TcpHandshake::new(),
+        }
+    }
+
+    pub fn forward(&self) -> &DirectionSequence {
+        &self.forward
+    }
+
+    pub fn backward(&self) -> &DirectionSequence {
+        &self.backward
+    }
+
+    /// Time from the SYN to the ACK of the SYN/ACK, if the handshake was
+    /// captured
+    pub fn handshake_rtt(&self) -> Option<TimeDelta> {
+        self.handshake.rtt()
+    }
+
+    /// Minimum RTT seen, used to tell reordered segments from retransmitted
+    /// ones
+    fn min_rtt(&self) -> Option<TimeDelta> {
+        [
+            self.forward.rtt.current_min(),
+            self.backward.rtt.current_min(),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .and_then(|min| Some(TimeDelta::microseconds(min.try_into().ok()?)))
+        .or(self.handshake.rtt())
+    }
+
+    pub fn include(&mut self, segment: &TcpSegment) {
+        self.handshake.include(segment);
+
+        let min_rtt = self.min_rtt();
+        let (current, peer) = if segment.is_forward {
+            (&mut self.forward, &mut self.backward)
+        } else {
+            (&mut self.backward, &mut self.forward)
+        };
+
+        if segment.syn {
+            current.syn_seen = true;
+            current.window_scale = segment.window_scale;
+        }
+        if !segment.rst {
+            Self::include_sequence(current, peer, segment, min_rtt);
+        }
+        if let Some(ack) = segment.acknowledgment_number {
+            Self::include_acknowledgment(current, peer, segment, ack);
+        }
+    }
+
+    fn include_sequence(
+        current: &mut DirectionSequence,
+        peer: &DirectionSequence,
+        segment: &TcpSegment,
+        min_rtt: Option<TimeDelta>,
+    ) {
+        let sequence_len = segment.sequence_len();
+        if sequence_len == 0 {
+            return;
+        }
+        let start = segment.sequence_number;
+        let end = start.wrapping_add(sequence_len);
+
+        let Some(next_seq) = current.next_seq else {
+            current.next_seq = Some(end);
+            current.push_pending(end, segment.time);
+            return;
+        };
+
+        let is_keep_alive = segment.payload_len <= 1
+            && !segment.syn
+            && !segment.fin
+            && start == next_seq.wrapping_sub(1);
+        if is_keep_alive {
+            return;
+        }
+
+        if !seq_after(next_seq, start) {
+            // New data, maybe leaving a hole behind
+            if seq_after(start, next_seq) && current.holes.len() < MAX_HOLES {
+                current.holes.push(Hole {
+                    start: next_seq,
+                    end: start,
+                    time: segment.time,
+                });
+            }
+            current.next_seq = Some(end);
+            current.push_pending(end, segment.time);
+            Self::check_window_full(current, peer, end);
+        } else if let Some(hole_time) = current.fill_hole(start, end) {
+            // Data that was missing, either late or retransmitted after a loss
+            let is_late = min_rtt.is_none_or(|min_rtt| segment.time - hole_time < min_rtt);
+            if is_late {
+                current.out_of_order_count += 1;
+            } else {
+                current.retransmission_count += 1;
+            }
+        } else {
+            current.retransmission_count += 1;
+            current.discard_pending(start, end);
+            if seq_after(end, next_seq) {
+                current.next_seq = Some(end);
+            }
+        }
+    }
+
+    fn check_window_full(current: &mut DirectionSequence, peer: &DirectionSequence, end: u32) {
+        if let (Some(ack), Some(window_bytes)) = (peer.last_ack, peer.last_window_bytes) {
+            let window_end = u64::from(ack) + window_bytes;
+            if window_end % (1 << 32) == u64::from(end) {
+                current.window_full_count += 1;
+            }
+        }
+    }
+
+    fn include_acknowledgment(
+        current: &mut DirectionSequence,
+        peer: &mut DirectionSequence,
+        segment: &TcpSegment,
+        ack: u32,
+    ) {
+        let has_outstanding_data = peer.next_seq.is_some_and(|n| seq_after(n, ack));
+        let is_duplicate = segment.sequence_len() == 0
+            && !segment.rst
+            && has_outstanding_data
+            && current.last_ack == Some(ack)
+            && current.last_window_size == Some(segment.window_size);
+        if is_duplicate {
+            current.duplicate_ack_count += 1;
+        }
+        if segment.window_size == 0 && !segment.syn && !segment.fin && !segment.rst {
+            current.zero_window_count += 1;
+        }
+
+        peer.acknowledge(ack, segment.time);
+
+        // Windows of SYN segments are never scaled, and the rest are scaled
+        // only if both sides announced it
+        let window_scale = if segment.syn {
+            Some(0)
+        } else if current.syn_seen && peer.syn_seen {
+            Some(
+                current
+                    .window_scale
+                    .zip(peer.window_scale)
+                    .map_or(0, |(s, _)| s),
+            )
+        } else {
+            None
+        };
+        current.last_ack = Some(ack);
+        current.last_window_size = Some(segment.window_size);
+        current.last_window_bytes = window_scale.map(|s| u64::from(segment.window_size) << s);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(is_forward: bool, millis: i64, seq: u32, ack: u32, payload_len: u32) -> TcpSegment {
+        TcpSegment {
+            is_forward,
+            time: DateTime::from_timestamp(0, 0).unwrap() + TimeDelta::milliseconds(millis),
+            sequence_number: seq,
+            acknowledgment_number: Some(ack),
+            window_size: 1000,
+            payload_len,
+            syn: false,
+            fin: false,
+            rst: false,
+            window_scale: None,
+        }
+    }
+
+    #[test]
+    fn test_handshake_and_rtt() {
+        let mut v = TcpSequenceTracker::new();
+        let client_isn = u32::MAX - 10;
+
+        let mut syn = segment(true, 0, client_isn, 0, 0);
+        syn.syn = true;
+        syn.acknowledgment_number = None;
+        syn.window_scale = Some(2);
+        let mut syn_ack = segment(false, 30, 500, client_isn.wrapping_add(1), 0);
+        syn_ack.syn = true;
+        syn_ack.window_scale = Some(0);
+
+        v.include(&syn);
+        v.include(&syn_ack);
+        v.include(&segment(true, 40, client_isn.wrapping_add(1), 501, 0));
+        assert_eq!(v.handshake_rtt(), Some(TimeDelta::milliseconds(40)));
+        assert_eq!(
+            v.handshake.syn_to_syn_ack(),
+            Some(TimeDelta::milliseconds(30))
+        );
+        assert_eq!(
+            v.handshake.syn_ack_to_ack(),
+            Some(TimeDelta::milliseconds(10))
+        );
+        assert_eq!(v.forward().rtt().current_min(), Some(30_000));
+        assert_eq!(v.backward().rtt().current_min(), Some(10_000));
+
+        // The data crosses the sequence wraparound and fills the window of
+        // the server
+        v.include(&segment(true, 50, client_isn.wrapping_add(1), 501, 400));
+        v.include(&segment(true, 51, client_isn.wrapping_add(401), 501, 600));
+        v.include(&segment(false, 70, 501, client_isn.wrapping_add(1001), 0));
+        assert_eq!(v.forward().rtt().current_count(), 2);
+        assert_eq!(v.forward().rtt().current_min(), Some(19_000));
+        assert_eq!(v.forward().window_full_count(), 1);
+        assert_eq!(v.forward().retransmission_count(), 0);
+    }
+
+    #[test]
+    fn test_losses_and_reordering() {
+        let mut v = TcpSequenceTracker::new();
+
+        // Starts mid-connection
+        v.include(&segment(true, 0, 1000, 1, 100));
+        v.include(&segment(false, 10, 1, 1100, 0));
+
+        // A segment arrives shortly after the following one
+        v.include(&segment(true, 20, 1200, 1, 100));
+        v.include(&segment(false, 21, 1, 1100, 0));
+        v.include(&segment(true, 22, 1100, 1, 100));
+        assert_eq!(v.forward().out_of_order_count(), 1);
+        assert_eq!(v.backward().duplicate_ack_count(), 1);
+
+        // A segment is lost and sent again much later
+        v.include(&segment(true, 30, 1400, 1, 100));
+        v.include(&segment(true, 200, 1300, 1, 100));
+        // A segment is sent twice
+        v.include(&segment(true, 300, 1400, 1, 100));
+        assert_eq!(v.forward().out_of_order_count(), 1);
+        assert_eq!(v.forward().retransmission_count(), 2);
+
+        let mut zero_window = segment(false, 310, 1, 1500, 0);
+        zero_window.window_size = 0;
+        v.include(&zero_window);
+        assert_eq!(v.backward().zero_window_count(), 1);
+        assert_eq!(v.backward().duplicate_ack_count(), 1);
+    }
+}
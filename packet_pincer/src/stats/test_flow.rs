@@ -0,0 +1,164 @@
+use super::{FlowStat, StatSettings};
+use crate::{packet_flow::TransportFlow, packet_parse::FlowIdentifier};
+use etherparse::{PacketBuilder, SlicedPacket};
+use libc::timeval;
+use std::io::BufWriter;
+
+pub const FIN: u8 = 0x01;
+pub const SYN: u8 = 0x02;
+pub const RST: u8 = 0x04;
+pub const PSH: u8 = 0x08;
+pub const ACK: u8 = 0x10;
+
+const CLIENT_IP: [u8; 4] = [192, 168, 1, 2];
+const SERVER_IP: [u8; 4] = [192, 168, 1, 1];
+
+/// Flow between a client and a server built packet by packet, to test the
+/// stats through `FlowStat`. The first packet must be sent by the client, so
+/// its packets are the forward ones
+pub struct TestFlow<S: FlowStat> {
+    settings: StatSettings,
+    client_port: u16,
+    server_port: u16,
+    /// Window of the next TCP segments
+    window: u16,
+    flow: Option<TransportFlow<S>>,
+}
+
+impl<S: FlowStat> TestFlow<S> {
+    pub fn new(settings: StatSettings, client_port: u16, server_port: u16) -> TestFlow<S> {
+        TestFlow {
+            settings,
+            client_port,
+            server_port,
+            window: u16::MAX,
+            flow: None,
+        }
+    }
+
+    fn addresses(&self, is_forward: bool) -> ([u8; 4], [u8; 4], u16, u16) {
+        if is_forward {
+            (CLIENT_IP, SERVER_IP, self.client_port, self.server_port)
+        } else {
+            (SERVER_IP, CLIENT_IP, self.server_port, self.client_port)
+        }
+    }
+
+    /// Include a UDP packet captured at the given microseconds
+    pub fn udp(&mut self, is_forward: bool, micros: i64, payload: &[u8]) -> &mut Self {
+        let (source, destination, source_port, destination_port) = self.addresses(is_forward);
+        let builder = PacketBuilder::ethernet2([1, 2, 3, 4, 5, 6], [7, 8, 9, 10, 11, 12])
+            .ipv4(source, destination, 64)
+            .udp(source_port, destination_port);
+        let mut data = Vec::with_capacity(builder.size(payload.len()));
+        builder.write(&mut data, payload).unwrap();
+        self.include(micros, &data)
+    }
+
+    /// Set the window of the next TCP segments
+    pub fn window(&mut self, window: u16) -> &mut Self {
+        self.window = window;
+        self
+    }
+
+    /// Include a TCP segment captured at the given microseconds, with the
+    /// flags of this module
+    pub fn tcp(
+        &mut self,
+        is_forward: bool,
+        micros: i64,
+        flags: u8,
+        sequence_number: u32,
+        acknowledgment_number: u32,
+        payload: &[u8],
+    ) -> &mut Self {
+        let (source, destination, source_port, destination_port) = self.addresses(is_forward);
+        let mut builder = PacketBuilder::ethernet2([1, 2, 3, 4, 5, 6], [7, 8, 9, 10, 11, 12])
+            .ipv4(source, destination, 64)
+            .tcp(source_port, destination_port, sequence_number, self.window);
+        if flags & SYN != 0 {
+            builder = builder.syn();
+        }
+        if flags & ACK != 0 {
+            builder = builder.ack(acknowledgment_number);
+        }
+        if flags & PSH != 0 {
+            builder = builder.psh();
+        }
+        if flags & FIN != 0 {
+            builder = builder.fin();
+        }
+        if flags & RST != 0 {
+            builder = builder.rst();
+        }
+        let mut data = Vec::with_capacity(builder.size(payload.len()));
+        builder.write(&mut data, payload).unwrap();
+        self.include(micros, &data)
+    }
+
+    fn include(&mut self, micros: i64, data: &[u8]) -> &mut Self {
+        let header = pcap::PacketHeader {
+            ts: timeval {
+                tv_sec: micros / 1_000_000,
+                tv_usec: micros % 1_000_000,
+            },
+            caplen: data.len().try_into().unwrap(),
+            len: data.len().try_into().unwrap(),
+        };
+        let sliced_packet = SlicedPacket::from_ethernet(data).unwrap();
+        match &mut self.flow {
+            Some(flow) => {
+                flow.include(&header, sliced_packet, None);
+            }
+            None => {
+                let Ok((FlowIdentifier::TransportFlowIdentifier(identifier), _)) =
+                    FlowIdentifier::from_sliced_packet(&sliced_packet)
+                else {
+                    panic!("Test packets have a transport layer");
+                };
+                self.flow = Some(TransportFlow::from(
+                    &self.settings,
+                    identifier,
+                    &header,
+                    sliced_packet,
+                    None,
+                ));
+            }
+        }
+        self
+    }
+
+    fn flow(&self) -> &TransportFlow<S> {
+        self.flow.as_ref().expect("The flow has packets")
+    }
+
+    /// The columns of the stat with their values, checking that the header
+    /// and the values have the same number of columns
+    pub fn columns(&self) -> Vec<(String, String)> {
+        let mut header = BufWriter::new(Vec::new());
+        S::write_csv_header(&mut header, &self.settings).unwrap();
+        let mut values = BufWriter::new(Vec::new());
+        let flow = self.flow();
+        flow.statistics
+            .write_csv_value(&mut values, &flow.flow_times)
+            .unwrap();
+
+        let split = |writer: BufWriter<Vec<u8>>| {
+            let line = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+            let line = line.strip_suffix(',').expect("Values end with a comma");
+            line.split(',').map(str::to_string).collect::<Vec<_>>()
+        };
+        let (header, values) = (split(header), split(values));
+        assert_eq!(header.len(), values.len());
+        header.into_iter().zip(values).collect()
+    }
+
+    /// The value written on the column
+    pub fn value(&self, column: &str) -> String {
+        self.columns()
+            .into_iter()
+            .find(|(name, _)| name == column)
+            .unwrap_or_else(|| panic!("Missing column {}", column))
+            .1
+    }
+}
@@ -0,0 +1,174 @@
+use super::{
+    csv_text::escape_text,
+    stream_frames::Directional,
+    tls_message::{
+        certificate_count, version_name, ClientHello, HandshakeReader, ServerHello, CERTIFICATE,
+        CLIENT_HELLO, SERVER_HELLO, SERVER_HELLO_DONE, TLS_1_3,
+    },
+    FlowStat, FlowTimes, StatSettings,
+};
+use crate::{
+    packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier,
+    stream_reassembly::StreamChunk,
+};
+use std::io::{BufWriter, Error, Write};
+
+/// Metadata of the TLS handshake of TCP flows, read from the reassembled
+/// streams: server name, offered and selected versions, cipher suites, ALPN,
+/// certificates and the JA3, JA3S and JA4 fingerprints. Certificates are only
+/// seen before TLS 1.3, as they are encrypted afterwards
+#[derive(Debug, Clone)]
+pub struct Tls {
+    readers: Directional<HandshakeReader>,
+    client_hello: Option<ClientHello>,
+    server_hello: Option<ServerHello>,
+    certificate_count: Option<usize>,
+}
+
+impl Tls {
+    /// Include a handshake message, returning whether more messages of its
+    /// direction are needed
+    fn include_message(&mut self, message_type: u8, body: &[u8]) -> bool {
+        match message_type {
+            CLIENT_HELLO => {
+                if self.client_hello.is_none() {
+                    self.client_hello = ClientHello::parse(body);
+                }
+                false
+            }
+            SERVER_HELLO => {
+                if self.server_hello.is_none() {
+                    self.server_hello = ServerHello::parse(body);
+                }
+                self.server_hello
+                    .as_ref()
+                    .is_some_and(|hello| hello.selected_version() != TLS_1_3)
+            }
+            CERTIFICATE => {
+                self.certificate_count = certificate_count(body);
+                false
+            }
+            SERVER_HELLO_DONE => false,
+            _ => true,
+        }
+    }
+
+    /// Write the values separated by `-`, which has to be escaped in them
+    fn write_list<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        values: impl Iterator<Item = String>,
+    ) -> Result<(), Error> {
+        write!(writer, "{},", values.collect::<Vec<String>>().join("-"))
+    }
+}
+
+impl FlowStat for Tls {
+    fn from_packet(
+        _settings: &StatSettings,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        _sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) -> Self {
+        Tls {
+            readers: Directional::default(),
+            client_hello: None,
+            server_hello: None,
+            certificate_count: None,
+        }
+    }
+    fn include(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        _packet_header: &pcap::PacketHeader,
+        _sliced_packet: &etherparse::SlicedPacket,
+        _reasembly_information: Option<&FragmentReasemblyInformation>,
+    ) {
+    }
+    fn uses_streams(_settings: &StatSettings) -> bool {
+        true
+    }
+    fn include_stream(
+        &mut self,
+        _identifier: &TransportFlowIdentifier,
+        _flow_times: &FlowTimes,
+        chunk: &StreamChunk,
+    ) {
+        Directional::with(
+            self,
+            |tls| &mut tls.readers,
+            chunk.is_forward,
+            |tls, reader| {
+                reader.include(chunk.gap != 0, chunk.data, |message_type, body| {
+                    tls.include_message(message_type, body)
+                })
+            },
+        );
+    }
+    fn write_csv_header<T: ?Sized + std::io::Write>(
+        writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
+    ) -> Result<(), Error> {
+        write!(writer, "tls_client_hello,tls_sni,tls_client_versions,")?;
+        write!(writer, "tls_cipher_suite_count,tls_extension_count,")?;
+        write!(writer, "tls_client_alpn,tls_ja3,tls_ja4,")?;
+        write!(writer, "tls_server_hello,tls_server_version,")?;
+        write!(writer, "tls_selected_cipher_suite,tls_server_alpn,")?;
+        write!(writer, "tls_ja3s,")?;
+        write!(writer, "tls_certificate,tls_certificate_count,")?;
+        Ok(())
+    }
+    fn write_csv_value<T: ?Sized + std::io::Write>(
+        &self,
+        writer: &mut BufWriter<T>,
+        _flow_times: &FlowTimes,
+    ) -> Result<(), Error> {
+        match &self.client_hello {
+            Some(hello) => {
+                write!(writer, "{},", 1)?;
+                write!(
+                    writer,
+                    "{},",
+                    escape_text(hello.server_name.as_deref().unwrap_or_default())
+                )?;
+                Self::write_list(
+                    writer,
+                    hello.offered_versions().into_iter().map(version_name),
+                )?;
+                write!(writer, "{},", hello.cipher_suite_count())?;
+                write!(writer, "{},", hello.extension_count())?;
+                Self::write_list(
+                    writer,
+                    hello
+                        .alpn
+                        .iter()
+                        .map(|p| escape_text(p).replace('-', "\\x2d")),
+                )?;
+                write!(writer, "{},{},", hello.ja3(), hello.ja4())?;
+            }
+            None => write!(writer, "0,,,0,0,,,,")?,
+        }
+
+        match &self.server_hello {
+            Some(hello) => {
+                write!(writer, "{},", 1)?;
+                write!(writer, "{},", version_name(hello.selected_version()))?;
+                write!(writer, "{},", hello.cipher_suite)?;
+                write!(
+                    writer,
+                    "{},",
+                    escape_text(hello.alpn.as_deref().unwrap_or_default())
+                )?;
+                write!(writer, "{},", hello.ja3s())?;
+            }
+            None => write!(writer, "0,,0,,,")?,
+        }
+
+        let certificate_count = self.certificate_count.unwrap_or(0);
+        write!(writer, "{},", u8::from(certificate_count != 0))?;
+        write!(writer, "{},", certificate_count)?;
+        Ok(())
+    }
+}
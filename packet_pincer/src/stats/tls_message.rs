@@ -0,0 +1,475 @@
+use super::{
+    byte_reader::ByteReader,
+    digest::{md5, sha256, to_hex},
+};
+
+/// Record content type of handshake messages
+const HANDSHAKE: u8 = 22;
+
+/// Largest record length allowed, including the expansion of compression and
+/// encryption
+const MAX_RECORD_LEN: usize = 18432;
+
+/// Largest handshake message buffered
+const MAX_HANDSHAKE_LEN: usize = 65536;
+
+pub const CLIENT_HELLO: u8 = 1;
+pub const SERVER_HELLO: u8 = 2;
+pub const CERTIFICATE: u8 = 11;
+pub const SERVER_HELLO_DONE: u8 = 14;
+
+const SERVER_NAME: u16 = 0;
+const SUPPORTED_GROUPS: u16 = 10;
+const EC_POINT_FORMATS: u16 = 11;
+const SIGNATURE_ALGORITHMS: u16 = 13;
+const ALPN: u16 = 16;
+const SUPPORTED_VERSIONS: u16 = 43;
+
+pub const TLS_1_3: u16 = 0x0304;
+
+/// Check if the value is one of the reserved GREASE values of RFC 8701, that
+/// are ignored by the fingerprints
+fn is_grease(value: u16) -> bool {
+    value & 0x0F0F == 0x0A0A && value >> 8 == value & 0xFF
+}
+
+fn without_grease(values: &[u16]) -> Vec<u16> {
+    values.iter().copied().filter(|v| !is_grease(*v)).collect()
+}
+
+/// Short name of a protocol version
+pub fn version_name(version: u16) -> String {
+    match version {
+        0x0300 => "ssl3.0".to_string(),
+        0x0301 => "tls1.0".to_string(),
+        0x0302 => "tls1.1".to_string(),
+        0x0303 => "tls1.2".to_string(),
+        0x0304 => "tls1.3".to_string(),
+        _ => format!("0x{:04x}", version),
+    }
+}
+
+/// The fields of a ClientHello used by the fingerprints
+#[derive(Debug, Clone, Default)]
+pub struct ClientHello {
+    pub legacy_version: u16,
+    pub cipher_suites: Vec<u16>,
+    /// Extension types, in the order they were sent
+    pub extensions: Vec<u16>,
+    pub server_name: Option<Vec<u8>>,
+    pub supported_groups: Vec<u16>,
+    pub ec_point_formats: Vec<u8>,
+    pub signature_algorithms: Vec<u16>,
+    pub alpn: Vec<Vec<u8>>,
+    pub supported_versions: Vec<u16>,
+}
+
+impl ClientHello {
+    pub fn parse(body: &[u8]) -> Option<ClientHello> {
+        let mut reader = ByteReader::new(body);
+        let mut hello = ClientHello {
+            legacy_version: reader.u16()?,
+            ..Default::default()
+        };
+        reader.bytes(32)?;
+        reader.prefixed(1)?;
+        hello.cipher_suites = reader.prefixed(2)?.u16_list()?;
+        reader.prefixed(1)?;
+
+        // Hellos without extensions are valid
+        let Some(mut extensions) = reader.prefixed(2) else {
+            return Some(hello);
+        };
+        while !extensions.is_empty() {
+            let extension_type = extensions.u16()?;
+            let mut data = extensions.prefixed(2)?;
+            hello.extensions.push(extension_type);
+
+            match extension_type {
+                SERVER_NAME => {
+                    let mut names = data.prefixed(2)?;
+                    if names.u8()? == 0 {
+                        hello.server_name = Some(names.prefixed_bytes(2)?.to_vec());
+                    }
+                }
+                SUPPORTED_GROUPS => hello.supported_groups = data.prefixed(2)?.u16_list()?,
+                EC_POINT_FORMATS => hello.ec_point_formats = data.prefixed_bytes(1)?.to_vec(),
+                SIGNATURE_ALGORITHMS => {
+                    hello.signature_algorithms = data.prefixed(2)?.u16_list()?
+                }
+                ALPN => {
+                    let mut protocols = data.prefixed(2)?;
+                    while !protocols.is_empty() {
+                        hello.alpn.push(protocols.prefixed_bytes(1)?.to_vec());
+                    }
+                }
+                SUPPORTED_VERSIONS => hello.supported_versions = data.prefixed(1)?.u16_list()?,
+                _ => {}
+            }
+        }
+        Some(hello)
+    }
+
+    /// Versions offered by the client, without GREASE values
+    pub fn offered_versions(&self) -> Vec<u16> {
+        if self.supported_versions.is_empty() {
+            vec![self.legacy_version]
+        } else {
+            without_grease(&self.supported_versions)
+        }
+    }
+
+    /// Number of cipher suites offered, without GREASE values
+    pub fn cipher_suite_count(&self) -> usize {
+        without_grease(&self.cipher_suites).len()
+    }
+
+    /// Number of extensions sent, without GREASE values
+    pub fn extension_count(&self) -> usize {
+        without_grease(&self.extensions).len()
+    }
+
+    pub fn ja3_string(&self) -> String {
+        let join = |values: Vec<String>| values.join("-");
+        format!(
+            "{},{},{},{},{}",
+            self.legacy_version,
+            join(
+                without_grease(&self.cipher_suites)
+                    .iter()
+                    .map(u16::to_string)
+                    .collect()
+            ),
+            join(
+                without_grease(&self.extensions)
+                    .iter()
+                    .map(u16::to_string)
+                    .collect()
+            ),
+            join(
+                without_grease(&self.supported_groups)
+                    .iter()
+                    .map(u16::to_string)
+                    .collect()
+            ),
+            join(self.ec_point_formats.iter().map(u8::to_string).collect()),
+        )
+    }
+
+    pub fn ja3(&self) -> String {
+        to_hex(&md5(self.ja3_string().as_bytes()))
+    }
+
+    /// The JA4 fingerprint of the hello, sent over TCP
+    pub fn ja4(&self) -> String {
+        let version = match self.offered_versions().into_iter().max() {
+            Some(0x0304) => "13",
+            Some(0x0303) => "12",
+            Some(0x0302) => "11",
+            Some(0x0301) => "10",
+            Some(0x0300) => "s3",
+            _ => "00",
+        };
+        let sni = if self.server_name.is_some() { 'd' } else { 'i' };
+        let cipher_suites = without_grease(&self.cipher_suites);
+        let extensions = without_grease(&self.extensions);
+
+        let alpn = match self.alpn.first() {
+            Some(protocol) if !protocol.is_empty() => {
+                let first = protocol[0];
+                let last = protocol[protocol.len() - 1];
+                if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+                    format!("{}{}", char::from(first), char::from(last))
+                } else {
+                    let hex = to_hex(protocol);
+                    format!("{}{}", &hex[..1], &hex[hex.len() - 1..])
+                }
+            }
+            _ => "00".to_string(),
+        };
+
+        let mut sorted_cipher_suites = cipher_suites.clone();
+        sorted_cipher_suites.sort_unstable();
+        let mut sorted_extensions: Vec<u16> = extensions
+            .iter()
+            .copied()
+            .filter(|e| *e != SERVER_NAME && *e != ALPN)
+            .collect();
+        sorted_extensions.sort_unstable();
+
+        let hex_list = |values: &[u16]| {
+            values
+                .iter()
+                .map(|value| format!("{:04x}", value))
+                .collect::<Vec<String>>()
+                .join(",")
+        };
+        let mut extensions_text = hex_list(&sorted_extensions);
+        let signature_algorithms = without_grease(&self.signature_algorithms);
+        if !signature_algorithms.is_empty() {
+            extensions_text = format!("{}_{}", extensions_text, hex_list(&signature_algorithms));
+        }
+
+        format!(
+            "t{}{}{:02}{:02}{}_{}_{}",
+            version,
+            sni,
+            cipher_suites.len().min(99),
+            extensions.len().min(99),
+            alpn,
+            ja4_hash(&hex_list(&sorted_cipher_suites)),
+            ja4_hash(if sorted_extensions.is_empty() {
+                ""
+            } else {
+                &extensions_text
+            }),
+        )
+    }
+}
+
+/// Truncated hash of a JA4 section, all zeros when empty
+fn ja4_hash(text: &str) -> String {
+    if text.is_empty() {
+        "000000000000".to_string()
+    } else {
+        to_hex(&sha256(text.as_bytes()))[..12].to_string()
+    }
+}
+
+/// The fields of a ServerHello used by the fingerprints
+#[derive(Debug, Clone, Default)]
+pub struct ServerHello {
+    pub legacy_version: u16,
+    pub cipher_suite: u16,
+    pub extensions: Vec<u16>,
+    pub alpn: Option<Vec<u8>>,
+    pub supported_version: Option<u16>,
+}
+
+impl ServerHello {
+    pub fn parse(body: &[u8]) -> Option<ServerHello> {
+        let mut reader = ByteReader::new(body);
+        let mut hello = ServerHello {
+            legacy_version: reader.u16()?,
+            ..Default::default()
+        };
+        reader.bytes(32)?;
+        reader.prefixed(1)?;
+        hello.cipher_suite = reader.u16()?;
+        reader.u8()?;
+
+        let Some(mut extensions) = reader.prefixed(2) else {
+            return Some(hello);
+        };
+        while !extensions.is_empty() {
+            let extension_type = extensions.u16()?;
+            let mut data = extensions.prefixed(2)?;
+            hello.extensions.push(extension_type);
+
+            match extension_type {
+                ALPN => {
+                    let mut protocols = data.prefixed(2)?;
+                    hello.alpn = Some(protocols.prefixed_bytes(1)?.to_vec());
+                }
+                SUPPORTED_VERSIONS => hello.supported_version = Some(data.u16()?),
+                _ => {}
+            }
+        }
+        Some(hello)
+    }
+
+    /// Version selected by the server
+    pub fn selected_version(&self) -> u16 {
+        self.supported_version.unwrap_or(self.legacy_version)
+    }
+
+    pub fn ja3s_string(&self) -> String {
+        let extensions: Vec<String> = without_grease(&self.extensions)
+            .iter()
+            .map(u16::to_string)
+            .collect();
+        format!(
+            "{},{},{}",
+            self.legacy_version,
+            self.cipher_suite,
+            extensions.join("-")
+        )
+    }
+
+    pub fn ja3s(&self) -> String {
+        to_hex(&md5(self.ja3s_string().as_bytes()))
+    }
+}
+
+/// Number of certificates of a Certificate message before TLS 1.3
+pub fn certificate_count(body: &[u8]) -> Option<usize> {
+    let mut reader = ByteReader::new(body);
+    let len = reader.u24()?;
+    let mut certificates = ByteReader::new(reader.bytes(len)?);
+    let mut count = 0;
+    while !certificates.is_empty() {
+        let len = certificates.u24()?;
+        certificates.bytes(len)?;
+        count += 1;
+    }
+    Some(count)
+}
+
+/// Reader of the cleartext handshake messages of a direction of a TCP stream
+#[derive(Debug, Clone, Default)]
+pub struct HandshakeReader {
+    records: Vec<u8>,
+    handshake: Vec<u8>,
+    /// Whether the cleartext handshake ended, bytes were missed or the stream
+    /// is not TLS
+    done: bool,
+}
+
+impl HandshakeReader {
+    /// Include the next bytes of the stream and call `consume` with the type
+    /// and body of each handshake message they complete. Reading stops when
+    /// `consume` returns false
+    pub fn include<F: FnMut(u8, &[u8]) -> bool>(&mut self, gap: bool, data: &[u8], consume: F) {
+        if self.done {
+            return;
+        }
+        if gap {
+            self.finish();
+            return;
+        }
+
+        self.records.extend_from_slice(data);
+        let mut start = 0;
+        let mut handshake_ended = false;
+        while let Some(header) = self.records.get(start..start + 5) {
+            let len = usize::from(u16::from_be_bytes([header[3], header[4]]));
+            if header[0] != HANDSHAKE || header[1] != 3 || len > MAX_RECORD_LEN {
+                handshake_ended = true;
+                break;
+            }
+            let Some(fragment) = self.records.get(start + 5..start + 5 + len) else {
+                break;
+            };
+            self.handshake.extend_from_slice(fragment);
+            start += 5 + len;
+        }
+        self.records.drain(..start);
+
+        self.read_messages(consume);
+        if handshake_ended {
+            self.finish();
+        }
+    }
+
+    fn read_messages<F: FnMut(u8, &[u8]) -> bool>(&mut self, mut consume: F) {
+        let mut start = 0;
+        while let Some(header) = self.handshake.get(start..start + 4) {
+            let message_type = header[0];
+            let len =
+                usize::from(header[1]) << 16 | usize::from(header[2]) << 8 | usize::from(header[3]);
+            if len > MAX_HANDSHAKE_LEN {
+                self.finish();
+                return;
+            }
+            let Some(body) = self.handshake.get(start + 4..start + 4 + len) else {
+                break;
+            };
+            if !consume(message_type, body) {
+                self.finish();
+                return;
+            }
+            start += 4 + len;
+        }
+        self.handshake.drain(..start);
+    }
+
+    fn finish(&mut self) {
+        self.done = true;
+        self.records = Vec::new();
+        self.handshake = Vec::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_hello_body() -> Vec<u8> {
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0; 32]);
+        body.push(0);
+        // GREASE and two cipher suites
+        body.extend_from_slice(&[0x00, 0x06, 0x0A, 0x0A, 0x13, 0x01, 0xC0, 0x2F]);
+        body.extend_from_slice(&[0x01, 0x00]);
+
+        let extensions: Vec<u8> = [
+            // server name: example.com
+            &[0x00, 0x00, 0x00, 0x10, 0x00, 0x0E, 0x00, 0x00, 0x0B][..],
+            b"example.com",
+            // supported groups: GREASE, x25519, secp256r1
+            &[
+                0x00, 0x0A, 0x00, 0x08, 0x00, 0x06, 0x1A, 0x1A, 0x00, 0x1D, 0x00, 0x17,
+            ],
+            // ec point formats: uncompressed
+            &[0x00, 0x0B, 0x00, 0x02, 0x01, 0x00],
+            // signature algorithms
+            &[0x00, 0x0D, 0x00, 0x06, 0x00, 0x04, 0x04, 0x03, 0x08, 0x04],
+            // alpn: h2, http/1.1
+            &[0x00, 0x10, 0x00, 0x0E, 0x00, 0x0C, 0x02, b'h', b'2', 0x08],
+            b"http/1.1",
+            // supported versions: tls1.3, tls1.2
+            &[0x00, 0x2B, 0x00, 0x05, 0x04, 0x03, 0x04, 0x03, 0x03],
+        ]
+        .concat();
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend_from_slice(&extensions);
+        body
+    }
+
+    #[test]
+    fn test_client_hello() {
+        let hello = ClientHello::parse(&client_hello_body()).unwrap();
+
+        assert_eq!(hello.server_name.as_deref(), Some(&b"example.com"[..]));
+        assert_eq!(hello.alpn, vec![b"h2".to_vec(), b"http/1.1".to_vec()]);
+        assert_eq!(hello.offered_versions(), vec![0x0304, 0x0303]);
+        assert_eq!(hello.cipher_suite_count(), 2);
+        assert_eq!(hello.extension_count(), 6);
+        assert_eq!(
+            hello.ja3_string(),
+            "771,4865-49199,0-10-11-13-16-43,29-23,0"
+        );
+        assert_eq!(hello.ja3().len(), 32);
+
+        let ja4 = hello.ja4();
+        assert!(ja4.starts_with("t13d0206h2_"));
+        assert_eq!(ja4.len(), 36);
+    }
+
+    #[test]
+    fn test_handshake_reader() {
+        let body = client_hello_body();
+        let mut message = vec![CLIENT_HELLO, 0, 0, body.len() as u8];
+        message.extend_from_slice(&body);
+        // Split on two records, the first one split on two segments
+        let mut stream = vec![HANDSHAKE, 3, 1, 0, 10];
+        stream.extend_from_slice(&message[..10]);
+        stream.extend_from_slice(&[HANDSHAKE, 3, 1, 0, (message.len() - 10) as u8]);
+        stream.extend_from_slice(&message[10..]);
+        stream.extend_from_slice(&[20, 3, 3, 0, 1, 1]);
+
+        let mut v = HandshakeReader::default();
+        let mut messages = Vec::new();
+        v.include(false, &stream[..7], |t, b| {
+            messages.push((t, b.len()));
+            true
+        });
+        assert!(messages.is_empty());
+        v.include(false, &stream[7..], |t, b| {
+            messages.push((t, b.len()));
+            true
+        });
+        assert_eq!(messages, vec![(CLIENT_HELLO, body.len())]);
+        assert!(v.done);
+    }
+}
@@ -1,11 +1,8 @@
-use super::{running_stat::RunningStat, FlowStat, FlowTimes};
+use super::{running_stat::RunningStat, FlowStat, FlowTimes, StatSettings};
 use crate::{packet_flow::FragmentReasemblyInformation, packet_parse::TransportFlowIdentifier};
-use std::{
-    io::{BufWriter, Error, Write},
-    net::IpAddr,
-};
+use std::io::{BufWriter, Error, Write};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Transport {
     forward_transport_header_bytes: RunningStat,
     forward_transport_payload_bytes: RunningStat,
@@ -20,6 +17,7 @@ pub struct Transport {
 
 impl FlowStat for Transport {
     fn from_packet(
+        _settings: &StatSettings,
         _identifier: &TransportFlowIdentifier,
         _flow_times: &FlowTimes,
         _packet_header: &pcap::PacketHeader,
@@ -84,17 +82,7 @@ impl FlowStat for Transport {
         sliced_packet: &etherparse::SlicedPacket,
         _reasembly_information: Option<&FragmentReasemblyInformation>,
     ) {
-        let is_forward = match &sliced_packet.net {
-            Some(header) => match header {
-                etherparse::NetSlice::Ipv4(v) => {
-                    IpAddr::V4(v.header().source_addr()) == identifier.source_ip
-                }
-                etherparse::NetSlice::Ipv6(v) => {
-                    IpAddr::V6(v.header().source_addr()) == identifier.source_ip
-                }
-            },
-            None => panic!("Unexpected sliced packet without net layer"),
-        };
+        let is_forward = identifier.is_forward(sliced_packet);
 
         if let Some(transport_slice) = &sliced_packet.transport {
             match (transport_slice, is_forward) {
@@ -167,6 +155,7 @@ impl FlowStat for Transport {
 
     fn write_csv_header<T: ?Sized + std::io::Write>(
         writer: &mut BufWriter<T>,
+        _settings: &StatSettings,
     ) -> Result<(), Error> {
         write!(writer, "forward_transport_header_bytes_sum,")?;
         write!(writer, "forward_transport_payload_bytes_mean,")?;
@@ -0,0 +1,429 @@
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+
+/// Ordered bytes of a direction of a TCP stream
+#[derive(Debug, Clone)]
+pub struct StreamChunk<'a> {
+    /// Whether the bytes were sent on the forward direction of the flow
+    pub is_forward: bool,
+    /// Time of the packet that completed the chunk
+    pub time: DateTime<Utc>,
+    /// Position of the first byte since the start of the direction stream
+    pub offset: u64,
+    /// Number of bytes missing right before the chunk, because they were
+    /// not captured or did not fit on the buffer
+    pub gap: u64,
+    /// The bytes of the chunk
+    pub data: &'a [u8],
+}
+
+/// Reassembly state of a single direction of a TCP stream
+#[derive(Debug, Clone)]
+pub struct DirectionStream {
+    /// Sequence number of the next byte to deliver, unknown until the first
+    /// segment when the capture starts mid-connection
+    next_seq: Option<u32>,
+    /// Position of `next_seq` since the start of the stream
+    offset: u64,
+    /// Bytes skipped and not reported yet
+    pending_gap: u64,
+    /// Segments received ahead of `next_seq` by their position, without
+    /// overlaps between them
+    segments: BTreeMap<u64, Vec<u8>>,
+    buffered_bytes: usize,
+    gap_bytes: u64,
+    overlap_bytes: u64,
+}
+
+impl DirectionStream {
+    fn new() -> DirectionStream {
+        DirectionStream {
+            next_seq: None,
+            offset: 0,
+            pending_gap: 0,
+            segments: BTreeMap::new(),
+            buffered_bytes: 0,
+            gap_bytes: 0,
+            overlap_bytes: 0,
+        }
+    }
+
+    /// Number of bytes delivered in order
+    pub fn delivered_bytes(&self) -> u64 {
+        self.offset - self.gap_bytes
+    }
+
+    /// Number of bytes skipped because they were missing
+    pub fn gap_bytes(&self) -> u64 {
+        self.gap_bytes
+    }
+
+    /// Number of bytes received more than once, on retransmissions or
+    /// overlapping segments. The first copy received is the one delivered
+    pub fn overlap_bytes(&self) -> u64 {
+        self.overlap_bytes
+    }
+
+    /// Number of bytes received out of order and waiting for the missing ones
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffered_bytes
+    }
+
+    /// Position of the given sequence number since the start of the stream,
+    /// if it is after the next byte to deliver
+    fn position_ahead(&self, seq: u32) -> Option<u64> {
+        let distance = seq.wrapping_sub(self.next_seq?) as i32;
+        (0 < distance).then(|| self.offset + distance as u64)
+    }
+
+    fn advance(&mut self, len: u64) {
+        self.offset += len;
+        self.next_seq = self.next_seq.map(|seq| seq.wrapping_add(len as u32));
+    }
+
+    fn deliver<F: FnMut(&StreamChunk)>(
+        &mut self,
+        is_forward: bool,
+        time: DateTime<Utc>,
+        data: &[u8],
+        consume: &mut F,
+    ) {
+        consume(&StreamChunk {
+            is_forward,
+            time,
+            offset: self.offset,
+            gap: self.pending_gap,
+            data,
+        });
+        self.pending_gap = 0;
+        self.advance(data.len() as u64);
+    }
+
+    /// Deliver the buffered segments that became contiguous
+    fn deliver_buffered<F: FnMut(&StreamChunk)>(
+        &mut self,
+        is_forward: bool,
+        time: DateTime<Utc>,
+        consume: &mut F,
+    ) {
+        while let Some(entry) = self.segments.first_entry() {
+            if self.offset < *entry.key() {
+                break;
+            }
+            let (start, data) = entry.remove_entry();
+            self.buffered_bytes -= data.len();
+
+            let skipped = usize::try_from(self.offset - start).unwrap();
+            if skipped < data.len() {
+                self.deliver(is_forward, time, &data[skipped..], consume);
+            }
+        }
+    }
+
+    /// Skip the missing bytes up to the given position, or up to the first
+    /// buffered segment if it is before
+    fn skip_gap<F: FnMut(&StreamChunk)>(
+        &mut self,
+        is_forward: bool,
+        time: DateTime<Utc>,
+        until: u64,
+        consume: &mut F,
+    ) {
+        let until = match self.segments.first_key_value() {
+            Some((start, _)) => std::cmp::min(*start, until),
+            None => until,
+        };
+        if self.offset < until {
+            let skipped = until - self.offset;
+            self.gap_bytes += skipped;
+            self.pending_gap += skipped;
+            self.advance(skipped);
+        }
+        self.deliver_buffered(is_forward, time, consume);
+    }
+
+    /// Buffer the parts of a segment not received yet
+    fn buffer(&mut self, start: u64, data: &[u8]) {
+        let end = start + data.len() as u64;
+        let mut position = start;
+
+        let overlapping: Vec<(u64, u64)> = self
+            .segments
+            .range(..end)
+            .map(|(s, d)| (*s, *s + d.len() as u64))
+            .filter(|(_, e)| start < *e)
+            .collect();
+        for (segment_start, segment_end) in overlapping {
+            if position < segment_start {
+                self.insert(
+                    position,
+                    &data[Self::index(start, position)..Self::index(start, segment_start)],
+                );
+            }
+            let overlap_end = std::cmp::min(segment_end, end);
+            self.overlap_bytes += overlap_end - std::cmp::max(position, segment_start);
+            position = std::cmp::max(position, overlap_end);
+        }
+        if position < end {
+            self.insert(position, &data[Self::index(start, position)..]);
+        }
+    }
+
+    fn index(start: u64, position: u64) -> usize {
+        usize::try_from(position - start).unwrap()
+    }
+
+    fn insert(&mut self, start: u64, data: &[u8]) {
+        self.buffered_bytes += data.len();
+        self.segments.insert(start, data.to_vec());
+    }
+
+    fn include<F: FnMut(&StreamChunk)>(
+        &mut self,
+        is_forward: bool,
+        time: DateTime<Utc>,
+        data_seq: u32,
+        payload: &[u8],
+        consume: &mut F,
+    ) {
+        let next_seq = *self.next_seq.get_or_insert(data_seq);
+        let distance = i64::from(data_seq.wrapping_sub(next_seq) as i32);
+        let len = payload.len() as i64;
+
+        if distance + len <= 0 {
+            // Everything was already delivered
+            self.overlap_bytes += payload.len() as u64;
+        } else if distance <= 0 {
+            let skipped = usize::try_from(-distance).unwrap();
+            self.overlap_bytes += skipped as u64;
+            self.buffer(self.offset, &payload[skipped..]);
+            self.deliver_buffered(is_forward, time, consume);
+        } else {
+            self.buffer(self.offset + distance as u64, payload);
+        }
+    }
+}
+
+/// The fields of a TCP segment used by the reassembly
+#[derive(Debug, Clone)]
+struct Segment<'a> {
+    is_forward: bool,
+    time: DateTime<Utc>,
+    sequence_number: u32,
+    /// The acknowledgment number, if it is meaningful
+    acknowledgment_number: Option<u32>,
+    syn: bool,
+    payload: &'a [u8],
+}
+
+/// Reassembler of the byte streams of both directions of a TCP connection.
+/// The bytes are delivered in order as soon as they are contiguous, and the
+/// first copy received is kept on retransmissions and overlaps. The bytes
+/// received out of order are buffered up to a limit for the whole flow. When
+/// it is exceeded, or when the receiver acknowledges bytes that were not
+/// captured, the missing bytes are skipped and reported as a gap
+#[derive(Debug, Clone)]
+pub struct TcpStreamReassembler {
+    forward: DirectionStream,
+    backward: DirectionStream,
+    buffer_limit: usize,
+}
+
+impl TcpStreamReassembler {
+    /// Create a reassembler buffering at most `buffer_limit` bytes received
+    /// out of order
+    pub fn new(buffer_limit: usize) -> TcpStreamReassembler {
+        TcpStreamReassembler {
+            forward: DirectionStream::new(),
+            backward: DirectionStream::new(),
+            buffer_limit,
+        }
+    }
+
+    /// Get the reassembly state of the forward direction
+    pub fn forward(&self) -> &DirectionStream {
+        &self.forward
+    }
+
+    /// Get the reassembly state of the backward direction
+    pub fn backward(&self) -> &DirectionStream {
+        &self.backward
+    }
+
+    /// Include a TCP segment, passing the ordered bytes it completes to
+    /// `consume`
+    pub fn include<F: FnMut(&StreamChunk)>(
+        &mut self,
+        is_forward: bool,
+        time: DateTime<Utc>,
+        tcp_slice: &etherparse::TcpSlice,
+        mut consume: F,
+    ) {
+        let acknowledgment_number =
+            (tcp_slice.ack() && !tcp_slice.rst()).then(|| tcp_slice.acknowledgment_number());
+
+        let segment = Segment {
+            is_forward,
+            time,
+            sequence_number: tcp_slice.sequence_number(),
+            acknowledgment_number,
+            syn: tcp_slice.syn(),
+            payload: tcp_slice.payload(),
+        };
+        self.include_segment(&segment, &mut consume);
+    }
+
+    fn include_segment<F: FnMut(&StreamChunk)>(&mut self, segment: &Segment, consume: &mut F) {
+        let Segment {
+            is_forward,
+            time,
+            sequence_number,
+            acknowledgment_number,
+            syn,
+            payload,
+        } = *segment;
+        let (current, peer) = if is_forward {
+            (&mut self.forward, &mut self.backward)
+        } else {
+            (&mut self.backward, &mut self.forward)
+        };
+
+        // Bytes acknowledged by the peer were received even if not captured
+        if let Some(acknowledged) = acknowledgment_number.and_then(|a| peer.position_ahead(a)) {
+            peer.skip_gap(!is_forward, time, acknowledged, consume);
+        }
+
+        // SYN consumes a sequence number before the data
+        let data_seq = if syn {
+            if current.next_seq.is_none() {
+                current.next_seq = Some(sequence_number.wrapping_add(1));
+            }
+            sequence_number.wrapping_add(1)
+        } else {
+            sequence_number
+        };
+        if !payload.is_empty() {
+            current.include(is_forward, time, data_seq, payload, consume);
+        }
+
+        // Skip gaps until the buffered bytes fit on the limit
+        while self.buffer_limit < self.forward.buffered_bytes + self.backward.buffered_bytes {
+            let (direction, direction_is_forward) = if (is_forward
+                && self.forward.buffered_bytes != 0)
+                || self.backward.buffered_bytes == 0
+            {
+                (&mut self.forward, true)
+            } else {
+                (&mut self.backward, false)
+            };
+            direction.skip_gap(direction_is_forward, time, u64::MAX, consume);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn include(
+        v: &mut TcpStreamReassembler,
+        is_forward: bool,
+        seq: u32,
+        ack: Option<u32>,
+        syn: bool,
+        payload: &[u8],
+        chunks: &mut Vec<(bool, u64, u64, Vec<u8>)>,
+    ) {
+        let segment = Segment {
+            is_forward,
+            time: DateTime::from_timestamp(0, 0).unwrap(),
+            sequence_number: seq,
+            acknowledgment_number: ack,
+            syn,
+            payload,
+        };
+        v.include_segment(&segment, &mut |c: &StreamChunk| {
+            chunks.push((c.is_forward, c.offset, c.gap, c.data.to_vec()))
+        });
+    }
+
+    #[test]
+    fn test_ordering_and_overlaps() {
+        let mut v = TcpStreamReassembler::new(1024);
+        let mut chunks = Vec::new();
+        let isn = u32::MAX - 2;
+
+        include(&mut v, true, isn, None, true, b"", &mut chunks);
+        // Out of order segment crossing the sequence wraparound
+        include(
+            &mut v,
+            true,
+            isn.wrapping_add(4),
+            None,
+            false,
+            b"defg",
+            &mut chunks,
+        );
+        assert!(chunks.is_empty());
+        assert_eq!(v.forward().buffered_bytes(), 4);
+
+        // Overlaps with the buffered segment, which is kept
+        include(
+            &mut v,
+            true,
+            isn.wrapping_add(1),
+            None,
+            false,
+            b"abcDE",
+            &mut chunks,
+        );
+        // Retransmission
+        include(
+            &mut v,
+            true,
+            isn.wrapping_add(1),
+            None,
+            false,
+            b"ab",
+            &mut chunks,
+        );
+
+        assert_eq!(
+            chunks,
+            vec![
+                (true, 0, 0, b"abc".to_vec()),
+                (true, 3, 0, b"defg".to_vec()),
+            ]
+        );
+        assert_eq!(v.forward().delivered_bytes(), 7);
+        assert_eq!(v.forward().overlap_bytes(), 4);
+        assert_eq!(v.forward().buffered_bytes(), 0);
+    }
+
+    #[test]
+    fn test_gaps() {
+        let mut v = TcpStreamReassembler::new(4);
+        let mut chunks = Vec::new();
+
+        // Starts mid-connection
+        include(&mut v, false, 100, None, false, b"ab", &mut chunks);
+        include(&mut v, false, 104, None, false, b"ef", &mut chunks);
+
+        // The receiver acknowledges the missing bytes
+        include(&mut v, true, 1, Some(106), false, b"", &mut chunks);
+
+        // The buffer limit is exceeded
+        include(&mut v, false, 108, None, false, b"ijklm", &mut chunks);
+
+        assert_eq!(
+            chunks,
+            vec![
+                (false, 0, 0, b"ab".to_vec()),
+                (false, 4, 2, b"ef".to_vec()),
+                (false, 8, 2, b"ijklm".to_vec()),
+            ]
+        );
+        assert_eq!(v.backward().gap_bytes(), 4);
+        assert_eq!(v.backward().delivered_bytes(), 9);
+    }
+}